@@ -0,0 +1,95 @@
+//! Timings of the interpreter hot paths, run with `cargo bench --bench interpreter`.
+//!
+//! A plain timing loop rather than a benchmark framework, so it builds
+//! without extra dependencies. Each case runs for about a second after a
+//! short warm-up; compare the ns/iter figures between builds. Pass a name to
+//! run only the cases containing it, e.g. `cargo bench -- draw`.
+
+use std::env;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use chip_8_emulate::cpu::{Cpu, Engine};
+use chip_8_emulate::headless;
+use chip_8_emulate::instruction::Instruction;
+
+const WARM_UP: Duration = Duration::from_millis(200);
+const MEASURE: Duration = Duration::from_secs(1);
+
+/// Times `routine`; it does `per_call` iterations per call.
+fn bench(filter: &Option<String>, name: &str, per_call: u64, mut routine: impl FnMut()) {
+    if filter
+        .as_ref()
+        .is_some_and(|filter| !name.contains(filter.as_str()))
+    {
+        return;
+    }
+
+    let started = Instant::now();
+    while started.elapsed() < WARM_UP {
+        routine();
+    }
+
+    let mut calls = 0u64;
+    let started = Instant::now();
+    while started.elapsed() < MEASURE {
+        routine();
+        calls += 1;
+    }
+    let per_iter = started.elapsed().as_nanos() as f64 / (calls * per_call) as f64;
+    println!("{:<24} {:>10.2} ns/iter", name, per_iter);
+}
+
+/// A machine running `instruction` over and over: 100 copies and a jump back.
+fn looping(setup: &[u8], instruction: u16) -> Cpu {
+    let mut rom = setup.to_vec();
+    let start = 0x200 + rom.len() as u16;
+    for _ in 0..100 {
+        rom.extend_from_slice(&instruction.to_be_bytes());
+    }
+    rom.extend_from_slice(&(0x1000 | start).to_be_bytes());
+
+    let mut cpu = headless::machine();
+    cpu.load_rom(&rom).unwrap();
+    cpu
+}
+
+/// Runs the 101 instruction loop once, after the setup instructions.
+fn run_loop(cpu: &mut Cpu) {
+    for _ in 0..101 {
+        cpu.step().unwrap();
+    }
+}
+
+fn main() {
+    // cargo passes --bench, anything else is a filter
+    let filter = env::args().skip(1).find(|arg| !arg.starts_with("--"));
+
+    bench(&filter, "decode all opcodes", 0x10000, || {
+        for opcode in 0..=u16::MAX {
+            let _ = black_box(Instruction::decode(black_box(opcode)));
+        }
+    });
+
+    // I = font, then draw a 5 row sprite at (V0, V0)
+    let mut cpu = looping(&[0xA0, 0x50], 0xD005);
+    cpu.step().unwrap();
+    bench(&filter, "draw (Dxyn)", 101, || {
+        run_loop(black_box(&mut cpu))
+    });
+
+    // I = 0x300, V2 = 0xFE, then store its BCD digits
+    let mut cpu = looping(&[0xA3, 0x00, 0x62, 0xFE], 0xF233);
+    cpu.step().unwrap();
+    cpu.step().unwrap();
+    bench(&filter, "bcd (Fx33)", 101, || run_loop(black_box(&mut cpu)));
+
+    let mut cpu = looping(&[], 0x7001);
+    bench(&filter, "add (7xkk)", 101, || run_loop(black_box(&mut cpu)));
+
+    let mut cpu = looping(&[], 0x7001);
+    cpu.engine = Engine::Cached;
+    bench(&filter, "add (7xkk), cached", 101, || {
+        run_loop(black_box(&mut cpu))
+    });
+}
@@ -0,0 +1,56 @@
+//! Sizes of the `min-size` builds, run with `cargo bench --bench size`.
+//!
+//! Builds the `chip8` binary and the `core` example, which is little more
+//! than the emulator core, with the `min-size` profile in a target directory
+//! of their own. Prints their sizes and fails when one has grown past its
+//! budget; raise a budget on purpose, saying why in the commit.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::{self, Command};
+
+/// Bytes, with some headroom over the sizes when they were set: 761K and
+/// 331K, of which about 290K is the standard library. chip8's went up from
+/// 840K at 860K, with the fuzzer, network play and the profiler, then to
+/// 920K at 940K with the session log and crash bundles.
+const BUDGETS: &[(&str, u64)] = &[("chip8", 940_000), ("examples/core", 365_000)];
+
+fn main() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // the outer cargo still holds the lock on the usual target directory
+    let target_dir = manifest_dir.join("target").join("size");
+    let status = Command::new(env!("CARGO"))
+        .current_dir(manifest_dir)
+        .args(["build", "--quiet", "--profile", "min-size"])
+        .args(["--bin", "chip8", "--example", "core", "--target-dir"])
+        .arg(&target_dir)
+        .status();
+    if !status.is_ok_and(|status| status.success()) {
+        eprintln!("the min-size build failed");
+        process::exit(1);
+    }
+
+    let mut over = false;
+    for &(name, budget) in BUDGETS {
+        let path = target_dir
+            .join("min-size")
+            .join(format!("{}{}", name, env::consts::EXE_SUFFIX));
+        let size = match fs::metadata(&path) {
+            Ok(metadata) => metadata.len(),
+            Err(err) => {
+                eprintln!("{}: {}", path.display(), err);
+                process::exit(1);
+            }
+        };
+        let verdict = if size > budget { "OVER BUDGET" } else { "ok" };
+        println!(
+            "{:<24} {:>8} bytes, budget {:>8}  {}",
+            name, size, budget, verdict
+        );
+        over |= size > budget;
+    }
+    if over {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,47 @@
+//! The emulator core on its own: runs a ROM for some frames without a
+//! frontend, the way an embedding application would with `Cpu::poll_frame`,
+//! and prints the hash of the screen. `benches/size.rs` builds it to see how
+//! much room the core takes.
+
+use std::env;
+use std::fs;
+use std::process;
+
+use chip_8_emulate::cpu::Cpu;
+use chip_8_emulate::host::{Event, InputState};
+use chip_8_emulate::rng::Rng;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some(path) = args.first() else {
+        eprintln!("usage: core <rom> [frames]");
+        process::exit(2);
+    };
+    let frames = args.get(1).and_then(|n| n.parse().ok()).unwrap_or(60);
+
+    let rom = fs::read(path).unwrap_or_else(|err| {
+        eprintln!("{}: {}", path, err);
+        process::exit(2);
+    });
+    let mut cpu = Cpu::new();
+    cpu.rng = Rng::new(0);
+    cpu.assertions = true;
+    if let Err(err) = cpu.load_rom(&rom) {
+        eprintln!("{}: {}", path, err);
+        process::exit(3);
+    }
+
+    let mut ran = 0;
+    while ran < frames {
+        let frame = cpu.poll_frame(&InputState::default());
+        ran += 1;
+        let stopped = frame
+            .events
+            .iter()
+            .any(|event| matches!(event, Event::Halted | Event::Error(_)));
+        if stopped {
+            break;
+        }
+    }
+    println!("{} {:016x}", ran, cpu.display.hash());
+}
@@ -0,0 +1,123 @@
+//! A frontend in a hundred lines, on nothing but `emulator::Emulator`: plays
+//! a ROM in the terminal with the usual 1234/qwer/asdf/zxcv keys, and Esc
+//! quits. A starting point for writing your own, for a window or a board.
+//!
+//! ```text
+//! cargo run --example frontend -- rom.ch8
+//! ```
+
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process::{self, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chip_8_emulate::emulator::{Emulator, HEIGHT, WIDTH};
+
+/// The keyboard keys for the keypad's 0-F.
+const KEYS: &[u8; 16] = b"x123qweasdzc4rfv";
+/// Terminals only send presses, so a key counts as held this many frames.
+const HOLD_FRAMES: u8 = 6;
+
+fn main() {
+    let Some(path) = env::args().nth(1) else {
+        eprintln!("usage: frontend <rom>");
+        process::exit(2);
+    };
+    let rom = fs::read(&path).unwrap_or_else(|err| fail(&path, err));
+    let mut emulator = Emulator::new();
+    if let Err(err) = emulator.load(&rom) {
+        fail(&path, err);
+    }
+
+    let saved = stty(&["-g"]).unwrap_or_else(|err| fail("stty", err));
+    stty(&["-icanon", "-echo", "min", "1"]).unwrap_or_else(|err| fail("stty", err));
+    let result = play(&mut emulator);
+    let _ = stty(&[saved.trim()]);
+    println!("\x1b[?25h");
+    if let Err(err) = result {
+        fail(&path, err);
+    }
+}
+
+fn play(emulator: &mut Emulator) -> Result<(), String> {
+    let presses = keyboard();
+    let mut held = [0u8; 16];
+    let mut beeping = false;
+    let mut out = io::stdout().lock();
+    let mut next = Instant::now();
+    print!("\x1b[2J\x1b[?25l");
+
+    loop {
+        for byte in presses.try_iter() {
+            if byte == 0x1b {
+                return Ok(());
+            }
+            if let Some(key) = KEYS.iter().position(|&k| k == byte.to_ascii_lowercase()) {
+                held[key] = HOLD_FRAMES;
+            }
+        }
+        emulator.set_keys(std::array::from_fn(|key| held[key] > 0));
+        held = held.map(|frames| frames.saturating_sub(1));
+
+        emulator.frame().map_err(|err| err.to_string())?;
+        draw(&mut out, emulator.framebuffer()).map_err(|err| err.to_string())?;
+        // a bell each time the sound starts is as close as a terminal gets
+        if emulator.sound_active() && !beeping {
+            print!("\x07");
+        }
+        beeping = emulator.sound_active();
+
+        next += Duration::from_secs(1) / 60;
+        thread::sleep(next.saturating_duration_since(Instant::now()));
+    }
+}
+
+/// Two rows of pixels to a line of half blocks.
+fn draw(out: &mut impl Write, pixels: &[bool]) -> io::Result<()> {
+    let mut screen = String::from("\x1b[H");
+    for y in (0..HEIGHT).step_by(2) {
+        for x in 0..WIDTH {
+            let top = pixels[y * WIDTH + x];
+            let bottom = pixels[(y + 1) * WIDTH + x];
+            screen.push(match (top, bottom) {
+                (false, false) => ' ',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (true, true) => '█',
+            });
+        }
+        screen.push('\n');
+    }
+    out.write_all(screen.as_bytes())?;
+    out.flush()
+}
+
+/// Bytes typed, from a thread since reading stdin blocks.
+fn keyboard() -> mpsc::Receiver<u8> {
+    let (send, receive) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0; 16];
+        while let Ok(len @ 1..) = io::stdin().read(&mut buf) {
+            if buf[..len].iter().any(|&byte| send.send(byte).is_err()) {
+                break;
+            }
+        }
+    });
+    receive
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn fail(what: &str, err: impl std::fmt::Display) -> ! {
+    eprintln!("{}: {}", what, err);
+    process::exit(1);
+}
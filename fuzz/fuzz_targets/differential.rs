@@ -0,0 +1,12 @@
+//! Both engines against the reference interpreter, see `chip_8_emulate::fuzz`.
+
+#![no_main]
+
+use chip_8_emulate::fuzz;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Err(mismatch) = fuzz::check(data, 1000) {
+        panic!("{}", mismatch);
+    }
+});
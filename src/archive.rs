@@ -0,0 +1,177 @@
+//! ROM archives laid out like the community chip8Archive: a `programs.json`
+//! index of entries keyed by ROM id, with each ROM at `roms/<id>.ch8` next to it.
+//!
+//! ```text
+//! {
+//!   "outlaw": {
+//!     "title": "Outlaw",
+//!     "authors": ["John Earnest"],
+//!     "platform": "chip8",
+//!     "options": { "tickrate": 15 },
+//!     "sha1": "...",           (optional, checked when present)
+//!     "license": "CC0-1.0",    (optional, CC0 when missing)
+//!     "rom": "roms/outlaw.ch8" (optional, relative to the index)
+//!   }
+//! }
+//! ```
+
+use std::fs;
+use std::io;
+use std::process::{Command, Stdio};
+
+use crate::json::{self, Value};
+
+pub const DEFAULT_INDEX: &str = "https://johnearnest.github.io/chip8Archive/programs.json";
+
+/// The chip8Archive releases everything under CC0, so entries without a
+/// license get that.
+const DEFAULT_LICENSE: &str = "CC0-1.0";
+
+/// Prefixes of licenses that let anyone download and keep a copy.
+const FREE_LICENSES: &[&str] = &[
+    "cc0",
+    "cc-by",
+    "mit",
+    "bsd",
+    "apache",
+    "gpl",
+    "lgpl",
+    "mpl",
+    "unlicense",
+    "zlib",
+    "public domain",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub id: String,
+    pub title: String,
+    pub authors: Vec<String>,
+    pub license: String,
+    pub sha1: Option<String>,
+    /// Where to download the ROM from.
+    pub rom: String,
+    /// The entry as it is in the index, to keep next to the ROM.
+    pub metadata: Value,
+}
+
+impl Entry {
+    pub fn redistributable(&self) -> bool {
+        let license = self.license.to_ascii_lowercase();
+        FREE_LICENSES.iter().any(|free| license.starts_with(free))
+    }
+
+    /// The metadata with what was learnt while fetching filled in.
+    pub fn metadata_with(&self, sha1: &str) -> Value {
+        let mut metadata = self.metadata.as_object().cloned().unwrap_or_default();
+        metadata.insert("id".to_string(), Value::String(self.id.clone()));
+        metadata.insert("sha1".to_string(), Value::String(sha1.to_string()));
+        metadata.insert("license".to_string(), Value::String(self.license.clone()));
+        metadata.insert("source".to_string(), Value::String(self.rom.clone()));
+        Value::Object(metadata)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Index {
+    pub entries: Vec<Entry>,
+}
+
+impl Index {
+    /// Parses the index found at `location`, which relative ROM paths are
+    /// resolved against.
+    pub fn parse(text: &str, location: &str) -> Result<Index, String> {
+        let document = json::parse(text).map_err(|err| err.to_string())?;
+        let programs = document
+            .as_object()
+            .ok_or("expected an object of ROMs by id")?;
+
+        let mut entries = Vec::new();
+        for (id, program) in programs {
+            // the id ends up as a file name
+            if id.is_empty()
+                || id.starts_with('.')
+                || !id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            {
+                return Err(format!("{:?} is not a valid ROM id", id));
+            }
+            let text = |key: &str| program.get(key).and_then(Value::as_str);
+            let rom = text("rom")
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("roms/{}.ch8", id));
+
+            entries.push(Entry {
+                id: id.clone(),
+                title: text("title").unwrap_or(id).to_string(),
+                authors: program
+                    .get("authors")
+                    .and_then(Value::as_array)
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect(),
+                license: text("license").unwrap_or(DEFAULT_LICENSE).to_string(),
+                sha1: text("sha1").map(str::to_ascii_lowercase),
+                rom: resolve(location, &rom),
+                metadata: program.clone(),
+            });
+        }
+        Ok(Index { entries })
+    }
+
+    /// Entries whose id or title is `query`, or failing that whose title
+    /// contains every word of it, ignoring case.
+    pub fn search(&self, query: &str) -> Vec<&Entry> {
+        let query = query.to_lowercase();
+        let exact: Vec<&Entry> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.id.to_lowercase() == query || entry.title.to_lowercase() == query)
+            .collect();
+        if !exact.is_empty() {
+            return exact;
+        }
+
+        self.entries
+            .iter()
+            .filter(|entry| {
+                let title = entry.title.to_lowercase();
+                query.split_whitespace().all(|word| title.contains(word))
+            })
+            .collect()
+    }
+}
+
+fn is_url(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+fn resolve(base: &str, relative: &str) -> String {
+    if is_url(relative) || relative.starts_with('/') {
+        return relative.to_string();
+    }
+    match base.rfind('/') {
+        Some(slash) => format!("{}/{}", &base[..slash], relative),
+        None => relative.to_string(),
+    }
+}
+
+/// Reads a local file, or downloads a URL with curl since std has no TLS.
+pub fn fetch(location: &str) -> io::Result<Vec<u8>> {
+    if !is_url(location) {
+        return fs::read(location);
+    }
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location", location])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| io::Error::new(err.kind(), format!("running curl: {}", err)))?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(io::Error::other(message));
+    }
+    Ok(output.stdout)
+}
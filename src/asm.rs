@@ -0,0 +1,287 @@
+//! An assembler for the mnemonics `Instruction` displays as (Cowgod's), so
+//! homebrew ROMs can be written without another toolchain.
+//!
+//! ```text
+//! ; draws the key pressed, forever
+//! start:
+//!     LD V1, K
+//!     LD F, V1
+//!     DRW V0, V0, 5
+//!     JP start
+//! data: DB 0xF0, 0x90, 0b10010000
+//! ```
+//!
+//! Mnemonics and registers are case insensitive, numbers are decimal, `0x` or
+//! `#` hex or `0b` binary, and labels can stand in for any address. `DB` and
+//! `DW` emit bytes and big endian words.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::instruction::Instruction;
+use crate::memory::{MEMORY_SIZE, PROGRAM_START};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Operand {
+    V(u8),
+    I,
+    /// `[I]`, memory at I.
+    AtI,
+    Dt,
+    St,
+    K,
+    F,
+    /// `HF`, the big font.
+    Hf,
+    /// `PITCH`, the XO-CHIP audio pitch.
+    Pitch,
+    B,
+    Number(u16),
+    Label(String),
+}
+
+/// A source line with its label taken off
+struct Statement<'a> {
+    line: usize,
+    mnemonic: String,
+    operands: Vec<&'a str>,
+}
+
+/// Assembles `source` into a ROM to be loaded at 0x200.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    // first pass: every instruction is two bytes, so label addresses are
+    // known before anything is encoded
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut addr = PROGRAM_START;
+
+    for (number, text) in source.lines().enumerate() {
+        let line = number + 1;
+        let error = |message: String| AsmError { line, message };
+        let mut text = text.split(';').next().unwrap_or("").trim();
+
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) {
+                return Err(error(format!("invalid label {:?}", label)));
+            }
+            if labels.insert(label.to_ascii_lowercase(), addr).is_some() {
+                return Err(error(format!("label {} is defined twice", label)));
+            }
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, operands) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let operands: Vec<&str> = match operands.trim() {
+            "" => Vec::new(),
+            operands => operands.split(',').map(str::trim).collect(),
+        };
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        addr += match mnemonic.as_str() {
+            "DB" => operands.len(),
+            "DW" => 2 * operands.len(),
+            _ => 2,
+        };
+        if addr > MEMORY_SIZE {
+            return Err(error("the program does not fit in memory".to_string()));
+        }
+        statements.push(Statement {
+            line,
+            mnemonic,
+            operands,
+        });
+    }
+
+    let mut rom = Vec::new();
+    for statement in &statements {
+        let error = |message: String| AsmError {
+            line: statement.line,
+            message,
+        };
+        let operands = statement
+            .operands
+            .iter()
+            .map(|text| operand(text, &labels).map_err(error))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match statement.mnemonic.as_str() {
+            "DB" => {
+                for operand in &operands {
+                    rom.push(number(operand, 0xFF).map_err(error)? as u8);
+                }
+            }
+            "DW" => {
+                for operand in &operands {
+                    rom.extend_from_slice(&number(operand, 0xFFFF).map_err(error)?.to_be_bytes());
+                }
+            }
+            mnemonic => {
+                let instruction = instruction(mnemonic, &operands).map_err(error)?;
+                rom.extend_from_slice(&instruction.encode().to_be_bytes());
+            }
+        }
+    }
+
+    Ok(rom)
+}
+
+fn is_label(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn operand(text: &str, labels: &HashMap<String, usize>) -> Result<Operand, String> {
+    let upper = text.to_ascii_uppercase();
+    let operand = match upper.as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::AtI,
+        "DT" => Operand::Dt,
+        "ST" => Operand::St,
+        "K" => Operand::K,
+        "F" => Operand::F,
+        "HF" => Operand::Hf,
+        "PITCH" => Operand::Pitch,
+        "B" => Operand::B,
+        _ => {
+            if let Some(register) = upper
+                .strip_prefix('V')
+                .filter(|digit| digit.len() == 1)
+                .and_then(|digit| u8::from_str_radix(digit, 16).ok())
+            {
+                Operand::V(register)
+            } else if let Some(value) = parse_number(text) {
+                Operand::Number(value)
+            } else if is_label(text) {
+                match labels.get(&text.to_ascii_lowercase()) {
+                    Some(&addr) => Operand::Number(addr as u16),
+                    None => Operand::Label(text.to_string()),
+                }
+            } else {
+                return Err(format!("invalid operand {:?}", text));
+            }
+        }
+    };
+    Ok(operand)
+}
+
+fn parse_number(text: &str) -> Option<u16> {
+    let lower = text.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x").or(lower.strip_prefix('#')) {
+        u16::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        u16::from_str_radix(binary, 2).ok()
+    } else {
+        lower.parse().ok()
+    }
+}
+
+/// A number operand that has to fit in `max`.
+fn number(operand: &Operand, max: u16) -> Result<u16, String> {
+    match operand {
+        Operand::Number(value) if *value <= max => Ok(*value),
+        Operand::Number(value) => Err(format!("{:#x} is larger than {:#x}", value, max)),
+        Operand::Label(label) => Err(format!("unknown label {}", label)),
+        other => Err(format!("expected a number, got {:?}", other)),
+    }
+}
+
+const MNEMONICS: [&str; 22] = [
+    "CLS", "RET", "EXIT", "SYS", "JP", "CALL", "SE", "SNE", "LD", "ADD", "OR", "AND", "XOR", "SUB",
+    "SUBN", "SHR", "SHL", "RND", "DRW", "SKP", "SKNP", "AUDIO",
+];
+
+fn instruction(mnemonic: &str, operands: &[Operand]) -> Result<Instruction, String> {
+    use Operand::*;
+
+    let addr = |operand| number(operand, 0x0FFF);
+    let byte = |operand| number(operand, 0xFF).map(|value| value as u8);
+
+    let instruction = match (mnemonic, operands) {
+        ("CLS", []) => Instruction::Cls,
+        ("RET", []) => Instruction::Ret,
+        ("EXIT", []) => Instruction::Exit,
+        ("AUDIO", []) => Instruction::Audio,
+        ("SYS", [a]) => Instruction::Sys { addr: addr(a)? },
+        ("JP", [V(0), a]) => Instruction::JumpV0 { addr: addr(a)? },
+        ("JP", [a]) => Instruction::Jump { addr: addr(a)? },
+        ("CALL", [a]) => Instruction::Call { addr: addr(a)? },
+        ("SE", [V(x), V(y)]) => Instruction::SeXy { x: *x, y: *y },
+        ("SE", [V(x), kk]) => Instruction::SeXkk {
+            x: *x,
+            kk: byte(kk)?,
+        },
+        ("SNE", [V(x), V(y)]) => Instruction::SneXy { x: *x, y: *y },
+        ("SNE", [V(x), kk]) => Instruction::SneXkk {
+            x: *x,
+            kk: byte(kk)?,
+        },
+        ("LD", [V(x), V(y)]) => Instruction::SetXy { x: *x, y: *y },
+        ("LD", [V(x), Dt]) => Instruction::GetDelay { x: *x },
+        ("LD", [V(x), K]) => Instruction::WaitKey { x: *x },
+        ("LD", [V(x), AtI]) => Instruction::Load { x: *x },
+        ("LD", [V(x), kk]) => Instruction::Set {
+            x: *x,
+            kk: byte(kk)?,
+        },
+        ("LD", [I, a]) => Instruction::SetI { addr: addr(a)? },
+        ("LD", [Dt, V(x)]) => Instruction::SetDelay { x: *x },
+        ("LD", [St, V(x)]) => Instruction::SetSound { x: *x },
+        ("LD", [F, V(x)]) => Instruction::Font { x: *x },
+        ("LD", [Hf, V(x)]) => Instruction::BigFont { x: *x },
+        ("LD", [Pitch, V(x)]) => Instruction::Pitch { x: *x },
+        ("LD", [B, V(x)]) => Instruction::Bcd { x: *x },
+        ("LD", [AtI, V(x)]) => Instruction::Store { x: *x },
+        ("ADD", [I, V(x)]) => Instruction::AddI { x: *x },
+        ("ADD", [V(x), V(y)]) => Instruction::AddXy { x: *x, y: *y },
+        ("ADD", [V(x), kk]) => Instruction::Add {
+            x: *x,
+            kk: byte(kk)?,
+        },
+        ("OR", [V(x), V(y)]) => Instruction::OrXy { x: *x, y: *y },
+        ("AND", [V(x), V(y)]) => Instruction::AndXy { x: *x, y: *y },
+        ("XOR", [V(x), V(y)]) => Instruction::XorXy { x: *x, y: *y },
+        ("SUB", [V(x), V(y)]) => Instruction::SubXy { x: *x, y: *y },
+        ("SUBN", [V(x), V(y)]) => Instruction::SubnXy { x: *x, y: *y },
+        // the one operand form shifts vx itself, whichever way the quirk is set
+        ("SHR", [V(x)]) => Instruction::ShrXy { x: *x, y: *x },
+        ("SHR", [V(x), V(y)]) => Instruction::ShrXy { x: *x, y: *y },
+        ("SHL", [V(x)]) => Instruction::ShlXy { x: *x, y: *x },
+        ("SHL", [V(x), V(y)]) => Instruction::ShlXy { x: *x, y: *y },
+        ("RND", [V(x), kk]) => Instruction::Rand {
+            x: *x,
+            kk: byte(kk)?,
+        },
+        ("DRW", [V(x), V(y), n]) => Instruction::Draw {
+            x: *x,
+            y: *y,
+            n: number(n, 0xF)? as u8,
+        },
+        ("SKP", [V(x)]) => Instruction::SkipKey { x: *x },
+        ("SKNP", [V(x)]) => Instruction::SkipNotKey { x: *x },
+        _ if !MNEMONICS.contains(&mnemonic) => {
+            return Err(format!("unknown instruction {}", mnemonic))
+        }
+        _ => return Err(format!("invalid operands for {}", mnemonic)),
+    };
+    Ok(instruction)
+}
@@ -0,0 +1,73 @@
+//! Convention for test ROMs to report results to the host.
+//!
+//! With assertions enabled, two otherwise unused 0nnn opcodes end the run:
+//!
+//! - `0A00`: PASS
+//! - `0A01`: FAIL
+//!
+//! If I is non-zero at that point it points at a NUL terminated ASCII message
+//! (at most 64 bytes) that is reported along with the result. Both halt the machine.
+//! They are off by default because real 0nnn machine code calls could collide.
+
+use core::fmt;
+
+use crate::memory::Memory;
+
+pub const PASS: u16 = 0x0A00;
+pub const FAIL: u16 = 0x0A01;
+
+const MAX_MESSAGE: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assertion {
+    pub passed: bool,
+    /// ASCII, anything else is kept as `?`.
+    message: [u8; MAX_MESSAGE],
+    len: usize,
+}
+
+impl Assertion {
+    /// Builds the assertion for a 0nnn address, if it is one of ours.
+    pub fn from_sys(addr: u16, index: u16, memory: &Memory) -> Option<Assertion> {
+        let passed = match addr {
+            PASS => true,
+            FAIL => false,
+            _ => return None,
+        };
+
+        let mut message = [0; MAX_MESSAGE];
+        let mut len = 0;
+        if index != 0 {
+            for addr in index as usize..index as usize + MAX_MESSAGE {
+                match memory.read_byte(addr) {
+                    Ok(0) | Err(_) => break,
+                    Ok(byte) => {
+                        message[len] = if byte.is_ascii() { byte } else { b'?' };
+                        len += 1;
+                    }
+                }
+            }
+        }
+
+        Some(Assertion {
+            passed,
+            message,
+            len,
+        })
+    }
+
+    pub fn message(&self) -> &str {
+        core::str::from_utf8(&self.message[..self.len]).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Assertion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let result = if self.passed { "PASS" } else { "FAIL" };
+        if self.len == 0 {
+            write!(f, "{}", result)
+        } else {
+            write!(f, "{}: {}", result, self.message())
+        }
+    }
+}
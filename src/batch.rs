@@ -0,0 +1,108 @@
+//! Running a job over a whole ROM collection on every core.
+
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// File extensions picked up when a directory is given.
+const EXTENSIONS: [&str; 2] = ["ch8", "c8"];
+
+const PROGRESS_WIDTH: usize = 30;
+
+/// Expands directories into the ROMs inside them, recursively and sorted.
+/// Files named directly are kept whatever their extension.
+pub fn collect_roms(paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut roms = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut found = Vec::new();
+            walk(path, &mut found)?;
+            found.sort();
+            roms.extend(found);
+        } else {
+            roms.push(path.clone());
+        }
+    }
+    Ok(roms)
+}
+
+fn walk(dir: &Path, roms: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk(&path, roms)?;
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+        {
+            roms.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Runs `job` on every item across all cores and returns the results in
+/// input order. Draws a progress bar on stderr when it is a terminal.
+pub fn run<T, R>(items: &[T], job: impl Fn(&T) -> R + Sync) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+{
+    let workers = thread::available_parallelism()
+        .map_or(1, |count| count.get())
+        .min(items.len());
+    let progress = items.len() > 1 && io::stderr().is_terminal();
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    let mut results: Vec<Option<R>> = items.iter().map(|_| None).collect();
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let sender = sender.clone();
+            let (next, job) = (&next, &job);
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(i) else {
+                    break;
+                };
+                if sender.send((i, job(item))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for (done, (i, result)) in receiver.iter().enumerate() {
+            results[i] = Some(result);
+            if progress {
+                draw_progress(done + 1, items.len());
+            }
+        }
+    });
+
+    if progress {
+        eprint!("\r{:width$}\r", "", width = PROGRESS_WIDTH + 24);
+    }
+    results
+        .into_iter()
+        .map(|result| result.expect("every item is processed"))
+        .collect()
+}
+
+fn draw_progress(done: usize, total: usize) {
+    let filled = done * PROGRESS_WIDTH / total;
+    let mut stderr = io::stderr().lock();
+    let _ = write!(
+        stderr,
+        "\r[{}{}] {}/{}",
+        "#".repeat(filled),
+        " ".repeat(PROGRESS_WIDTH - filled),
+        done,
+        total
+    );
+    let _ = stderr.flush();
+}
@@ -0,0 +1,134 @@
+//! Finding where a replay stops matching its recording. The recording keeps a
+//! `replay::state_hash` of the machine once a second; a replay that ends up
+//! somewhere else (an emulator change, a quirk set differently) fails one of
+//! them, and every one after it. `bisect` narrows that down to the first
+//! checkpoint that fails by running the replay again up to the checkpoint in
+//! the middle, and again, halving the range each time: runs are deterministic,
+//! so each probe needs nothing but a fresh machine.
+//!
+//! What's left is at most `CHECKPOINT_FRAMES` frames, between the last
+//! checkpoint that matched and the first that didn't, and the machine at both
+//! ends comes back as save states to load and step through.
+
+use crate::cpu::Cpu;
+use crate::headless;
+use crate::replay::{self, Player, Replay};
+use crate::savestate::State;
+
+/// The first checkpoint that doesn't match.
+pub struct Desync {
+    /// The frame of the last checkpoint that matched, 0 when it is the first
+    /// one that doesn't.
+    pub good_frame: usize,
+    pub bad_frame: usize,
+    /// The hash recorded and the one the replay got.
+    pub expected: u64,
+    pub actual: u64,
+    pub good: State,
+    pub bad: State,
+    /// Replay runs it took.
+    pub runs: usize,
+}
+
+/// What bisecting found.
+pub enum Outcome {
+    /// Every checkpoint matches, out of this many.
+    Matches(usize),
+    /// The recording has no checkpoints to go by (version 1).
+    NoCheckpoints,
+    Desync(Box<Desync>),
+}
+
+/// Bisects `replay` over machines from `prepare`, which should be set up the
+/// way the session was recorded, ROM loaded and the recording applied.
+pub fn bisect(replay: &Replay, prepare: impl Fn() -> Cpu) -> Outcome {
+    let checkpoints = &replay.hashes;
+    let Some(&(last_frame, last_hash)) = checkpoints.last() else {
+        return Outcome::NoCheckpoints;
+    };
+    let mut runs = 1;
+    if replay::state_hash(&run_to(replay, &prepare, last_frame)) == last_hash {
+        return Outcome::Matches(checkpoints.len());
+    }
+
+    // checkpoints[..good] match and checkpoints[bad] doesn't
+    let (mut good, mut bad) = (0, checkpoints.len() - 1);
+    while good < bad {
+        let middle = (good + bad) / 2;
+        let (frame, hash) = checkpoints[middle];
+        runs += 1;
+        if replay::state_hash(&run_to(replay, &prepare, frame)) == hash {
+            good = middle + 1;
+        } else {
+            bad = middle;
+        }
+    }
+
+    let good_frame = bad.checked_sub(1).map_or(0, |good| checkpoints[good].0);
+    let (bad_frame, expected) = checkpoints[bad];
+    let good = run_to(replay, &prepare, good_frame);
+    let bad = run_to(replay, &prepare, bad_frame);
+    Outcome::Desync(Box::new(Desync {
+        good_frame,
+        bad_frame,
+        expected,
+        actual: replay::state_hash(&bad),
+        good: State::capture(&good),
+        bad: State::capture(&bad),
+        runs: runs + 2,
+    }))
+}
+
+/// A fresh machine with the first `frames` frames of `replay` run on it, or
+/// as many as it got through before halting or failing.
+pub fn run_to(replay: &Replay, prepare: impl Fn() -> Cpu, frames: usize) -> Cpu {
+    let mut player = Player::new(replay);
+    let run = headless::resume(prepare(), frames, |cpu| {
+        cpu.keys = player.frame().unwrap_or([false; 16]);
+    });
+    run.cpu
+}
+
+/// How `b` differs from `a`, a line per register and a summary of memory and
+/// the screen, e.g. `pc 0x2a4 -> 0x2b0`.
+pub fn differences(a: &State, b: &State) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut compare = |name: &str, was: u16, now: u16| {
+        if was != now {
+            lines.push(format!("{} {:#04x} -> {:#04x}", name, was, now));
+        }
+    };
+    compare("pc", a.program_counter, b.program_counter);
+    compare("I", a.index, b.index);
+    for (v, (&was, &now)) in a.registers.iter().zip(&b.registers).enumerate() {
+        compare(&format!("V{:X}", v), was as u16, now as u16);
+    }
+    compare("sp", a.stack_pointer as u16, b.stack_pointer as u16);
+    compare("DT", a.delay_timer as u16, b.delay_timer as u16);
+    compare("ST", a.sound_timer as u16, b.sound_timer as u16);
+    if a.rng != b.rng {
+        lines.push("rng state differs".to_string());
+    }
+
+    let changed: Vec<usize> = (0..a.memory.len().min(b.memory.len()))
+        .filter(|&addr| a.memory[addr] != b.memory[addr])
+        .collect();
+    if let (Some(first), Some(last)) = (changed.first(), changed.last()) {
+        lines.push(format!(
+            "memory: {} bytes differ, {:#05x}-{:#05x}",
+            changed.len(),
+            first,
+            last
+        ));
+    }
+    let pixels = a
+        .pixels
+        .iter()
+        .zip(&b.pixels)
+        .filter(|(was, now)| was != now)
+        .count();
+    if pixels > 0 {
+        lines.push(format!("screen: {} pixels differ", pixels));
+    }
+    lines
+}
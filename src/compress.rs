@@ -0,0 +1,124 @@
+//! A small LZ77 compressor for save states and rewind keyframes.
+//!
+//! Machine snapshots are mostly zeros and repeated sprite data, so a simple
+//! byte oriented scheme already shrinks them several times over. The stream
+//! is a sequence of operations, each starting with a control byte:
+//!
+//! * `0x00..=0x7F`: `control + 1` literal bytes follow.
+//! * `0x80..=0xFF`: copy `(control & 0x7F) + MIN_MATCH` bytes starting
+//!   `offset` bytes back, with the offset following as a little endian `u16`.
+
+use std::fmt;
+
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 0x7F + MIN_MATCH;
+const MAX_LITERALS: usize = 0x80;
+const MAX_OFFSET: usize = u16::MAX as usize;
+const HASH_BITS: u32 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CorruptData;
+
+impl fmt::Display for CorruptData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "corrupt compressed data")
+    }
+}
+
+impl std::error::Error for CorruptData {}
+
+fn hash(bytes: &[u8]) -> usize {
+    let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]);
+    (value.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() / 2);
+    // last position each 3 byte sequence was seen at
+    let mut table = vec![usize::MAX; 1 << HASH_BITS];
+    let mut literals = 0..0;
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let mut length = 0;
+        let mut offset = 0;
+        if pos + MIN_MATCH <= data.len() {
+            let slot = &mut table[hash(&data[pos..])];
+            let candidate = *slot;
+            *slot = pos;
+
+            if candidate != usize::MAX && pos - candidate <= MAX_OFFSET {
+                length = data[candidate..]
+                    .iter()
+                    .zip(&data[pos..])
+                    .take(MAX_MATCH)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                offset = pos - candidate;
+            }
+        }
+
+        if length >= MIN_MATCH {
+            flush_literals(&mut out, &data[literals]);
+            out.push(0x80 | (length - MIN_MATCH) as u8);
+            out.extend_from_slice(&(offset as u16).to_le_bytes());
+            pos += length;
+            literals = pos..pos;
+        } else {
+            pos += 1;
+            literals.end = pos;
+        }
+    }
+
+    flush_literals(&mut out, &data[literals]);
+    out
+}
+
+fn flush_literals(out: &mut Vec<u8>, literals: &[u8]) {
+    for chunk in literals.chunks(MAX_LITERALS) {
+        out.push((chunk.len() - 1) as u8);
+        out.extend_from_slice(chunk);
+    }
+}
+
+/// Decompresses `data`, which has to expand to exactly `len` bytes. `len`
+/// usually comes from a file, so no more is allocated up front than `data`
+/// could possibly expand to.
+pub fn decompress(data: &[u8], len: usize) -> Result<Vec<u8>, CorruptData> {
+    // no operation makes more than MAX_MATCH bytes out of a byte
+    let mut out = Vec::with_capacity(len.min(data.len().saturating_mul(MAX_MATCH)));
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let control = data[pos] as usize;
+        pos += 1;
+
+        if control < 0x80 {
+            let literals = data.get(pos..pos + control + 1).ok_or(CorruptData)?;
+            out.extend_from_slice(literals);
+            pos += literals.len();
+        } else {
+            let offset = data.get(pos..pos + 2).ok_or(CorruptData)?;
+            let offset = u16::from_le_bytes([offset[0], offset[1]]) as usize;
+            pos += 2;
+            if offset == 0 || offset > out.len() {
+                return Err(CorruptData);
+            }
+
+            // byte by byte, matches may overlap what they produce
+            let start = out.len() - offset;
+            for i in 0..(control & 0x7F) + MIN_MATCH {
+                out.push(out[start + i]);
+            }
+        }
+
+        if out.len() > len {
+            return Err(CorruptData);
+        }
+    }
+
+    if out.len() != len {
+        return Err(CorruptData);
+    }
+    Ok(out)
+}
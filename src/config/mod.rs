@@ -0,0 +1,470 @@
+//! The `chip8.toml` configuration file.
+//!
+//! Looked up at `--config <path>`, then `./chip8.toml`, then
+//! `$XDG_CONFIG_HOME/chip8/chip8.toml` (`~/.config/chip8/chip8.toml`).
+//! Command line flags take precedence over anything set here.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::cpu::INSTRUCTIONS_PER_FRAME;
+use crate::font::{self, Font, FONTS};
+use crate::frontend::{self, PixelStyle, Rgb};
+use crate::gamepad::Bindings;
+use crate::keypad::Keymap;
+use crate::overrides::{self, Overrides, Rule};
+use crate::platform::{self, Preset, PRESETS};
+use crate::quirks::{self, Quirks};
+
+pub mod toml;
+
+use toml::Value;
+
+pub const FILE_NAME: &str = "chip8.toml";
+
+/// How the timers run while fast-forwarding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FastForwardTimers {
+    /// Every emulated frame ticks them, so the game plays exactly as it
+    /// would, only faster; beeps get shorter.
+    #[default]
+    Emulated,
+    /// They tick once per frame on the wall clock, so sounds and music keep
+    /// their length, though a game that waits on the delay timer is no
+    /// faster.
+    Wall,
+}
+
+impl FastForwardTimers {
+    pub fn parse(name: &str) -> Option<FastForwardTimers> {
+        match name {
+            "emulated" => Some(FastForwardTimers::Emulated),
+            "wall" => Some(FastForwardTimers::Wall),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub frontend: String,
+    /// The machine the settings below start from.
+    pub preset: &'static Preset,
+    /// Instructions per 60Hz frame.
+    pub speed: usize,
+    pub quirks: Quirks,
+    pub font: &'static Font,
+    pub keymap: Keymap,
+    pub gamepad: Bindings,
+    pub style: PixelStyle,
+    /// Beep while the sound timer runs.
+    pub audio: bool,
+    /// Seconds of history kept for rewinding, 0 turns it off.
+    pub rewind: u32,
+    /// Frames run per frame shown while fast-forwarding.
+    pub fast_forward: u32,
+    pub fast_forward_timers: FastForwardTimers,
+    /// Opcodes skipped or run as others, see `overrides`.
+    pub opcodes: Overrides,
+    /// Where `chip8 fetch` puts ROMs and the browser looks for them, instead of
+    /// the data directory's `roms`.
+    pub rom_dir: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            frontend: "terminal".to_string(),
+            preset: &PRESETS[0],
+            speed: INSTRUCTIONS_PER_FRAME,
+            quirks: Quirks::default(),
+            font: &FONTS[0],
+            keymap: Keymap::default(),
+            gamepad: Bindings::default(),
+            style: PixelStyle::default(),
+            audio: true,
+            rewind: 30,
+            fast_forward: 4,
+            fast_forward_timers: FastForwardTimers::default(),
+            opcodes: Overrides::default(),
+            rom_dir: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Invalid {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            ConfigError::Invalid {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Where the config file is read from when no path is given, if anywhere.
+pub fn default_path() -> Option<PathBuf> {
+    let local = PathBuf::from(FILE_NAME);
+    if local.exists() {
+        return Some(local);
+    }
+
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    let path = config_dir.join("chip8").join(FILE_NAME);
+    path.exists().then_some(path)
+}
+
+impl Config {
+    /// Loads `path` if given (it has to exist), otherwise the default location
+    /// if there is a file there, otherwise the defaults.
+    /// Also returns the path that was used.
+    pub fn resolve(path: Option<&Path>) -> Result<(Config, Option<PathBuf>), ConfigError> {
+        let path = match path {
+            Some(path) => Some(path.to_path_buf()),
+            None => default_path(),
+        };
+
+        match path {
+            Some(path) => Ok((Config::load(&path)?, Some(path))),
+            None => Ok((Config::default(), None)),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Config, ConfigError> {
+        let text =
+            fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+        Config::parse(&text).map_err(|(line, message)| ConfigError::Invalid {
+            path: path.to_path_buf(),
+            line,
+            message,
+        })
+    }
+
+    /// Parses a config file, returning the line and message of the first problem.
+    pub fn parse(text: &str) -> Result<Config, (usize, String)> {
+        let entries = toml::parse(text).map_err(|err| (err.line, err.message))?;
+        let mut config = Config::default();
+
+        // the platform sets the defaults for everything else, and the profile
+        // has to be applied before single quirk overrides
+        if let Some(entry) = entries.get("platform") {
+            let name = string(&entry.value).ok_or((entry.line, "expected a string".into()))?;
+            platform::preset(name)
+                .ok_or_else(|| {
+                    (
+                        entry.line,
+                        format!(
+                            "unknown platform {}, expected one of: {}",
+                            name,
+                            platform::preset_names()
+                        ),
+                    )
+                })?
+                .apply(&mut config);
+        }
+        if let Some(entry) = entries.get("quirks.profile") {
+            let name = string(&entry.value).ok_or((entry.line, "expected a string".into()))?;
+            config.quirks = Quirks::profile(name).ok_or_else(|| {
+                let expected = quirks::PROFILES.join(", ");
+                (
+                    entry.line,
+                    format!("unknown profile {}, expected one of: {}", name, expected),
+                )
+            })?;
+        }
+
+        for (key, entry) in &entries {
+            config
+                .set(key, &entry.value)
+                .map_err(|message| (entry.line, message))?;
+        }
+
+        Ok(config)
+    }
+
+    fn set(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        let expected = |what: &str| format!("{} must be {}, not {}", key, what, value);
+
+        match key {
+            "frontend" => {
+                let name = string(value).ok_or_else(|| expected("a string"))?;
+                if !frontend::FRONTENDS.contains(&name) {
+                    return Err(format!(
+                        "unknown frontend {}, expected one of: {}",
+                        name,
+                        frontend::FRONTENDS.join(", ")
+                    ));
+                }
+                self.frontend = name.to_string();
+            }
+            "speed" => {
+                self.speed = integer(value)
+                    .and_then(|speed| usize::try_from(speed).ok())
+                    .filter(|&speed| speed > 0)
+                    .ok_or_else(|| expected("a positive integer"))?;
+            }
+            "platform" | "quirks.profile" => {}
+            "font" => {
+                let name = string(value).ok_or_else(|| expected("a string"))?;
+                self.font = font::font(name).ok_or_else(|| {
+                    format!(
+                        "unknown font {}, expected one of: {}",
+                        name,
+                        font::font_names()
+                    )
+                })?;
+            }
+            "quirks.shift_uses_vy" => self.quirks.shift_uses_vy = boolean(value, key)?,
+            "quirks.load_store_increments_i" => {
+                self.quirks.load_store_increments_i = boolean(value, key)?
+            }
+            "quirks.jump_uses_vx" => self.quirks.jump_uses_vx = boolean(value, key)?,
+            "quirks.logic_resets_vf" => self.quirks.logic_resets_vf = boolean(value, key)?,
+            "quirks.wrap_sprites" => self.quirks.wrap_sprites = boolean(value, key)?,
+            "display.scale" => {
+                self.style.scale = integer(value)
+                    .and_then(|scale| u32::try_from(scale).ok())
+                    .filter(|&scale| (1..=16).contains(&scale))
+                    .ok_or_else(|| expected("between 1 and 16"))?;
+            }
+            "display.foreground" => {
+                self.style.foreground = color(value).ok_or_else(|| expected("#rrggbb"))?
+            }
+            "display.background" => {
+                self.style.background = color(value).ok_or_else(|| expected("#rrggbb"))?
+            }
+            "display.ghosting" => self.style.ghosting = boolean(value, key)?,
+            "audio.enabled" => self.audio = boolean(value, key)?,
+            "rewind.seconds" => {
+                self.rewind = integer(value)
+                    .and_then(|seconds| u32::try_from(seconds).ok())
+                    .filter(|&seconds| seconds <= 600)
+                    .ok_or_else(|| expected("between 0 and 600"))?;
+            }
+            "fast_forward.speed" => {
+                self.fast_forward = integer(value)
+                    .and_then(|speed| u32::try_from(speed).ok())
+                    .filter(|&speed| (2..=16).contains(&speed))
+                    .ok_or_else(|| expected("between 2 and 16"))?;
+            }
+            "fast_forward.timers" => {
+                self.fast_forward_timers = string(value)
+                    .and_then(FastForwardTimers::parse)
+                    .ok_or_else(|| expected("\"emulated\" or \"wall\""))?;
+            }
+            "roms.dir" => {
+                let dir = string(value).ok_or_else(|| expected("a string"))?;
+                // ~ is what people write, and the shell isn't there to expand it
+                self.rom_dir = Some(match dir.strip_prefix("~/") {
+                    Some(rest) => env::var_os("HOME")
+                        .map(|home| PathBuf::from(home).join(rest))
+                        .ok_or_else(|| format!("{}: HOME is not set", key))?,
+                    None => PathBuf::from(dir),
+                });
+            }
+            _ => {
+                if let Some(pad_key) = key.strip_prefix("keys.") {
+                    let pad_key = keypad_key(pad_key)
+                        .ok_or_else(|| format!("unknown keypad key {}", pad_key))?;
+                    let host = string(value)
+                        .and_then(|host| {
+                            let mut chars = host.chars();
+                            chars.next().filter(|_| chars.next().is_none())
+                        })
+                        .ok_or_else(|| expected("a single character"))?;
+                    self.keymap.keys[pad_key as usize] = host.to_ascii_lowercase();
+                } else if let Some(pattern) = key.strip_prefix("opcodes.") {
+                    let action = string(value).ok_or_else(|| expected("a string"))?;
+                    let rule = Rule::parse(pattern, action)
+                        .map_err(|message| format!("{}: {}", key, message))?;
+                    self.opcodes.push(rule).map_err(|_| {
+                        format!("at most {} opcode overrides", overrides::MAX_RULES)
+                    })?;
+                } else if let Some(input) = key.strip_prefix("gamepad.") {
+                    let input = Bindings::parse_input(input).ok_or_else(|| {
+                        format!(
+                            "unknown gamepad input {}, expected buttonN, axisN- or axisN+",
+                            input
+                        )
+                    })?;
+                    let pad_key = string(value)
+                        .and_then(keypad_key)
+                        .ok_or_else(|| expected("a keypad key 0-F"))?;
+                    self.gamepad.keys.insert(input, pad_key);
+                } else {
+                    return Err(format!("unknown setting {}", key));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn string(value: &Value) -> Option<&str> {
+    match value {
+        Value::String(text) => Some(text),
+        _ => None,
+    }
+}
+
+fn integer(value: &Value) -> Option<i64> {
+    match value {
+        Value::Integer(number) => Some(*number),
+        _ => None,
+    }
+}
+
+fn boolean(value: &Value, key: &str) -> Result<bool, String> {
+    match value {
+        Value::Boolean(flag) => Ok(*flag),
+        _ => Err(format!("{} must be true or false, not {}", key, value)),
+    }
+}
+
+fn color(value: &Value) -> Option<Rgb> {
+    string(value).and_then(Rgb::parse)
+}
+
+/// A single hex digit naming a keypad key.
+fn keypad_key(text: &str) -> Option<u8> {
+    if text.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(text, 16).ok()
+}
+
+/// The file written by `chip8 config init`: every setting at its default, with comments.
+pub fn default_file() -> String {
+    let defaults = Config::default();
+    let mut file = String::new();
+
+    file.push_str(&format!(
+        r#"# chip8 configuration. Command line flags take precedence over these settings.
+
+# Frontend: {frontends}
+frontend = "{frontend}"
+
+# The machine to emulate, one of: {presets}.
+# It sets the quirks, speed and colours; the settings left commented out below
+# take its values.
+platform = "{preset}"
+
+# Instructions executed per 60Hz frame. Raise it for games that feel sluggish.
+# speed = {speed}
+
+# The digit sprites, one of: {fonts}.
+# font = "{font}"
+
+[quirks]
+# Base profile, one of: {profiles}. The settings below override single quirks.
+# profile = "chip8"
+# 8xy6/8xyE shift vy into vx instead of shifting vx in place.
+# shift_uses_vy = true
+# Fx55/Fx65 leave I pointing past the last register.
+# load_store_increments_i = true
+# Bnnn jumps to xnn + vx instead of nnn + v0.
+# jump_uses_vx = false
+# 8xy1/8xy2/8xy3 reset vf.
+# logic_resets_vf = true
+# Sprites wrap around the screen edges instead of being clipped.
+# wrap_sprites = false
+
+[keys]
+# Host key for each keypad key 0-F.
+"#,
+        frontends = frontend::FRONTENDS.join(", "),
+        frontend = defaults.frontend,
+        presets = platform::preset_names(),
+        preset = defaults.preset.name,
+        speed = defaults.speed,
+        fonts = font::font_names(),
+        font = defaults.font.name,
+        profiles = quirks::PROFILES.join(", "),
+    ));
+    for (pad_key, host) in defaults.keymap.keys.iter().enumerate() {
+        file.push_str(&format!("{:x} = \"{}\"\n", pad_key, host));
+    }
+
+    file.push_str(
+        r#"
+[gamepad]
+# Controller input = keypad key. Inputs are buttonN, "axisN-" and "axisN+"
+# (quoted, since + is not allowed in bare keys); see chip8 --list-gamepads.
+# Anything listed here is added to the defaults: stick and d-pad on 2/4/6/8,
+# buttons 0-3 on 5, 0, A and B, button 7 on F.
+# button4 = "1"
+# "axis1+" = "8"
+"#,
+    );
+
+    file.push_str(&format!(
+        r#"
+[display]
+# Integer scaling factor.
+scale = {scale}
+# Colours as #rrggbb.
+# foreground = "{foreground}"
+# background = "{background}"
+# Fade pixels out over a few frames like an old phosphor screen, reduces flicker.
+ghosting = {ghosting}
+
+[audio]
+# Beep while the sound timer runs.
+enabled = {audio}
+
+[rewind]
+# Seconds of history Backspace can step back through, 0 turns rewinding off.
+seconds = {rewind}
+
+[fast_forward]
+# F2 runs this many frames for every one shown.
+speed = {fast_forward}
+# "emulated" ticks the timers every emulated frame, so the game runs exactly
+# as it would; "wall" ticks them at the normal 60 a second, so beeps and
+# music keep their length.
+timers = "emulated"
+
+[opcodes]
+# Opcodes to skip or to run as another, for old ROMs that need it. Four hex
+# digits, x for any; an x in the replacement keeps the original's digit.
+# Reloaded as soon as the file is saved.
+# "0xxx" = "nop"
+# "00FB" = "00E0"
+
+[roms]
+# Where chip8 fetch downloads to and where chip8 without arguments lists ROMs
+# from, by default the roms directory next to the play statistics.
+# dir = "~/roms"
+"#,
+        scale = defaults.style.scale,
+        foreground = defaults.style.foreground,
+        background = defaults.style.background,
+        ghosting = defaults.style.ghosting,
+        audio = defaults.audio,
+        rewind = defaults.rewind,
+        fast_forward = defaults.fast_forward,
+    ));
+
+    file
+}
@@ -0,0 +1,196 @@
+//! Just enough TOML for the config file: tables, bare or quoted keys, and
+//! string, integer and boolean values. Arrays, inline tables and dates are
+//! not supported.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(text) => write!(f, "{:?}", text),
+            Value::Integer(number) => write!(f, "{}", number),
+            Value::Boolean(flag) => write!(f, "{}", flag),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// A parsed value and the line it came from, for error messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub value: Value,
+    pub line: usize,
+}
+
+/// Parses a document into a flat map of `table.key` (or just `key` before the
+/// first table header) to values.
+pub fn parse(text: &str) -> Result<BTreeMap<String, Entry>, ParseError> {
+    let mut entries = BTreeMap::new();
+    let mut table = String::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let line_number = number + 1;
+        let err = |message: &str| ParseError {
+            line: line_number,
+            message: message.to_string(),
+        };
+
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            let header = strip_comment(header).trim_end();
+            let name = header
+                .strip_suffix(']')
+                .ok_or_else(|| err("expected ] after table name"))?;
+            let (name, rest) = parse_key(name.trim()).map_err(|message| err(&message))?;
+            if !rest.trim().is_empty() {
+                return Err(err("unexpected characters in table name"));
+            }
+            table = name;
+            continue;
+        }
+
+        let (key, rest) = parse_key(line).map_err(|message| err(&message))?;
+        let rest = rest
+            .trim_start()
+            .strip_prefix('=')
+            .ok_or_else(|| err("expected = after key"))?;
+        let (value, rest) = parse_value(rest.trim_start()).map_err(|message| err(&message))?;
+        if !strip_comment(rest).trim().is_empty() {
+            return Err(err("unexpected characters after value"));
+        }
+
+        let full_key = if table.is_empty() {
+            key
+        } else {
+            format!("{}.{}", table, key)
+        };
+        if entries.contains_key(&full_key) {
+            return Err(err(&format!("duplicate key {}", full_key)));
+        }
+        entries.insert(
+            full_key,
+            Entry {
+                value,
+                line: line_number,
+            },
+        );
+    }
+
+    Ok(entries)
+}
+
+fn strip_comment(text: &str) -> &str {
+    match text.find('#') {
+        Some(start) => &text[..start],
+        None => text,
+    }
+}
+
+/// Parses a bare or quoted key, possibly dotted, returning it and the rest of the line.
+fn parse_key(text: &str) -> Result<(String, &str), String> {
+    let mut key = String::new();
+    let mut rest = text;
+
+    loop {
+        rest = rest.trim_start();
+        if rest.starts_with('"') || rest.starts_with('\'') {
+            let (part, after) = parse_string(rest)?;
+            key.push_str(&part);
+            rest = after;
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err("expected a key".to_string());
+            }
+            key.push_str(&rest[..end]);
+            rest = &rest[end..];
+        }
+
+        match rest.trim_start().strip_prefix('.') {
+            Some(after) => {
+                key.push('.');
+                rest = after;
+            }
+            None => return Ok((key, rest)),
+        }
+    }
+}
+
+fn parse_value(text: &str) -> Result<(Value, &str), String> {
+    if text.starts_with('"') || text.starts_with('\'') {
+        let (string, rest) = parse_string(text)?;
+        return Ok((Value::String(string), rest));
+    }
+
+    let end = text
+        .find(|c: char| c.is_whitespace() || c == '#')
+        .unwrap_or(text.len());
+    let (word, rest) = text.split_at(end);
+
+    let value = match word {
+        "true" => Value::Boolean(true),
+        "false" => Value::Boolean(false),
+        "" => return Err("expected a value".to_string()),
+        word => {
+            Value::Integer(parse_integer(word).ok_or_else(|| format!("invalid value {}", word))?)
+        }
+    };
+    Ok((value, rest))
+}
+
+fn parse_integer(word: &str) -> Option<i64> {
+    let digits = word.replace('_', "");
+    let (negative, digits) = match digits.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, digits.strip_prefix('+').unwrap_or(&digits)),
+    };
+
+    let number = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -number } else { number })
+}
+
+/// Parses a "basic" (with escapes) or 'literal' string.
+fn parse_string(text: &str) -> Result<(String, &str), String> {
+    let quote = text.chars().next().ok_or("expected a string")?;
+    let mut string = String::new();
+    let mut chars = text[1..].char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c == quote => return Ok((string, &text[i + 2..])),
+            '\\' if quote == '"' => match chars.next().map(|(_, c)| c) {
+                Some('"') => string.push('"'),
+                Some('\\') => string.push('\\'),
+                Some('n') => string.push('\n'),
+                Some('t') => string.push('\t'),
+                _ => return Err("invalid escape in string".to_string()),
+            },
+            c => string.push(c),
+        }
+    }
+
+    Err("unterminated string".to_string())
+}
@@ -0,0 +1,228 @@
+//! The cached engine. Every address is decoded once, into a handler function
+//! and the operands it takes, so a step is a table lookup and an indirect call
+//! instead of a walk through the opcode ranges.
+//!
+//! Each entry remembers the opcode it was decoded from, and a fetch that reads
+//! something else decodes it again. That way self-modifying code, loading a
+//! save state or a debugger poking memory all invalidate stale entries
+//! without `Memory` knowing about the cache.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::Cpu;
+use crate::error::Error;
+use crate::instruction::Instruction;
+use crate::memory::MEMORY_SIZE;
+
+type Handler = fn(&mut Cpu, Operands) -> Result<(), Error>;
+
+/// Every field an instruction can take, cut out of the opcode up front.
+#[derive(Clone, Copy)]
+struct Operands {
+    x: u8,
+    y: u8,
+    kk: u8,
+    n: u8,
+    addr: u16,
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    opcode: u16,
+    handler: Handler,
+    operands: Operands,
+}
+
+/// Decoded entries by address, allocated on first use so machines on the
+/// simple engine don't pay for it. Writes don't touch it: comparing the
+/// opcode on every fetch is the only invalidation there is.
+pub(super) struct Cache {
+    entries: Vec<Option<Entry>>,
+}
+
+impl Cache {
+    pub(super) fn new() -> Cache {
+        Cache {
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl Entry {
+    fn decode(opcode: u16) -> Result<Entry, Error> {
+        Ok(Entry {
+            opcode,
+            handler: handler(Instruction::decode(opcode)?),
+            operands: Operands {
+                x: (opcode >> 8 & 0xF) as u8,
+                y: (opcode >> 4 & 0xF) as u8,
+                kk: (opcode & 0xFF) as u8,
+                n: (opcode & 0xF) as u8,
+                addr: opcode & 0x0FFF,
+            },
+        })
+    }
+}
+
+impl Cpu {
+    /// Executes `opcode`, fetched from `pc`, through the cache.
+    pub(super) fn execute_cached(&mut self, pc: usize, opcode: u16) -> Result<(), Error> {
+        if self.cache.entries.is_empty() {
+            self.cache.entries = vec![None; MEMORY_SIZE];
+        }
+
+        let entry = match self.cache.entries[pc] {
+            Some(entry) if entry.opcode == opcode => entry,
+            _ => {
+                let entry = Entry::decode(opcode)?;
+                self.cache.entries[pc] = Some(entry);
+                entry
+            }
+        };
+        (entry.handler)(self, entry.operands)
+    }
+}
+
+/// The handler for an instruction. These mirror `Cpu::execute` arm by arm,
+/// which stays the reference; `chip8 test --differential` compares the two.
+fn handler(instruction: Instruction) -> Handler {
+    match instruction {
+        Instruction::Sys { addr: 0 } => |cpu, _| {
+            cpu.halted = true;
+            Ok(())
+        },
+        Instruction::Sys { .. } => |cpu, op| cpu.sys(op.addr),
+        Instruction::Cls => |cpu, _| {
+            cpu.display.clear();
+            Ok(())
+        },
+        Instruction::Ret => |cpu, _| cpu.ret(),
+        Instruction::Exit => |cpu, _| {
+            cpu.exit();
+            Ok(())
+        },
+        Instruction::Jump { .. } => |cpu, op| {
+            cpu.jump(op.addr);
+            Ok(())
+        },
+        Instruction::Call { .. } => |cpu, op| cpu.call(op.addr),
+        Instruction::SeXkk { .. } => |cpu, op| {
+            cpu.se_xkk(op.x, op.kk);
+            Ok(())
+        },
+        Instruction::SneXkk { .. } => |cpu, op| {
+            cpu.sne(cpu.registers[op.x as usize], op.kk);
+            Ok(())
+        },
+        Instruction::SeXy { .. } => |cpu, op| {
+            cpu.se_xy(op.x, op.y);
+            Ok(())
+        },
+        Instruction::Set { .. } => |cpu, op| {
+            cpu.set(op.x, op.kk);
+            Ok(())
+        },
+        Instruction::Add { .. } => |cpu, op| {
+            cpu.add(op.x, op.kk);
+            Ok(())
+        },
+        Instruction::SetXy { .. } => |cpu, op| {
+            cpu.set_xy(op.x, op.y);
+            Ok(())
+        },
+        Instruction::OrXy { .. } => |cpu, op| {
+            cpu.or_xy(op.x, op.y);
+            Ok(())
+        },
+        Instruction::AndXy { .. } => |cpu, op| {
+            cpu.and_xy(op.x, op.y);
+            Ok(())
+        },
+        Instruction::XorXy { .. } => |cpu, op| {
+            cpu.xor_xy(op.x, op.y);
+            Ok(())
+        },
+        Instruction::AddXy { .. } => |cpu, op| {
+            cpu.add_xy(op.x, op.y);
+            Ok(())
+        },
+        Instruction::SubXy { .. } => |cpu, op| {
+            cpu.sub_xy(op.x, op.y);
+            Ok(())
+        },
+        Instruction::ShrXy { .. } => |cpu, op| {
+            cpu.shr_xy(op.x, op.y);
+            Ok(())
+        },
+        Instruction::SubnXy { .. } => |cpu, op| {
+            cpu.subn_xy(op.x, op.y);
+            Ok(())
+        },
+        Instruction::ShlXy { .. } => |cpu, op| {
+            cpu.shl_xy(op.x, op.y);
+            Ok(())
+        },
+        Instruction::SneXy { .. } => |cpu, op| {
+            cpu.sne_xy(op.x, op.y);
+            Ok(())
+        },
+        Instruction::SetI { .. } => |cpu, op| {
+            cpu.index = op.addr;
+            Ok(())
+        },
+        Instruction::JumpV0 { .. } => |cpu, op| {
+            cpu.jump_v0(op.addr);
+            Ok(())
+        },
+        Instruction::Rand { .. } => |cpu, op| {
+            cpu.registers[op.x as usize] = cpu.rng.next_u8() & op.kk;
+            Ok(())
+        },
+        Instruction::Draw { .. } => |cpu, op| cpu.draw(op.x, op.y, op.n),
+        Instruction::SkipKey { .. } => |cpu, op| {
+            cpu.skip_key(op.x);
+            Ok(())
+        },
+        Instruction::SkipNotKey { .. } => |cpu, op| {
+            cpu.skip_not_key(op.x);
+            Ok(())
+        },
+        Instruction::Audio => |cpu, _| cpu.audio(),
+        Instruction::GetDelay { .. } => |cpu, op| {
+            cpu.registers[op.x as usize] = cpu.delay_timer;
+            Ok(())
+        },
+        Instruction::WaitKey { .. } => |cpu, op| {
+            cpu.wait_key(op.x);
+            Ok(())
+        },
+        Instruction::SetDelay { .. } => |cpu, op| {
+            cpu.delay_timer = cpu.registers[op.x as usize];
+            Ok(())
+        },
+        Instruction::SetSound { .. } => |cpu, op| {
+            cpu.sound_timer = cpu.registers[op.x as usize];
+            Ok(())
+        },
+        Instruction::AddI { .. } => |cpu, op| {
+            cpu.add_i(op.x);
+            Ok(())
+        },
+        Instruction::Font { .. } => |cpu, op| {
+            cpu.font(op.x);
+            Ok(())
+        },
+        Instruction::BigFont { .. } => |cpu, op| {
+            cpu.big_font(op.x);
+            Ok(())
+        },
+        Instruction::Pitch { .. } => |cpu, op| {
+            cpu.pitch = cpu.registers[op.x as usize];
+            Ok(())
+        },
+        Instruction::Bcd { .. } => |cpu, op| cpu.bcd(op.x),
+        Instruction::Store { .. } => |cpu, op| cpu.store(op.x),
+        Instruction::Load { .. } => |cpu, op| cpu.load(op.x),
+    }
+}
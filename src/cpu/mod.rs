@@ -0,0 +1,901 @@
+use crate::assertion::Assertion;
+use crate::display::Display;
+use crate::error::Error;
+use crate::font::{Font, BIG_FONT_ADDR, BIG_FONT_HEIGHT, FONTS, FONT_ADDR, FONT_HEIGHT};
+#[cfg(feature = "std")]
+use crate::frontend::PixelStyle;
+#[cfg(feature = "std")]
+use crate::image::Image;
+use crate::instruction::Instruction;
+use crate::log::{self, event, Level, Span};
+use crate::memory::{AddressSet, Memory, MEMORY_SIZE, PROGRAM_START};
+use crate::overrides::Overrides;
+use crate::platform::Platform;
+#[cfg(feature = "alloc")]
+use crate::profile::Profile;
+#[cfg(feature = "alloc")]
+use crate::provenance::{Origin, Provenance};
+use crate::quirks::Quirks;
+use crate::rng::Rng;
+#[cfg(feature = "alloc")]
+use crate::self_modify::SelfModify;
+
+#[cfg(feature = "alloc")]
+mod cached;
+
+/// Roughly 600 instructions per second at 60 frames per second.
+pub const INSTRUCTIONS_PER_FRAME: usize = 10;
+
+/// The XO-CHIP pitch until Fx3A sets one: the pattern plays at 4000 bits
+/// a second.
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// What happens when the program counter runs past the end of memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PcOverflow {
+    /// Stop with `Error::ProgramCounterOutOfBounds`.
+    #[default]
+    Error,
+    /// Continue from the start of memory, like the 12 bit address bus of the VIP.
+    Wrap,
+}
+
+/// What happens when an instruction is fetched from an odd address.
+///
+/// The VIP fetches any two bytes just fine and some ROMs rely on it, e.g. by
+/// packing code around data, but in most it means a jump went wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OddPc {
+    #[default]
+    Allow,
+    /// Run it, but remember the address in `Cpu::odd_pcs`.
+    Warn,
+    /// Strict mode: stop with `Error::UnalignedProgramCounter`.
+    Trap,
+}
+
+/// What 00FD, SUPER-CHIP's exit, does. The HP-48 went back to its own
+/// menus; an emulator has a few choices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnExit {
+    /// Stop like 0000 does.
+    #[default]
+    Halt,
+    /// Start the ROM over, see `Cpu::reset`.
+    Reset,
+    /// Stop and set `Cpu::exited`, for the frontend to go back to its ROM
+    /// menu.
+    Menu,
+}
+
+/// How instructions are decoded and dispatched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    /// Decodes every opcode as it is fetched. The reference implementation.
+    #[default]
+    Simple,
+    /// Decodes each address once and keeps a handler for it, see `cached`.
+    /// The table is allocated, so this needs the alloc feature.
+    #[cfg(feature = "alloc")]
+    Cached,
+}
+
+/// A Dxyn that turned lit pixels off, caught by `Cpu::break_on_collision`.
+#[derive(Clone)]
+pub struct Collision {
+    /// Where the Dxyn is.
+    pub pc: usize,
+    /// The screen just before it drew.
+    pub before: Display,
+}
+
+pub struct Cpu {
+    pub registers: [u8; 16],
+    pub index: u16,             // the I register
+    pub program_counter: usize, // position in memory
+    pub memory: Memory,
+    pub stack: [u16; 16],
+    pub stack_pointer: usize,
+    pub display: Display,
+    pub keys: [bool; 16], // pressed state of the hex keypad
+    pub delay_timer: u8,
+    pub sound_timer: u8,                 // beeps while non-zero
+    pub audio_pattern: Option<[u8; 16]>, // XO-CHIP sound, None until F002
+    pub pitch: u8,                       // set by Fx3A, see DEFAULT_PITCH
+    pub quirks: Quirks,
+    pub rng: Rng,
+    pub assertions: bool,             // honour the test ROM assertion opcodes
+    pub assertion: Option<Assertion>, // result reported by a test ROM
+    pub pc_overflow: PcOverflow,
+    pub odd_pc: OddPc,
+    pub odd_pcs: AddressSet, // odd addresses executed with OddPc::Warn
+    pub speed: usize,        // instructions per 60Hz frame
+    pub hold_timers: bool,   // frames leave the timers alone, for fast-forward
+    pub halted: bool,        // set by 0000
+    pub paused: bool,        // run_frame does nothing, see pause()
+    pub on_exit: OnExit,
+    pub exited: bool,                 // set by 00FD with OnExit::Menu
+    pub break_on_collision: bool,     // pause right after a Dxyn sets vf
+    pub collision: Option<Collision>, // the Dxyn that paused, until resume()
+    pub engine: Engine,
+    pub overrides: Overrides, // opcodes skipped or run as others
+    /// Counts what runs while set, see `profile`.
+    #[cfg(feature = "alloc")]
+    pub profile: Option<alloc::boxed::Box<Profile>>,
+    /// Watches for code the program wrote itself, see `self_modify`.
+    #[cfg(feature = "alloc")]
+    pub self_modify: Option<alloc::boxed::Box<SelfModify>>,
+    /// Which Dxyn drew each pixel, see `provenance`.
+    #[cfg(feature = "alloc")]
+    pub provenance: Option<alloc::boxed::Box<Provenance>>,
+    #[cfg(feature = "alloc")]
+    cache: cached::Cache,
+    frames: u64,             // timer ticks so far, for the frame span
+    frame_steps: usize,      // instructions run of the current frame
+    logged_keys: [bool; 16], // the keys at the start of the last frame span
+    font: &'static Font,
+    rom: [u8; MEMORY_SIZE - PROGRAM_START], // what load_rom loaded, for reset()
+    rom_len: usize,
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// vx and vy are registers (0-F)
+/// kk is a number between 0 and 255.
+/// addr is an address between 0 and 4095.
+impl Cpu {
+    pub fn new() -> Cpu {
+        let mut cpu = Cpu {
+            registers: [0; 16],
+            index: 0,
+            memory: Memory::new(),
+            program_counter: 0,
+            stack: [0; 16],
+            stack_pointer: 0,
+            display: Display::new(),
+            keys: [false; 16],
+            delay_timer: 0,
+            sound_timer: 0,
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
+            quirks: Quirks::default(),
+            #[cfg(feature = "std")]
+            rng: Rng::from_time(),
+            // there is no clock to seed from, set one with `Rng::new`
+            #[cfg(not(feature = "std"))]
+            rng: Rng::new(0),
+            assertions: false,
+            assertion: None,
+            pc_overflow: PcOverflow::default(),
+            odd_pc: OddPc::default(),
+            odd_pcs: AddressSet::new(),
+            speed: INSTRUCTIONS_PER_FRAME,
+            hold_timers: false,
+            halted: false,
+            paused: false,
+            on_exit: OnExit::default(),
+            exited: false,
+            break_on_collision: false,
+            collision: None,
+            engine: Engine::default(),
+            overrides: Overrides::default(),
+            #[cfg(feature = "alloc")]
+            profile: None,
+            #[cfg(feature = "alloc")]
+            self_modify: None,
+            #[cfg(feature = "alloc")]
+            provenance: None,
+            #[cfg(feature = "alloc")]
+            cache: cached::Cache::new(),
+            frames: 0,
+            frame_steps: 0,
+            logged_keys: [false; 16],
+            font: &FONTS[0],
+            rom: [0; MEMORY_SIZE - PROGRAM_START],
+            rom_len: 0,
+        };
+        cpu.load_font(&FONTS[0]);
+        cpu
+    }
+
+    /// Replaces the digit sprites in the interpreter area.
+    pub fn load_font(&mut self, font: &'static Font) {
+        self.font = font;
+        self.memory
+            .load(FONT_ADDR, &font.bytes())
+            .expect("font fits in the interpreter area");
+    }
+
+    /// Copies a ROM to 0x200 and points the program counter at it.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), Error> {
+        if rom.len() > MEMORY_SIZE - PROGRAM_START {
+            return Err(Error::RomTooLarge { size: rom.len() });
+        }
+
+        self.memory.load(PROGRAM_START, rom)?;
+        self.rom[..rom.len()].copy_from_slice(rom);
+        self.rom_len = rom.len();
+        self.program_counter = PROGRAM_START;
+        Ok(())
+    }
+
+    /// Stops run_frame from running instructions or ticking the timers until
+    /// resume().
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.collision = None;
+    }
+
+    /// Changes engines, forgetting whatever the old one kept for itself. Safe
+    /// between any two steps; `savestate::switch_engine` does it through a
+    /// snapshot.
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+        #[cfg(feature = "alloc")]
+        {
+            self.cache = cached::Cache::new();
+        }
+    }
+
+    /// Like the reset button: the ROM and font are loaded again over whatever
+    /// the program changed, and registers, stack, timers and the display are
+    /// cleared. The rest of memory is left as it was, and settings such as the
+    /// quirks and speed stay.
+    pub fn reset(&mut self) {
+        self.registers = [0; 16];
+        self.index = 0;
+        self.stack = [0; 16];
+        self.stack_pointer = 0;
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.audio_pattern = None;
+        self.pitch = DEFAULT_PITCH;
+        self.display.clear();
+        self.assertion = None;
+        self.halted = false;
+        self.paused = false;
+        self.exited = false;
+        self.collision = None;
+        #[cfg(feature = "alloc")]
+        if let Some(provenance) = &mut self.provenance {
+            provenance.clear();
+        }
+        self.load_font(self.font);
+        self.memory
+            .load(PROGRAM_START, &self.rom[..self.rom_len])
+            .expect("the ROM fitted when it was loaded");
+        self.program_counter = PROGRAM_START;
+    }
+
+    /// Turning it off and on again: a reset that also clears all of memory.
+    pub fn power_cycle(&mut self) {
+        self.memory.clear();
+        self.reset();
+    }
+
+    /// The screen as it is now, in the style's colours and scale.
+    #[cfg(feature = "std")]
+    pub fn screenshot(&self, style: &PixelStyle) -> Image {
+        Image::of(&self.display, style)
+    }
+
+    /// Whether the program has stopped for good without halting: the next
+    /// instruction jumps to itself, which is how many ROMs end.
+    pub fn stuck(&self) -> bool {
+        let pc = self.program_counter;
+        self.memory.read_word(pc).ok() == Some(0x1000 | pc as u16)
+    }
+
+    /// Runs until a 0000 opcode is reached.
+    pub fn run(&mut self) -> Result<(), Error> {
+        while !self.halted {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Runs one 60Hz frame worth (`speed`) of instructions, stopping early on
+    /// halt, then ticks the timers. Does nothing while paused. A collision
+    /// break pauses in the middle of a frame; the rest of it runs after
+    /// resume().
+    pub fn run_frame(&mut self) -> Result<(), Error> {
+        if self.paused {
+            return Ok(());
+        }
+        let _span = self.frame_span();
+        while self.frame_steps < self.speed {
+            if self.halted {
+                break;
+            }
+            self.frame_steps += 1;
+            self.step()?;
+            if self.paused {
+                return Ok(());
+            }
+        }
+        self.frame_steps = 0;
+        self.tick_timers();
+        Ok(())
+    }
+
+    /// The span the events of a frame go in, for whatever runs frames.
+    /// Logs the keys that changed since the last one.
+    pub fn frame_span(&mut self) -> Span {
+        let frame = self.frames;
+        let span = log::span("frame", format_args!("n={}", frame));
+        if log::enabled(Level::Debug, "chip8::keypad") {
+            for (key, (&now, &before)) in self.keys.iter().zip(&self.logged_keys).enumerate() {
+                if now != before {
+                    let change = if now { "pressed" } else { "released" };
+                    event!(Level::Debug, "chip8::keypad", "key {:X} {}", key, change);
+                }
+            }
+        }
+        self.logged_keys = self.keys;
+        span
+    }
+
+    /// Counts both timers down, called at 60Hz. Does nothing while
+    /// `hold_timers` is set, so extra frames run to fast-forward can leave
+    /// the timers at the pace of the wall clock.
+    pub fn tick_timers(&mut self) {
+        if self.hold_timers {
+            return;
+        }
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+        self.frames += 1;
+        event!(
+            Level::Trace,
+            "chip8::timers",
+            "dt={} st={}",
+            self.delay_timer,
+            self.sound_timer
+        );
+    }
+
+    pub fn sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    /// Fetches, decodes and executes a single instruction.
+    pub fn step(&mut self) -> Result<(), Error> {
+        let pc = self.program_counter;
+        let result = self.fetch_and_execute();
+        if let Err(err) = &result {
+            event!(Level::Error, "chip8::cpu", "{:#05x}: {}", pc, err);
+        }
+        result
+    }
+
+    fn fetch_and_execute(&mut self) -> Result<(), Error> {
+        let mut opcode = self.fetch()?;
+        let pc = self.program_counter;
+        if !self.overrides.is_empty() {
+            match self.overrides.apply(opcode) {
+                Some(replacement) => opcode = replacement,
+                None => {
+                    event!(
+                        Level::Trace,
+                        "chip8::cpu",
+                        "{:#05x}: {:04x} skipped",
+                        pc,
+                        opcode
+                    );
+                    self.program_counter += 2;
+                    return Ok(());
+                }
+            }
+        }
+        if log::enabled(Level::Trace, "chip8::cpu") {
+            match Instruction::decode(opcode) {
+                Ok(instruction) => log::log(
+                    Level::Trace,
+                    "chip8::cpu",
+                    format_args!("{:#05x}: {:04x} {}", pc, opcode, instruction),
+                ),
+                Err(err) => log::log(
+                    Level::Trace,
+                    "chip8::cpu",
+                    format_args!("{:#05x}: {}", pc, err),
+                ),
+            }
+        }
+        #[cfg(feature = "alloc")]
+        if let Some(profile) = &mut self.profile {
+            profile.record(pc, opcode);
+        }
+        #[cfg(feature = "alloc")]
+        if let Some(writer) = self
+            .self_modify
+            .as_mut()
+            .and_then(|self_modify| self_modify.record_execute(pc))
+        {
+            log::log(
+                Level::Warn,
+                "chip8::cpu",
+                format_args!("{:#05x}: running code written by {:#05x}", pc, writer),
+            );
+        }
+
+        self.program_counter += 2; // 1 opcode = 2 u8
+
+        match self.engine {
+            Engine::Simple => self.execute(Instruction::decode(opcode)?),
+            #[cfg(feature = "alloc")]
+            Engine::Cached => self.execute_cached(pc, opcode),
+        }
+    }
+
+    /// Executes an instruction, with the program counter already past it.
+    fn execute(&mut self, instruction: Instruction) -> Result<(), Error> {
+        match instruction {
+            Instruction::Sys { addr: 0 } => self.halted = true,
+            Instruction::Sys { addr } => self.sys(addr)?,
+            Instruction::Cls => self.display.clear(),
+            Instruction::Ret => self.ret()?,
+            Instruction::Exit => self.exit(),
+            Instruction::Jump { addr } => self.jump(addr),
+            Instruction::Call { addr } => self.call(addr)?,
+            Instruction::SeXkk { x, kk } => self.se_xkk(x, kk),
+            Instruction::SneXkk { x, kk } => self.sne(self.registers[x as usize], kk),
+            Instruction::SeXy { x, y } => self.se_xy(x, y),
+            Instruction::Set { x, kk } => self.set(x, kk),
+            Instruction::Add { x, kk } => self.add(x, kk),
+            Instruction::SetXy { x, y } => self.set_xy(x, y),
+            Instruction::OrXy { x, y } => self.or_xy(x, y),
+            Instruction::AndXy { x, y } => self.and_xy(x, y),
+            Instruction::XorXy { x, y } => self.xor_xy(x, y),
+            Instruction::AddXy { x, y } => self.add_xy(x, y),
+            Instruction::SubXy { x, y } => self.sub_xy(x, y),
+            Instruction::ShrXy { x, y } => self.shr_xy(x, y),
+            Instruction::SubnXy { x, y } => self.subn_xy(x, y),
+            Instruction::ShlXy { x, y } => self.shl_xy(x, y),
+            Instruction::SneXy { x, y } => self.sne_xy(x, y),
+            Instruction::SetI { addr } => self.index = addr,
+            Instruction::JumpV0 { addr } => self.jump_v0(addr),
+            Instruction::Rand { x, kk } => self.registers[x as usize] = self.rng.next_u8() & kk,
+            Instruction::Draw { x, y, n } => self.draw(x, y, n)?,
+            Instruction::SkipKey { x } => self.skip_key(x),
+            Instruction::SkipNotKey { x } => self.skip_not_key(x),
+            Instruction::Audio => self.audio()?,
+            Instruction::GetDelay { x } => self.registers[x as usize] = self.delay_timer,
+            Instruction::WaitKey { x } => self.wait_key(x),
+            Instruction::SetDelay { x } => self.delay_timer = self.registers[x as usize],
+            Instruction::SetSound { x } => self.sound_timer = self.registers[x as usize],
+            Instruction::AddI { x } => self.add_i(x),
+            Instruction::Font { x } => self.font(x),
+            Instruction::BigFont { x } => self.big_font(x),
+            Instruction::Pitch { x } => self.pitch = self.registers[x as usize],
+            Instruction::Bcd { x } => self.bcd(x)?,
+            Instruction::Store { x } => self.store(x)?,
+            Instruction::Load { x } => self.load(x)?,
+        };
+        Ok(())
+    }
+
+    /// Reads the opcode at the program counter, applying the overflow policy.
+    fn fetch(&mut self) -> Result<u16, Error> {
+        let pc = self.program_counter;
+
+        if pc % 2 == 1 {
+            match self.odd_pc {
+                OddPc::Allow => {}
+                OddPc::Warn => {
+                    self.odd_pcs.insert(pc);
+                }
+                OddPc::Trap => return Err(Error::UnalignedProgramCounter { pc }),
+            }
+        }
+
+        match self.pc_overflow {
+            PcOverflow::Error => {
+                // the second byte of the opcode has to fit as well
+                if pc + 1 >= MEMORY_SIZE {
+                    return Err(Error::ProgramCounterOutOfBounds { pc });
+                }
+                self.memory.read_word(pc)
+            }
+            PcOverflow::Wrap => {
+                let pc = pc % MEMORY_SIZE;
+                self.program_counter = pc;
+
+                let hi = self.memory.peek_byte(pc)? as u16;
+                let lo = self.memory.peek_byte((pc + 1) % MEMORY_SIZE)? as u16;
+                Ok((hi << 8) | lo)
+            }
+        }
+    }
+
+    /// 0nnn: machine code routines are not supported, apart from test ROM
+    /// assertions. Later platforms put instructions in this range.
+    fn sys(&mut self, addr: u16) -> Result<(), Error> {
+        if let Some(platform) = Platform::introducing(addr) {
+            return Err(Error::UnsupportedInstruction {
+                opcode: addr,
+                platform,
+            });
+        }
+        if self.assertions {
+            let assertion = Assertion::from_sys(addr, self.index, &self.memory);
+            if assertion.is_some() {
+                self.assertion = assertion;
+                self.halted = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// 00FD: exit the interpreter
+    fn exit(&mut self) {
+        match self.on_exit {
+            OnExit::Halt => self.halted = true,
+            OnExit::Reset => self.reset(),
+            OnExit::Menu => {
+                self.halted = true;
+                self.exited = true;
+            }
+        }
+    }
+
+    /// 00EE: return from the current sub-routine
+    fn ret(&mut self) -> Result<(), Error> {
+        if self.stack_pointer == 0 {
+            return Err(Error::StackUnderflow);
+        }
+
+        self.stack_pointer -= 1;
+        let call_addr = self.stack[self.stack_pointer];
+        self.program_counter = call_addr as usize;
+        Ok(())
+    }
+
+    /// 1nnn: jump to nnn address
+    fn jump(&mut self, addr: u16) {
+        self.program_counter = addr as usize;
+    }
+
+    /// 2nnn: call sub-routine at addr
+    fn call(&mut self, addr: u16) -> Result<(), Error> {
+        let stack_ptr = self.stack_pointer;
+
+        if stack_ptr >= self.stack.len() {
+            return Err(Error::StackOverflow);
+        }
+
+        self.stack[stack_ptr] = self.program_counter as u16;
+        self.stack_pointer += 1;
+        self.program_counter = addr as usize;
+        Ok(())
+    }
+
+    /// 3xkk: store if vx == kk
+    fn se_xkk(&mut self, x: u8, kk: u8) {
+        if self.registers[x as usize] == kk {
+            self.program_counter += 2;
+        }
+    }
+
+    /// 4xkk: store if vx not equal kk
+    fn sne(&mut self, vx: u8, kk: u8) {
+        if vx != kk {
+            self.program_counter += 2;
+        }
+    }
+
+    /// 5xy0: store if vx == vy
+    fn se_xy(&mut self, x: u8, y: u8) {
+        let vx = self.registers[x as usize];
+        let vy = self.registers[y as usize];
+        if vx == vy {
+            self.program_counter += 2;
+        }
+    }
+
+    /// 6xkk: set register x to kk
+    fn set(&mut self, x: u8, kk: u8) {
+        self.registers[x as usize] = kk;
+    }
+
+    /// 7xkk: add kk to register x, without touching the carry flag
+    fn add(&mut self, vx: u8, kk: u8) {
+        self.registers[vx as usize] = self.registers[vx as usize].wrapping_add(kk);
+    }
+
+    /// 8xy0: set register x to vy
+    fn set_xy(&mut self, x: u8, y: u8) {
+        let vy = self.registers[y as usize];
+        self.set(x, vy);
+    }
+
+    /// 8xy2: vx &= vy
+    fn and_xy(&mut self, x: u8, y: u8) {
+        let vx = self.registers[x as usize];
+        let vy = self.registers[y as usize];
+
+        self.registers[x as usize] = vx & vy;
+        self.logic_vf();
+    }
+
+    /// 8xy1: vx |= vy
+    fn or_xy(&mut self, x: u8, y: u8) {
+        let vx = self.registers[x as usize];
+        let vy = self.registers[y as usize];
+
+        self.registers[x as usize] = vx | vy;
+        self.logic_vf();
+    }
+
+    /// 8xy3: vx ^= vy
+    fn xor_xy(&mut self, x: u8, y: u8) {
+        let vx = self.registers[x as usize];
+        let vy = self.registers[y as usize];
+
+        self.registers[x as usize] = vx ^ vy;
+        self.logic_vf();
+    }
+
+    fn logic_vf(&mut self) {
+        if self.quirks.logic_resets_vf {
+            self.registers[0xF] = 0;
+        }
+    }
+
+    /// 8xy4: add vy to vx
+    fn add_xy(&mut self, x: u8, y: u8) {
+        let vx = self.registers[x as usize];
+        let vy = self.registers[y as usize];
+
+        let (val, overflow) = vx.overflowing_add(vy);
+        self.registers[x as usize] = val;
+
+        // last register of CHIP-8 is a carry flag.
+        // if set indicates that an operation has overflowed the u8 register size
+        if overflow {
+            self.registers[0xF] = 1;
+        } else {
+            self.registers[0xF] = 0;
+        }
+    }
+
+    /// 8xy5: subtract vy from vx, vf = 1 when there is no borrow
+    fn sub_xy(&mut self, x: u8, y: u8) {
+        let vx = self.registers[x as usize];
+        let vy = self.registers[y as usize];
+
+        let (val, borrow) = vx.overflowing_sub(vy);
+        self.registers[x as usize] = val;
+        self.registers[0xF] = !borrow as u8;
+    }
+
+    /// 8xy7: set vx to vy - vx, vf = 1 when there is no borrow
+    fn subn_xy(&mut self, x: u8, y: u8) {
+        let vx = self.registers[x as usize];
+        let vy = self.registers[y as usize];
+
+        let (val, borrow) = vy.overflowing_sub(vx);
+        self.registers[x as usize] = val;
+        self.registers[0xF] = !borrow as u8;
+    }
+
+    /// 8xy6: shift right by one, vf = the bit shifted out
+    fn shr_xy(&mut self, x: u8, y: u8) {
+        let source = if self.quirks.shift_uses_vy { y } else { x };
+        let value = self.registers[source as usize];
+
+        self.registers[x as usize] = value >> 1;
+        self.registers[0xF] = value & 1;
+    }
+
+    /// 8xyE: shift left by one, vf = the bit shifted out
+    fn shl_xy(&mut self, x: u8, y: u8) {
+        let source = if self.quirks.shift_uses_vy { y } else { x };
+        let value = self.registers[source as usize];
+
+        self.registers[x as usize] = value << 1;
+        self.registers[0xF] = value >> 7;
+    }
+
+    /// 9xy0: skip if vx != vy
+    fn sne_xy(&mut self, x: u8, y: u8) {
+        let vx = self.registers[x as usize];
+        let vy = self.registers[y as usize];
+        if vx != vy {
+            self.program_counter += 2;
+        }
+    }
+
+    /// Bnnn: jump to nnn + v0, or xnn + vx with the CHIP-48 quirk
+    fn jump_v0(&mut self, addr: u16) {
+        let register = if self.quirks.jump_uses_vx {
+            (addr >> 8) as usize
+        } else {
+            0
+        };
+
+        self.program_counter = (addr + self.registers[register] as u16) as usize;
+    }
+
+    /// Ex9E: skip if the key in vx is pressed
+    fn skip_key(&mut self, x: u8) {
+        let key = self.registers[x as usize] & 0xF;
+        self.program_counter += 2 * self.keys[key as usize] as usize;
+    }
+
+    /// ExA1: skip if the key in vx is not pressed
+    fn skip_not_key(&mut self, x: u8) {
+        let key = self.registers[x as usize] & 0xF;
+        self.program_counter += 2 * !self.keys[key as usize] as usize;
+    }
+
+    /// Fx0A: wait until a key is pressed and store it in vx
+    fn wait_key(&mut self, x: u8) {
+        match self.keys.iter().position(|&pressed| pressed) {
+            Some(key) => self.registers[x as usize] = key as u8,
+            // run this instruction again until something is pressed
+            None => self.program_counter -= 2,
+        }
+    }
+
+    /// Fx1E: add vx to I
+    fn add_i(&mut self, x: u8) {
+        self.index = self.index.wrapping_add(self.registers[x as usize] as u16) & 0x0FFF;
+    }
+
+    /// Fx29: point I at the font sprite for the digit in vx
+    fn font(&mut self, x: u8) {
+        let digit = (self.registers[x as usize] & 0xF) as usize;
+        self.index = (FONT_ADDR + digit * FONT_HEIGHT) as u16;
+    }
+
+    /// Fx30: point I at the big font sprite for the digit in vx
+    fn big_font(&mut self, x: u8) {
+        let digit = (self.registers[x as usize] & 0xF) as usize;
+        self.index = (BIG_FONT_ADDR + digit * BIG_FONT_HEIGHT) as u16;
+    }
+
+    /// F002: load the 16 byte (128 bit) audio pattern from I
+    fn audio(&mut self) -> Result<(), Error> {
+        let mut pattern = [0; 16];
+        for (i, byte) in pattern.iter_mut().enumerate() {
+            *byte = self.memory.read_byte(self.index as usize + i)?;
+        }
+        self.audio_pattern = Some(pattern);
+        Ok(())
+    }
+
+    /// A write by the instruction that just ran, which `self_modify` keeps
+    /// track of.
+    fn write_byte(&mut self, addr: usize, value: u8) -> Result<(), Error> {
+        self.memory.write_byte(addr, value)?;
+        #[cfg(feature = "alloc")]
+        if let Some(self_modify) = &mut self.self_modify {
+            self_modify.record_write(addr, self.program_counter - 2);
+        }
+        Ok(())
+    }
+
+    /// Fx33: store the hundreds, tens and ones of vx at I, I+1 and I+2
+    fn bcd(&mut self, x: u8) -> Result<(), Error> {
+        let vx = self.registers[x as usize];
+        let addr = self.index as usize;
+
+        self.write_byte(addr, vx / 100)?;
+        self.write_byte(addr + 1, vx / 10 % 10)?;
+        self.write_byte(addr + 2, vx % 10)
+    }
+
+    /// Fx55: store v0 to vx in memory starting at I
+    fn store(&mut self, x: u8) -> Result<(), Error> {
+        for i in 0..=x as usize {
+            self.write_byte(self.index as usize + i, self.registers[i])?;
+        }
+        if self.quirks.load_store_increments_i {
+            self.index += x as u16 + 1;
+        }
+        Ok(())
+    }
+
+    /// Fx65: load v0 to vx from memory starting at I
+    fn load(&mut self, x: u8) -> Result<(), Error> {
+        for i in 0..=x as usize {
+            self.registers[i] = self.memory.read_byte(self.index as usize + i)?;
+        }
+        if self.quirks.load_store_increments_i {
+            self.index += x as u16 + 1;
+        }
+        Ok(())
+    }
+
+    /// Dxyn: draw n bytes of sprite data from I at (vx, vy), vf = collision
+    fn draw(&mut self, x: u8, y: u8, n: u8) -> Result<(), Error> {
+        let mut sprite = [0u8; 15];
+        for (i, byte) in sprite.iter_mut().take(n as usize).enumerate() {
+            *byte = self.memory.read_byte(self.index as usize + i)?;
+        }
+
+        let vx = self.registers[x as usize];
+        let vy = self.registers[y as usize];
+        #[cfg(feature = "alloc")]
+        let tracked = self.provenance.is_some();
+        #[cfg(not(feature = "alloc"))]
+        let tracked = false;
+        let before = (self.break_on_collision || tracked).then(|| self.display.clone());
+        let collision =
+            self.display
+                .draw_sprite(vx, vy, &sprite[..n as usize], self.quirks.wrap_sprites);
+        self.registers[0xF] = collision as u8;
+        #[cfg(feature = "alloc")]
+        if let (Some(provenance), Some(before)) = (&mut self.provenance, &before) {
+            let pc = self.program_counter - 2;
+            let origin = Origin {
+                pc,
+                frame: self.frames,
+            };
+            let over = provenance.record(before, &self.display, origin);
+            if !over.is_empty() && log::enabled(Level::Trace, "chip8::display") {
+                let pcs: alloc::vec::Vec<_> = over
+                    .iter()
+                    .map(|pc| alloc::format!("{:#05x}", pc))
+                    .collect();
+                log::log(
+                    Level::Trace,
+                    "chip8::display",
+                    format_args!("{:#05x} drew over pixels from {}", pc, pcs.join(", ")),
+                );
+            }
+        }
+        if let Some(before) = before.filter(|_| collision && self.break_on_collision) {
+            self.collision = Some(Collision {
+                pc: self.program_counter - 2,
+                before,
+            });
+            self.paused = true;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::error::Error;
+    use crate::testing;
+    use crate::{assert_pixel, assert_reg};
+
+    #[test]
+    fn big_font_draws_ten_rows() {
+        let mut cpu = testing::assembled("LD V0, 8\nLD HF, V0\nDRW V1, V1, 10\nwait: JP wait");
+        testing::run_until(&mut cpu, |cpu| cpu.program_counter == 0x206);
+        assert_reg!(cpu, VF == 0);
+        // the big 8 is closed at the top and the bottom
+        assert_pixel!(cpu, 2, 0, on);
+        assert_pixel!(cpu, 2, 9, on);
+        assert_pixel!(cpu, 2, 10, off);
+    }
+
+    #[test]
+    fn fetch_errors_leave_the_pc_at_the_instruction() {
+        let mut cpu = testing::assembled("JP 0xFFF");
+        testing::run_until(&mut cpu, |cpu| cpu.program_counter == 0xFFF);
+        assert_eq!(
+            cpu.step(),
+            Err(Error::ProgramCounterOutOfBounds { pc: 0xFFF })
+        );
+        assert_reg!(cpu, PC == 0xFFF);
+    }
+
+    #[test]
+    fn execute_errors_leave_the_pc_past_the_instruction() {
+        let mut cpu = testing::assembled("RET");
+        assert_eq!(cpu.step(), Err(Error::StackUnderflow));
+        assert_reg!(cpu, PC == 0x202);
+    }
+}
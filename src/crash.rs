@@ -0,0 +1,255 @@
+//! Crash bundles: everything it takes to make a game fail again the way it
+//! failed for a player, in one file to attach to a bug report. While a game
+//! runs, a `CrashRecorder` keeps its key presses and a save state every
+//! `SNAPSHOT_FRAMES`; when the game crashes, `chip8 run` offers to write a
+//! `Bundle` from them, and `chip8 repro <bundle> <rom>` plays it back to the
+//! same error.
+//!
+//! The file is plain text, the save states written out in hex:
+//!
+//! ```text
+//! chip8 crash 1
+//! rom pong.ch8
+//! sha1 2c1d3e5d8e1f0b5a...
+//! error 0x2a4: unknown opcode f010
+//! arg --engine
+//! arg cached
+//! opcodes 0230 nop
+//! start 43483853...
+//! snapshot 4200 43483853...
+//! chip8 replay 2
+//! ...
+//! end 4513
+//! ```
+//!
+//! `start` is the machine where the recording starts, at power on or after
+//! the last jump in time (a state loaded, a rewind, a reset); `snapshot` is
+//! one from 5 to 10 seconds before the crash, to begin from instead of
+//! playing the whole session. The recording after them is a `replay` whose
+//! seed means nothing, the states carry the random number generator. The ROM
+//! itself isn't included, only its name and hash.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::cpu::Cpu;
+use crate::overrides::Rule;
+use crate::replay::{self, Recorder, Replay};
+use crate::savestate::State;
+use crate::sha1;
+use crate::stats;
+
+const HEADER: &str = "chip8 crash 1";
+
+/// Frames between the snapshots kept for a bundle, 5 seconds.
+pub const SNAPSHOT_FRAMES: usize = 300;
+
+pub struct Bundle {
+    /// The file name of the ROM.
+    pub rom: String,
+    /// SHA-1 of the ROM, in hex.
+    pub sha1: String,
+    /// What went wrong, as the player saw it.
+    pub error: String,
+    /// The command line after the ROM, for the machine flags it ran with.
+    pub args: Vec<String>,
+    pub opcodes: Vec<Rule>,
+    pub start: State,
+    /// A later state and the frame of the recording it was taken before.
+    pub snapshot: Option<(usize, State)>,
+    /// The keys from `start` on, the frame that crashed included.
+    pub replay: Replay,
+}
+
+impl Bundle {
+    pub fn load(path: &Path) -> io::Result<Bundle> {
+        Bundle::parse(&fs::read_to_string(path)?)
+            .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_string())
+    }
+
+    pub fn parse(text: &str) -> Result<Bundle, String> {
+        let (head, replay) = text
+            .split_once("\nchip8 replay")
+            .ok_or("missing the recording")?;
+        let mut lines = head.lines().enumerate();
+        if lines.next().map(|(_, line)| line.trim()) != Some(HEADER) {
+            return Err(format!("not a crash bundle, expected \"{}\" first", HEADER));
+        }
+
+        let (mut rom, mut sha1, mut error) = (None, None, None);
+        let (mut args, mut opcodes) = (Vec::new(), Vec::new());
+        let (mut start, mut snapshot) = (None, None);
+        for (number, line) in lines {
+            let invalid = |what: &dyn fmt::Display| format!("line {}: {}", number + 1, what);
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "" => {}
+                "rom" => rom = Some(value.to_string()),
+                "sha1" => sha1 = Some(value.to_string()),
+                "error" => error = Some(value.to_string()),
+                "arg" => args.push(value.to_string()),
+                "opcodes" => {
+                    let (pattern, action) = value.split_once(' ').unwrap_or((value, ""));
+                    opcodes.push(Rule::parse(pattern, action).map_err(|err| invalid(&err))?);
+                }
+                "start" => start = Some(decode_state(value).map_err(|err| invalid(&err))?),
+                "snapshot" => {
+                    let (frame, state) = value.split_once(' ').unwrap_or((value, ""));
+                    let frame = frame.parse().map_err(|_| invalid(&"invalid frame"))?;
+                    snapshot = Some((frame, decode_state(state).map_err(|err| invalid(&err))?));
+                }
+                _ => return Err(invalid(&format!("invalid entry {}", line))),
+            }
+        }
+
+        let replay = Replay::parse(&format!("chip8 replay{}", replay))?;
+        if snapshot
+            .as_ref()
+            .is_some_and(|&(frame, _)| frame >= replay.frames)
+        {
+            return Err("snapshot after the crash".to_string());
+        }
+        Ok(Bundle {
+            rom: rom.ok_or("missing rom")?,
+            sha1: sha1.ok_or("missing sha1")?,
+            error: error.ok_or("missing error")?,
+            args,
+            opcodes,
+            start: start.ok_or("missing start")?,
+            snapshot,
+            replay,
+        })
+    }
+}
+
+impl fmt::Display for Bundle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "rom {}", self.rom)?;
+        writeln!(f, "sha1 {}", self.sha1)?;
+        writeln!(f, "error {}", self.error)?;
+        for arg in &self.args {
+            writeln!(f, "arg {}", arg)?;
+        }
+        for rule in &self.opcodes {
+            // "00FB -> 00E0" is written as the config has it, "00FB 00E0"
+            writeln!(f, "opcodes {}", rule.to_string().replace(" -> ", " "))?;
+        }
+        writeln!(f, "start {}", encode_state(&self.start))?;
+        if let Some((frame, state)) = &self.snapshot {
+            writeln!(f, "snapshot {} {}", frame, encode_state(state))?;
+        }
+        write!(f, "{}", self.replay)
+    }
+}
+
+fn encode_state(state: &State) -> String {
+    state
+        .encode()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn decode_state(hex: &str) -> Result<State, String> {
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or("invalid hex")?;
+    State::decode(&bytes).map_err(|err| err.to_string())
+}
+
+/// Keeps what a bundle needs while a game runs.
+pub struct CrashRecorder {
+    rom: String,
+    sha1: String,
+    rom_hash: u64,
+    args: Vec<String>,
+    start: State,
+    recorder: Recorder,
+    /// The last two snapshots, older first.
+    snapshots: Vec<(usize, State)>,
+    frames: usize,
+}
+
+impl CrashRecorder {
+    /// Starts with the machine as it is, its ROM loaded and set up. `rom` is
+    /// the ROM's file name and `bytes` what is in it.
+    pub fn start(cpu: &Cpu, rom: &str, bytes: &[u8], args: &[String]) -> CrashRecorder {
+        CrashRecorder {
+            rom: rom.to_string(),
+            sha1: sha1::hex(bytes),
+            rom_hash: replay::rom_hash(bytes),
+            args: args.to_vec(),
+            start: State::capture(cpu),
+            recorder: Recorder::resume(cpu, replay::rom_hash(bytes)),
+            snapshots: Vec::new(),
+            frames: 0,
+        }
+    }
+
+    /// Starts over from the machine as it is, after it jumped somewhere the
+    /// recording can't follow.
+    pub fn restart(&mut self, cpu: &Cpu) {
+        self.start = State::capture(cpu);
+        self.recorder = Recorder::resume(cpu, self.rom_hash);
+        self.snapshots.clear();
+        self.frames = 0;
+    }
+
+    /// Call right before emulating each frame, like `Recorder::frame`.
+    pub fn frame(&mut self, cpu: &Cpu) {
+        if self.frames > 0 && self.frames.is_multiple_of(SNAPSHOT_FRAMES) {
+            if self.snapshots.len() == 2 {
+                self.snapshots.remove(0);
+            }
+            self.snapshots.push((self.frames, State::capture(cpu)));
+        }
+        self.recorder.frame(cpu);
+        self.frames += 1;
+    }
+
+    /// The bundle for the frame that just failed with `error`.
+    pub fn bundle(self, cpu: &Cpu, error: &str) -> Bundle {
+        // at least a snapshot's worth of frames before the crash
+        let frames = self.frames;
+        let snapshot = self
+            .snapshots
+            .into_iter()
+            .rev()
+            .find(|(frame, _)| frames - frame > SNAPSHOT_FRAMES);
+        Bundle {
+            rom: self.rom,
+            sha1: self.sha1,
+            error: error.to_string(),
+            args: self.args,
+            opcodes: cpu.overrides.rules().copied().collect(),
+            start: self.start,
+            snapshot,
+            replay: self.recorder.finish(),
+        }
+    }
+}
+
+/// Where `chip8 run` saves bundles for `rom`, the first free
+/// `crashes/<rom>-<n>.crash` in the data directory.
+pub fn path_for(rom: &Path) -> Option<PathBuf> {
+    let name = rom.file_name()?.to_string_lossy().into_owned();
+    let dir = stats::data_dir()?.join("crashes");
+    (1..)
+        .map(|n| dir.join(format!("{}-{}.crash", name, n)))
+        .find(|path| !path.exists())
+}
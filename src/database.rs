@@ -0,0 +1,198 @@
+//! What is known about particular ROMs: title, authors, and the platform,
+//! speed and keys they play best with, applied when the ROM is run.
+//!
+//! Entries come from a JSON file next to the ROM (`game.json` for `game.ch8`,
+//! which `chip8 fetch` writes) or from `database.json` in the data directory,
+//! keyed by the ROM's SHA-1:
+//!
+//! ```text
+//! {
+//!   "<sha1>": {
+//!     "title": "Outlaw",
+//!     "authors": ["John Earnest"],
+//!     "platform": "chip48",
+//!     "quirks": "chip8",       (when they differ from the platform's)
+//!     "tickrate": 15,          (instructions per frame)
+//!     "keys": { "5": "w", "8": "s" },
+//!     "opcodes": { "0230": "nop" }  (see overrides)
+//!   }
+//! }
+//! ```
+//!
+//! A file next to the ROM with a `sha1` that doesn't match is ignored, the
+//! ROM has changed since.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::json::{self, Value};
+use crate::overrides::Rule;
+use crate::platform::{self, Preset};
+use crate::quirks::Quirks;
+use crate::sha1;
+use crate::stats;
+
+const DATABASE_FILE: &str = "database.json";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub platform: Option<&'static Preset>,
+    pub quirks: Option<Quirks>,
+    /// Instructions per frame.
+    pub speed: Option<usize>,
+    /// Host keys for some keypad keys.
+    pub keys: Vec<(u8, char)>,
+    /// Opcode overrides it needs, added to the config's.
+    pub opcodes: Vec<Rule>,
+}
+
+impl Metadata {
+    /// Reads an entry of either file. `tickrate` may also be under `options`,
+    /// as the chip8Archive has it.
+    pub fn parse(entry: &Value) -> Result<Metadata, String> {
+        let text = |key: &str| entry.get(key).and_then(Value::as_str);
+        let platform = text("platform")
+            .map(|name| platform::preset(name).ok_or_else(|| format!("unknown platform {}", name)))
+            .transpose()?;
+        let quirks = text("quirks")
+            .map(|name| Quirks::profile(name).ok_or_else(|| format!("unknown quirks {}", name)))
+            .transpose()?;
+        let speed = entry
+            .get("tickrate")
+            .or_else(|| {
+                entry
+                    .get("options")
+                    .and_then(|options| options.get("tickrate"))
+            })
+            .and_then(Value::as_f64)
+            .filter(|&rate| rate >= 1.0)
+            .map(|rate| rate as usize);
+
+        let mut keys = Vec::new();
+        for (pad_key, host) in entry
+            .get("keys")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+        {
+            let pad_key = u8::from_str_radix(pad_key, 16)
+                .ok()
+                .filter(|&key| key < 16)
+                .ok_or_else(|| format!("invalid keypad key {:?}", pad_key))?;
+            let mut chars = host.as_str().unwrap_or("").chars();
+            match (chars.next(), chars.next()) {
+                (Some(host), None) => keys.push((pad_key, host.to_ascii_lowercase())),
+                _ => return Err(format!("key {:X} needs a single character", pad_key)),
+            }
+        }
+
+        let mut opcodes = Vec::new();
+        for (pattern, action) in entry
+            .get("opcodes")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+        {
+            let rule = Rule::parse(pattern, action.as_str().unwrap_or(""))
+                .map_err(|message| format!("opcode {}: {}", pattern, message))?;
+            opcodes.push(rule);
+        }
+
+        Ok(Metadata {
+            title: text("title").unwrap_or("untitled").to_string(),
+            authors: entry
+                .get("authors")
+                .and_then(Value::as_array)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            platform,
+            quirks,
+            speed,
+            keys,
+            opcodes,
+        })
+    }
+
+    /// Overrides the settings the entry has, the platform's first.
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(preset) = self.platform {
+            preset.apply(config);
+        }
+        if let Some(quirks) = self.quirks {
+            config.quirks = quirks;
+        }
+        if let Some(speed) = self.speed {
+            config.speed = speed;
+        }
+        for &(pad_key, host) in &self.keys {
+            config.keymap.keys[pad_key as usize] = host;
+        }
+        for &rule in &self.opcodes {
+            // past the limit the config's own win
+            let _ = config.opcodes.push(rule);
+        }
+    }
+
+    /// The title with its authors, for logs and window titles.
+    pub fn describe(&self) -> String {
+        if self.authors.is_empty() {
+            self.title.clone()
+        } else {
+            format!("{} by {}", self.title, self.authors.join(", "))
+        }
+    }
+}
+
+/// The database in the data directory.
+pub fn default_path() -> Option<PathBuf> {
+    stats::data_dir().map(|dir| dir.join(DATABASE_FILE))
+}
+
+/// Looks up `rom`, read from `path`, next to it and then in `database`.
+/// Missing files just mean nothing is known.
+pub fn lookup(
+    path: &Path,
+    rom: &[u8],
+    database: Option<&Path>,
+) -> Result<Option<Metadata>, String> {
+    let hash = sha1::hex(rom);
+    let read = |file: &Path| -> Result<Option<Value>, String> {
+        match fs::read_to_string(file) {
+            Ok(text) => json::parse(&text)
+                .map(Some)
+                .map_err(|err| format!("{}: {}", file.display(), err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(format!("{}: {}", file.display(), err)),
+        }
+    };
+
+    let sidecar = path.with_extension("json");
+    if let Some(entry) = read(&sidecar)? {
+        let sha1 = entry.get("sha1").and_then(Value::as_str);
+        if sha1.is_none_or(|sha1| sha1.eq_ignore_ascii_case(&hash)) {
+            return Metadata::parse(&entry)
+                .map(Some)
+                .map_err(|err| format!("{}: {}", sidecar.display(), err));
+        }
+    }
+
+    let Some(database) = database else {
+        return Ok(None);
+    };
+    match read(database)?
+        .as_ref()
+        .and_then(|entries| entries.get(&hash))
+    {
+        Some(entry) => Metadata::parse(entry)
+            .map(Some)
+            .map_err(|err| format!("{}: {}: {}", database.display(), hash, err)),
+        None => Ok(None),
+    }
+}
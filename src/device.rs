@@ -0,0 +1,160 @@
+//! Memory-mapped pseudo-devices, for homebrew experiments: a few bytes of
+//! memory that talk to the host instead of storing anything. No real CHIP-8
+//! machine had these, so a ROM that uses them only runs here, and only in a
+//! build with the devices feature and with the devices mapped:
+//!
+//! ```text
+//! chip8 run game.ch8 --device console@0x1F0 --device clock@0x1F2
+//! ```
+//!
+//! The program's own reads and writes (Fx33, Fx55, Fx65 and the like) reach
+//! a device; fetching opcodes, the debug panel and save states see the plain
+//! memory underneath. Embedding applications can map their own with
+//! `Memory::map`, keeping a handle to get at the device afterwards.
+
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::memory::MEMORY_SIZE;
+
+pub trait Device: Send {
+    /// How many bytes it takes up.
+    fn size(&self) -> usize;
+
+    fn read(&mut self, offset: usize) -> u8;
+
+    fn write(&mut self, offset: usize, value: u8);
+
+    /// Lines of text for the host to print, like the debug mailbox's. With
+    /// `flush`, an unfinished last line too.
+    fn take_output(&mut self, _flush: bool) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// A device as `Memory` keeps it, shared with whoever mapped it.
+pub type Shared = Arc<Mutex<dyn Device>>;
+
+/// A byte-wide console: every byte written is a character, and a newline or
+/// NUL ends the line. Reading it gives 0, ready for more.
+#[derive(Debug, Default)]
+pub struct Console {
+    line: Vec<u8>,
+    lines: Vec<String>,
+}
+
+impl Device for Console {
+    fn size(&self) -> usize {
+        1
+    }
+
+    fn read(&mut self, _offset: usize) -> u8 {
+        0
+    }
+
+    fn write(&mut self, _offset: usize, value: u8) {
+        match value {
+            b'\n' | 0 => {
+                self.lines
+                    .push(String::from_utf8_lossy(&self.line).into_owned());
+                self.line.clear();
+            }
+            byte => self.line.push(byte),
+        }
+    }
+
+    fn take_output(&mut self, flush: bool) -> Vec<String> {
+        let mut lines = std::mem::take(&mut self.lines);
+        if flush && !self.line.is_empty() {
+            lines.push(String::from_utf8_lossy(&self.line).into_owned());
+            self.line.clear();
+        }
+        lines
+    }
+}
+
+/// Milliseconds since it was mapped, as a big endian u16 that wraps about
+/// once a minute. Reading the high byte latches the time, so the low byte
+/// read after it belongs with it: `LD V1, [I]` gets both.
+#[derive(Debug)]
+pub struct Clock {
+    start: Instant,
+    latched: u16,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock {
+            start: Instant::now(),
+            latched: 0,
+        }
+    }
+}
+
+impl Device for Clock {
+    fn size(&self) -> usize {
+        2
+    }
+
+    fn read(&mut self, offset: usize) -> u8 {
+        if offset == 0 {
+            self.latched = self.start.elapsed().as_millis() as u16;
+        }
+        self.latched.to_be_bytes()[offset.min(1)]
+    }
+
+    /// Writing anything starts it from 0 again.
+    fn write(&mut self, _offset: usize, _value: u8) {
+        self.start = Instant::now();
+    }
+}
+
+/// The devices `spec` can name.
+pub const NAMES: [&str; 2] = ["console", "clock"];
+
+/// How many bytes the device called `name` takes up.
+fn size(name: &str) -> usize {
+    match name {
+        "console" => 1,
+        _ => 2,
+    }
+}
+
+/// A `name@addr` from the command line, e.g. `clock@0x1F2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spec {
+    pub name: String,
+    pub addr: usize,
+}
+
+impl Spec {
+    pub fn parse(text: &str) -> Result<Spec, String> {
+        let (name, addr) = text
+            .split_once('@')
+            .ok_or_else(|| format!("invalid device {}, expected name@address", text))?;
+        if !NAMES.contains(&name) {
+            return Err(format!(
+                "unknown device {}, expected one of: {}",
+                name,
+                NAMES.join(", ")
+            ));
+        }
+        let digits = addr.trim_start_matches("0x").trim_start_matches("0X");
+        let addr = usize::from_str_radix(digits, 16)
+            .ok()
+            .filter(|&addr| addr + size(name) <= MEMORY_SIZE)
+            .ok_or_else(|| format!("invalid device address {}", addr))?;
+        Ok(Spec {
+            name: name.to_string(),
+            addr,
+        })
+    }
+
+    /// A new device of the kind named.
+    pub fn create(&self) -> Shared {
+        match self.name.as_str() {
+            "console" => Arc::new(Mutex::new(Console::default())),
+            _ => Arc::new(Mutex::new(Clock::default())),
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! Static disassembly. Code is told apart from data by tracing every path
+//! from 0x200, following jumps, calls and skips; whatever no path reaches is
+//! listed as data.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::assertion;
+use crate::instruction::Instruction;
+use crate::lint::{Finding, Issue};
+use crate::memory::PROGRAM_START;
+use crate::platform::Platform;
+
+pub struct Trace {
+    /// Addresses where reachable instructions start.
+    pub code: BTreeSet<usize>,
+    /// Jump and call targets inside the ROM.
+    pub labels: BTreeSet<usize>,
+    /// Problems found along the way, in address order.
+    pub findings: Vec<Finding>,
+}
+
+/// Follows every path through `rom`, loaded at 0x200.
+pub fn trace(rom: &[u8]) -> Trace {
+    let end = PROGRAM_START + rom.len();
+    let mut trace = Trace {
+        code: BTreeSet::new(),
+        labels: BTreeSet::new(),
+        findings: Vec::new(),
+    };
+    let mut pending = vec![PROGRAM_START];
+
+    while let Some(addr) = pending.pop() {
+        if trace.code.contains(&addr) {
+            continue;
+        }
+        if addr + 2 > end {
+            trace.findings.push(Finding {
+                addr,
+                issue: Issue::RunsOffEnd,
+            });
+            continue;
+        }
+        trace.code.insert(addr);
+
+        let offset = addr - PROGRAM_START;
+        let opcode = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+        if let Some(platform) = Platform::introducing(opcode) {
+            trace.findings.push(Finding {
+                addr,
+                issue: Issue::LaterPlatform { opcode, platform },
+            });
+            match opcode {
+                // exit
+                0x00FD => {}
+                // the address follows in the next word
+                0xF000 => pending.push(addr + 4),
+                _ => pending.push(addr + 2),
+            }
+            continue;
+        }
+
+        let instruction = match Instruction::decode(opcode) {
+            Ok(instruction) => instruction,
+            Err(_) => {
+                trace.findings.push(Finding {
+                    addr,
+                    issue: Issue::UnknownOpcode { opcode },
+                });
+                continue;
+            }
+        };
+
+        let mut target = |target: u16| {
+            let target = target as usize;
+            if !(PROGRAM_START..end).contains(&target) {
+                trace.findings.push(Finding {
+                    addr,
+                    issue: Issue::TargetOutsideRom { target },
+                });
+                return None;
+            }
+            if target % 2 == 1 {
+                trace.findings.push(Finding {
+                    addr,
+                    issue: Issue::UnalignedTarget { target },
+                });
+            }
+            trace.labels.insert(target);
+            Some(target)
+        };
+
+        match instruction {
+            // 0000 halts this emulator, as do the assertion opcodes when enabled,
+            // and 00FD at least leaves the ROM
+            Instruction::Sys {
+                addr: 0 | assertion::PASS | assertion::FAIL,
+            }
+            | Instruction::Ret
+            | Instruction::Exit => {}
+            Instruction::Sys { addr: routine } => {
+                trace.findings.push(Finding {
+                    addr,
+                    issue: Issue::MachineCode { routine },
+                });
+                pending.push(addr + 2);
+            }
+            Instruction::Jump { addr: to } => pending.extend(target(to)),
+            Instruction::Call { addr: to } => {
+                pending.extend(target(to));
+                pending.push(addr + 2);
+            }
+            Instruction::JumpV0 { .. } => trace.findings.push(Finding {
+                addr,
+                issue: Issue::ComputedJump,
+            }),
+            Instruction::SeXkk { .. }
+            | Instruction::SneXkk { .. }
+            | Instruction::SeXy { .. }
+            | Instruction::SneXy { .. }
+            | Instruction::SkipKey { .. }
+            | Instruction::SkipNotKey { .. } => {
+                pending.push(addr + 2);
+                pending.push(addr + 4);
+            }
+            _ => pending.push(addr + 2),
+        }
+    }
+
+    trace.findings.sort_by_key(|finding| finding.addr);
+    trace.findings.dedup();
+    trace
+}
+
+/// A listing of the whole ROM: traced code as mnemonics, the rest as data bytes.
+pub fn disassemble(rom: &[u8]) -> String {
+    let trace = trace(rom);
+    let mut listing = String::new();
+    let mut offset = 0;
+
+    while offset < rom.len() {
+        let addr = PROGRAM_START + offset;
+        if trace.labels.contains(&addr) {
+            let _ = writeln!(listing, "L{:03X}:", addr);
+        }
+
+        if trace.code.contains(&addr) {
+            let opcode = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+            let text = match Instruction::decode(opcode) {
+                Ok(instruction) => instruction.to_string(),
+                Err(_) => "??".to_string(),
+            };
+            let _ = writeln!(listing, "{:03x}: {:04x}  {}", addr, opcode, text);
+            // code can overlap itself when a path jumps into the middle of an instruction
+            offset += if trace.code.contains(&(addr + 1)) {
+                1
+            } else {
+                2
+            };
+            continue;
+        }
+
+        // up to 8 data bytes per line, stopping where code or a label starts
+        let mut len = 1;
+        while len < 8
+            && offset + len < rom.len()
+            && !trace.code.contains(&(addr + len))
+            && !trace.labels.contains(&(addr + len))
+        {
+            len += 1;
+        }
+        let bytes: Vec<String> = rom[offset..offset + len]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        let _ = writeln!(listing, "{:03x}: {:<5} DB {}", addr, "", bytes.join(" "));
+        offset += len;
+    }
+
+    listing
+}
@@ -0,0 +1,117 @@
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 32;
+
+/// The 64x32 monochrome framebuffer.
+///
+/// Rows touched since the last `clear_dirty` are tracked, so frontends can
+/// redraw only what changed.
+#[derive(Clone)]
+pub struct Display {
+    pixels: [bool; WIDTH * HEIGHT],
+    dirty: u32, // one bit per row
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display {
+    pub fn new() -> Display {
+        Display {
+            pixels: [false; WIDTH * HEIGHT],
+            dirty: u32::MAX,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels = [false; WIDTH * HEIGHT];
+        self.dirty = u32::MAX;
+    }
+
+    pub fn is_dirty(&self, y: usize) -> bool {
+        self.dirty & (1 << y) != 0
+    }
+
+    pub fn any_dirty(&self) -> bool {
+        self.dirty != 0
+    }
+
+    /// Called by the frontend once it has drawn the current contents.
+    pub fn clear_dirty(&mut self) {
+        self.dirty = 0;
+    }
+
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty = u32::MAX;
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.pixels[y * WIDTH + x]
+    }
+
+    pub fn pixels(&self) -> &[bool] {
+        &self.pixels
+    }
+
+    /// Replaces the whole screen, e.g. with a saved one.
+    pub fn set_pixels(&mut self, pixels: &[bool]) {
+        self.pixels.copy_from_slice(pixels);
+        self.dirty = u32::MAX;
+    }
+
+    /// XORs an 8 pixel wide sprite onto the screen. The start position always
+    /// wraps around; the part past the edges wraps too if `wrap` is set and is
+    /// clipped otherwise.
+    /// Returns true if any lit pixel was turned off.
+    pub fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8], wrap: bool) -> bool {
+        let x = x as usize % WIDTH;
+        let y = y as usize % HEIGHT;
+        let mut collision = false;
+
+        for (row, byte) in sprite.iter().enumerate() {
+            let mut py = y + row;
+            if py >= HEIGHT {
+                if !wrap {
+                    break;
+                }
+                py %= HEIGHT;
+            }
+            if *byte != 0 {
+                self.dirty |= 1 << py;
+            }
+
+            for bit in 0..8 {
+                let mut px = x + bit;
+                if px >= WIDTH {
+                    if !wrap {
+                        break;
+                    }
+                    px %= WIDTH;
+                }
+
+                if byte & (0x80 >> bit) != 0 {
+                    let pixel = &mut self.pixels[py * WIDTH + px];
+                    collision |= *pixel;
+                    *pixel ^= true;
+                }
+            }
+        }
+
+        collision
+    }
+
+    /// FNV-1a hash of the screen contents, used to compare headless runs.
+    pub fn hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for row in self.pixels.chunks(8) {
+            let byte = row
+                .iter()
+                .fold(0u8, |byte, &pixel| (byte << 1) | pixel as u8);
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        hash
+    }
+}
@@ -0,0 +1,68 @@
+//! Everything a frontend needs and nothing else: make an emulator, load a
+//! ROM, then at 60Hz set the keys, run a frame, draw the framebuffer and
+//! beep while `sound_active`. `examples/frontend.rs` is a whole terminal
+//! frontend on these six calls.
+//!
+//! ```ignore
+//! let mut emulator = Emulator::new();
+//! emulator.load(&rom)?;
+//! loop {
+//!     emulator.set_keys(read_keys());
+//!     emulator.frame()?;
+//!     draw(emulator.framebuffer()); // WIDTH x HEIGHT, a row at a time
+//!     beep(emulator.sound_active());
+//! }
+//! ```
+//!
+//! Anything more, quirks, speed, save states or the debugger, is on `Cpu`.
+
+use crate::cpu::Cpu;
+pub use crate::display::{HEIGHT, WIDTH};
+use crate::error::Error;
+
+pub struct Emulator {
+    cpu: Cpu,
+}
+
+impl Default for Emulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Emulator {
+    pub fn new() -> Emulator {
+        Emulator { cpu: Cpu::new() }
+    }
+
+    /// Starts `rom` on a machine switched off and on, whatever ran before.
+    pub fn load(&mut self, rom: &[u8]) -> Result<(), Error> {
+        self.cpu.load_rom(rom)?;
+        self.cpu.power_cycle();
+        // nothing the old ROM left in the cache applies
+        self.cpu.set_engine(self.cpu.engine);
+        Ok(())
+    }
+
+    /// Runs a 60th of a second. After an error the machine stays as it was.
+    /// The program counter is at the instruction if it couldn't be fetched
+    /// (`ProgramCounterOutOfBounds`, `UnalignedProgramCounter`) and just past
+    /// it if running it failed.
+    pub fn frame(&mut self) -> Result<(), Error> {
+        self.cpu.run_frame()
+    }
+
+    /// The screen, `WIDTH` pixels a row for `HEIGHT` rows, true for lit.
+    pub fn framebuffer(&self) -> &[bool] {
+        self.cpu.display.pixels()
+    }
+
+    /// Which of the keys 0-F are held, for the frames from now on.
+    pub fn set_keys(&mut self, keys: [bool; 16]) {
+        self.cpu.keys = keys;
+    }
+
+    pub fn sound_active(&self) -> bool {
+        self.cpu.sound_active()
+    }
+}
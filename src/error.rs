@@ -0,0 +1,81 @@
+use core::fmt;
+
+use crate::instruction::DecodeError;
+use crate::platform::Platform;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The opcode at the program counter is not a CHIP-8 instruction.
+    UnknownOpcode {
+        opcode: u16,
+    },
+    /// An instruction of a later platform, which is not emulated.
+    UnsupportedInstruction {
+        opcode: u16,
+        platform: Platform,
+    },
+    /// An access outside of the 4K address space.
+    AddressOutOfBounds {
+        addr: usize,
+    },
+    /// A write into the interpreter/font area while it is write protected.
+    ProtectedWrite {
+        addr: usize,
+    },
+    /// The program counter ran off the end of memory.
+    ProgramCounterOutOfBounds {
+        pc: usize,
+    },
+    /// An instruction fetch from an odd address with `OddPc::Trap`.
+    UnalignedProgramCounter {
+        pc: usize,
+    },
+    StackOverflow,
+    StackUnderflow,
+    /// The ROM does not fit between 0x200 and the end of memory.
+    RomTooLarge {
+        size: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnknownOpcode { opcode } => write!(f, "unknown opcode {:04x}", opcode),
+            Error::UnsupportedInstruction { opcode, platform } => {
+                write!(
+                    f,
+                    "{:04x} is a {} instruction, which is not emulated",
+                    opcode, platform
+                )
+            }
+            Error::AddressOutOfBounds { addr } => {
+                write!(f, "address {:#05x} is out of bounds", addr)
+            }
+            Error::ProtectedWrite { addr } => {
+                write!(f, "write to protected address {:#05x}", addr)
+            }
+            Error::ProgramCounterOutOfBounds { pc } => {
+                write!(f, "program counter {:#05x} ran past the end of memory", pc)
+            }
+            Error::UnalignedProgramCounter { pc } => {
+                write!(f, "program counter {:#05x} is not aligned", pc)
+            }
+            Error::StackOverflow => write!(f, "stack overflow"),
+            Error::StackUnderflow => write!(f, "stack underflow"),
+            Error::RomTooLarge { size } => write!(f, "ROM is too large ({} bytes)", size),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl From<DecodeError> for Error {
+    fn from(err: DecodeError) -> Self {
+        let opcode = err.opcode;
+        match Platform::introducing(opcode) {
+            Some(platform) => Error::UnsupportedInstruction { opcode, platform },
+            None => Error::UnknownOpcode { opcode },
+        }
+    }
+}
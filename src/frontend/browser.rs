@@ -0,0 +1,103 @@
+//! The ROM browser shown when chip8 starts without a ROM: a list of the ROMs
+//! in a directory to pick one from with the arrow keys, with how often and how
+//! long each has been played.
+
+use std::path::{Path, PathBuf};
+
+use crate::stats::{self, RomStats, Stats};
+
+/// ROMs listed at once, the list scrolls to keep the selection in view.
+const ROWS: usize = 12;
+
+pub struct Browser {
+    dir: PathBuf,
+    roms: Vec<PathBuf>,
+    selected: usize,
+    /// Shown under the list, e.g. why the last game stopped.
+    pub message: Option<String>,
+    /// What is listed next to each ROM, by file name.
+    pub stats: Stats,
+}
+
+impl Browser {
+    /// `roms` should not be empty.
+    pub fn new(dir: PathBuf, roms: Vec<PathBuf>) -> Browser {
+        Browser {
+            dir,
+            roms,
+            selected: 0,
+            message: None,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Moves the selection, wrapping around at either end.
+    pub fn select_by(&mut self, entries: isize) {
+        let len = self.roms.len() as isize;
+        self.selected = (self.selected as isize + entries).rem_euclid(len.max(1)) as usize;
+    }
+
+    pub fn selected(&self) -> &Path {
+        &self.roms[self.selected]
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(" ROMs in {}", self.dir.display()), String::new()];
+
+        let first = self
+            .selected
+            .saturating_sub(ROWS / 2)
+            .min(self.roms.len().saturating_sub(ROWS));
+        let shown = self.roms.iter().enumerate().skip(first).take(ROWS);
+        let names: Vec<String> = shown
+            .clone()
+            .map(|(_, rom)| {
+                rom.strip_prefix(&self.dir)
+                    .unwrap_or(rom)
+                    .display()
+                    .to_string()
+            })
+            .collect();
+        let width = names
+            .iter()
+            .map(|name| name.chars().count())
+            .max()
+            .unwrap_or(0);
+        for ((i, rom), name) in shown.zip(names) {
+            let marker = if i == self.selected { '>' } else { ' ' };
+            let played = rom
+                .file_name()
+                .and_then(|file| self.stats.get(&file.to_string_lossy()))
+                .map(describe);
+            match played {
+                Some(played) => lines.push(format!(" {} {:width$}  {}", marker, name, played)),
+                None => lines.push(format!(" {} {}", marker, name)),
+            }
+        }
+
+        lines.push(String::new());
+        lines.push(format!(
+            " {}/{}  Up/Down choose, Enter plays, Esc quits",
+            self.selected + 1,
+            self.roms.len()
+        ));
+        if let Some(message) = &self.message {
+            lines.push(format!(" {}", message));
+        }
+        lines
+    }
+}
+
+/// E.g. `3 plays, 1h 02m, last 2026-10-14`.
+fn describe(rom: &RomStats) -> String {
+    let mut text = format!(
+        "{} play{}, {}",
+        rom.launches,
+        if rom.launches == 1 { "" } else { "s" },
+        stats::format_playtime(rom.playtime)
+    );
+    if let Some(time) = rom.last_played {
+        text.push_str(&format!(", last {}", stats::format_date(time)));
+    }
+    text
+}
@@ -0,0 +1,117 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::display::Display;
+
+pub mod browser;
+pub mod panel;
+pub mod style;
+#[cfg(feature = "terminal")]
+pub mod terminal;
+
+pub use browser::Browser;
+pub use panel::Panel;
+pub use style::PixelStyle;
+
+/// Host input that is not (yet) mapped to the CHIP-8 keypad.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Quit,
+    /// Enter/return, used to dismiss overlays.
+    Confirm,
+    /// Save or restore the save state (F5/F9).
+    SaveState,
+    LoadState,
+    /// Pause or resume the game (F6).
+    Pause,
+    /// Run several frames for every one shown, or stop doing that (F2).
+    FastForward,
+    /// Move the game onto the other engine (F3).
+    SwitchEngine,
+    /// Start the ROM over (F7), or also clear memory like switching the
+    /// machine off and on (F8).
+    Reset,
+    PowerCycle,
+    /// Save a picture of the screen (F12).
+    Screenshot,
+    /// Step back in time (Backspace).
+    Rewind,
+    /// Show or hide the debug panel (Tab).
+    TogglePanel,
+    /// Scroll the panel's memory view by that many rows (Page Up/Down).
+    ScrollPanel(isize),
+    /// Move a menu's selection by that many entries (Up/Down).
+    Select(isize),
+    /// A file was dropped onto the window.
+    Drop(PathBuf),
+    /// A pixel of the game was clicked, while the debug panel is up.
+    Pick {
+        x: usize,
+        y: usize,
+    },
+    Char(char),
+}
+
+/// A 24 bit colour, written as `#rrggbb`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb(0x00, 0x00, 0x00);
+    pub const WHITE: Rgb = Rgb(0xff, 0xff, 0xff);
+
+    pub fn parse(text: &str) -> Option<Rgb> {
+        let hex = text.strip_prefix('#').unwrap_or(text);
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        Some(Rgb(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+/// A backend that shows the display and collects input.
+pub trait Frontend {
+    fn name(&self) -> &'static str;
+    /// Acquires whatever the backend needs (terminal mode, window, ...).
+    fn init(&mut self) -> io::Result<()>;
+    /// Releases everything acquired by `init`. Safe to call more than once.
+    fn teardown(&mut self) -> io::Result<()>;
+    /// Draws the display. Rows that are not dirty may be skipped; the caller
+    /// clears the dirty flags afterwards.
+    fn present(&mut self, display: &Display) -> io::Result<()>;
+    /// Shows a message on top of the last frame, e.g. while paused.
+    fn overlay(&mut self, message: &str) -> io::Result<()>;
+    /// Draws the debug panel next to the display, or removes it when `lines`
+    /// is empty.
+    fn panel(&mut self, lines: &[String]) -> io::Result<()>;
+    /// Shows a page of text instead of the display, e.g. the ROM browser.
+    fn screen(&mut self, lines: &[String]) -> io::Result<()>;
+    fn poll_events(&mut self) -> Vec<Event>;
+    /// Called every frame with whether the sound timer is running.
+    fn set_sound(&mut self, on: bool);
+    /// Names the window after the game, where there is a window to name.
+    fn set_title(&mut self, _title: &str) {}
+}
+
+/// Names accepted by `by_name`, the ones built in.
+#[cfg(feature = "terminal")]
+pub const FRONTENDS: &[&str] = &["terminal"];
+#[cfg(not(feature = "terminal"))]
+pub const FRONTENDS: &[&str] = &[];
+
+#[cfg_attr(not(feature = "terminal"), allow(unused_variables))]
+pub fn by_name(name: &str, style: PixelStyle) -> Option<Box<dyn Frontend>> {
+    match name {
+        #[cfg(feature = "terminal")]
+        "terminal" => Some(Box::new(terminal::Terminal::new(style))),
+        _ => None,
+    }
+}
@@ -0,0 +1,177 @@
+//! The debug panel: registers, timers, the stack and memory around PC and I,
+//! as plain text lines for a frontend to draw next to the display. After a
+//! collision break it also shows what the Dxyn changed, and once an XO-CHIP
+//! ROM has loaded an audio pattern, the pattern and the tone it makes.
+
+use crate::cpu::{Collision, Cpu};
+use crate::display::{Display, WIDTH};
+use crate::memory::MEMORY_SIZE;
+
+/// Bytes per hex dump row.
+const ROW: usize = 8;
+/// Rows shown around PC and around I.
+const ROWS: usize = 3;
+/// Stack entries shown, the most recent first.
+const STACK_SHOWN: usize = 6;
+/// Pattern bits per waveform row.
+const WAVE_ROW: usize = 32;
+const NOTES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// What the panel shows. `scroll` moves both hex views by that many rows.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Panel {
+    pub visible: bool,
+    pub scroll: isize,
+}
+
+impl Panel {
+    /// Scrolls the hex views, at most the size of memory either way.
+    pub fn scroll_by(&mut self, rows: isize) {
+        let limit = (MEMORY_SIZE / ROW) as isize;
+        self.scroll = (self.scroll + rows).clamp(-limit, limit);
+    }
+
+    pub fn lines(&self, cpu: &Cpu) -> Vec<String> {
+        let mut lines = vec![
+            format!(
+                "PC {:03x}  I {:03x}  SP {:x}",
+                cpu.program_counter, cpu.index, cpu.stack_pointer
+            ),
+            format!("DT {:02x}  ST {:02x}", cpu.delay_timer, cpu.sound_timer),
+        ];
+        for (row, values) in cpu.registers.chunks(4).enumerate() {
+            let text: Vec<String> = values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| format!("V{:X} {:02x}", row * 4 + i, value))
+                .collect();
+            lines.push(text.join(" "));
+        }
+
+        let stack: Vec<String> = cpu.stack[..cpu.stack_pointer.min(cpu.stack.len())]
+            .iter()
+            .rev()
+            .take(STACK_SHOWN)
+            .map(|addr| format!("{:03x}", addr))
+            .collect();
+        lines.push(format!("stack {}", stack.join(" ")));
+
+        lines.push("@PC".to_string());
+        self.dump(cpu, cpu.program_counter, &mut lines);
+        lines.push("@I".to_string());
+        self.dump(cpu, cpu.index as usize, &mut lines);
+        if let Some(pattern) = &cpu.audio_pattern {
+            lines.extend(audio_lines(pattern, cpu.pitch, cpu.sound_timer));
+        }
+        if let Some(collision) = &cpu.collision {
+            lines.extend(collision_lines(collision, &cpu.display));
+        }
+        lines
+    }
+
+    /// Hex rows around `addr`, marking the row that holds it.
+    fn dump(&self, cpu: &Cpu, addr: usize, lines: &mut Vec<String>) {
+        let memory = cpu.memory.as_slice();
+        let last_row = MEMORY_SIZE / ROW - ROWS;
+        let first = (addr / ROW) as isize - 1 + self.scroll;
+        let first = first.clamp(0, last_row as isize) as usize;
+
+        for row in first..first + ROWS {
+            let start = row * ROW;
+            let bytes: Vec<String> = memory[start..start + ROW]
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            let marker = if (start..start + ROW).contains(&addr) {
+                '>'
+            } else {
+                ' '
+            };
+            lines.push(format!("{}{:03x} {}", marker, start, bytes.join(" ")));
+        }
+    }
+}
+
+/// The rows and columns the Dxyn changed, before and after side by side. In
+/// the after view `+` was turned on and `x` turned off, the collision.
+fn collision_lines(collision: &Collision, after: &Display) -> Vec<String> {
+    let before = &collision.before;
+    let changed: Vec<(usize, usize)> = (0..before.pixels().len())
+        .filter(|&i| before.pixels()[i] != after.pixels()[i])
+        .map(|i| (i % WIDTH, i / WIDTH))
+        .collect();
+    let Some(left) = changed.iter().map(|&(x, _)| x).min() else {
+        return Vec::new();
+    };
+    let right = changed.iter().map(|&(x, _)| x).max().unwrap_or(left);
+    let top = changed.iter().map(|&(_, y)| y).min().unwrap_or(0);
+    let bottom = changed.iter().map(|&(_, y)| y).max().unwrap_or(top);
+
+    let mut lines = vec![format!(
+        "collision @{:03x} x {}-{} y {}-{}",
+        collision.pc, left, right, top, bottom
+    )];
+    for y in top..=bottom {
+        let was: String = (left..=right)
+            .map(|x| if before.pixel(x, y) { '#' } else { '.' })
+            .collect();
+        let is: String = (left..=right)
+            .map(|x| match (before.pixel(x, y), after.pixel(x, y)) {
+                (false, true) => '+',
+                (true, false) => 'x',
+                (true, true) => '#',
+                (false, false) => '.',
+            })
+            .collect();
+        lines.push(format!(" {} {}", was, is));
+    }
+    lines
+}
+
+/// The pattern as hex and as a waveform, its bit rate and the note its
+/// repeats come out as. The pattern plays at 4000 bits a second at pitch 64,
+/// an octave up every 48 steps.
+fn audio_lines(pattern: &[u8; 16], pitch: u8, sound_timer: u8) -> Vec<String> {
+    let rate = 4000.0 * 2f64.powf((pitch as f64 - 64.0) / 48.0);
+    let bits: Vec<bool> = pattern
+        .iter()
+        .flat_map(|&byte| (0..8).map(move |bit| byte & (0x80 >> bit) != 0))
+        .collect();
+    // the shortest rotation that gives the same pattern is one wave
+    let period = [1, 2, 4, 8, 16, 32, 64, 128]
+        .into_iter()
+        .find(|&p| (0..bits.len()).all(|i| bits[i] == bits[(i + p) % bits.len()]))
+        .unwrap_or(bits.len());
+
+    let tone = if period == 1 {
+        "tone none, flat".to_string()
+    } else {
+        let frequency = rate / period as f64;
+        let midi = 69.0 + 12.0 * (frequency / 440.0).log2();
+        let note = midi.round();
+        format!(
+            "tone {:.1}Hz {}{} {:+.0}c",
+            frequency,
+            NOTES[note.rem_euclid(12.0) as usize],
+            (note / 12.0).floor() - 1.0,
+            (midi - note) * 100.0
+        )
+    };
+    let state = if sound_timer > 0 { "on" } else { "off" };
+    let mut lines = vec![
+        format!("audio {}  ST {:02x}", state, sound_timer),
+        format!("pitch {:02x}  {:.0} bit/s", pitch, rate),
+        tone,
+    ];
+    for half in pattern.chunks(8) {
+        let bytes: Vec<String> = half.iter().map(|byte| format!("{:02x}", byte)).collect();
+        lines.push(format!(" {}", bytes.join(" ")));
+    }
+    for row in bits.chunks(WAVE_ROW) {
+        let wave: String = row.iter().map(|&on| if on { '‾' } else { '_' }).collect();
+        lines.push(format!(" {}", wave));
+    }
+    lines
+}
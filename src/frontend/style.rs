@@ -0,0 +1,103 @@
+use super::Rgb;
+use crate::display::{Display, HEIGHT, WIDTH};
+
+/// How frontends turn the monochrome framebuffer into coloured pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelStyle {
+    pub foreground: Rgb,
+    pub background: Rgb,
+    /// Integer scaling factor.
+    pub scale: u32,
+    /// Let pixels fade out over a few frames like the slow phosphor of the
+    /// original hardware, which hides most of the flicker of XOR drawing.
+    pub ghosting: bool,
+}
+
+impl Default for PixelStyle {
+    fn default() -> Self {
+        PixelStyle {
+            foreground: Rgb::WHITE,
+            background: Rgb::BLACK,
+            scale: 1,
+            ghosting: false,
+        }
+    }
+}
+
+impl PixelStyle {
+    /// Blends between background (level 0) and foreground (level 255).
+    pub fn color(&self, level: u8) -> Rgb {
+        let mix = |bg: u8, fg: u8| {
+            let level = level as u32;
+            ((bg as u32 * (255 - level) + fg as u32 * level) / 255) as u8
+        };
+        Rgb(
+            mix(self.background.0, self.foreground.0),
+            mix(self.background.1, self.foreground.1),
+            mix(self.background.2, self.foreground.2),
+        )
+    }
+}
+
+/// Per-pixel brightness after ghosting, shared by the frontends.
+pub struct Phosphor {
+    levels: [u8; WIDTH * HEIGHT],
+    /// Rows with pixels still fading out, they change even without drawing.
+    fading: u32,
+}
+
+impl Default for Phosphor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Phosphor {
+    pub fn new() -> Phosphor {
+        Phosphor {
+            levels: [0; WIDTH * HEIGHT],
+            fading: 0,
+        }
+    }
+
+    /// Brings the levels up to date with the display, once per frame.
+    /// Returns a mask of the rows whose levels changed.
+    pub fn update(&mut self, display: &Display, ghosting: bool) -> u32 {
+        let mut changed = 0;
+        let mut fading = 0;
+
+        for y in 0..HEIGHT {
+            let row = 1 << y;
+            if !display.is_dirty(y) && self.fading & row == 0 {
+                continue;
+            }
+
+            for x in 0..WIDTH {
+                let level = &mut self.levels[y * WIDTH + x];
+                let new = if display.pixel(x, y) {
+                    255
+                } else if ghosting {
+                    // roughly gone after 10 frames
+                    (*level as u16 * 5 / 8) as u8
+                } else {
+                    0
+                };
+
+                if new != *level {
+                    changed |= row;
+                }
+                if new != 0 && new != 255 {
+                    fading |= row;
+                }
+                *level = new;
+            }
+        }
+
+        self.fading = fading;
+        changed
+    }
+
+    pub fn level(&self, x: usize, y: usize) -> u8 {
+        self.levels[y * WIDTH + x]
+    }
+}
@@ -0,0 +1,461 @@
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+
+use super::style::{Phosphor, PixelStyle};
+use super::{Event, Frontend, Rgb};
+use crate::display::{Display, HEIGHT, WIDTH};
+
+/// Draws the screen with half block characters, two pixel rows per text line,
+/// in 24 bit colour. Input is read from stdin with the terminal in non-canonical mode.
+pub struct Terminal {
+    style: PixelStyle,
+    phosphor: Phosphor,
+    saved_mode: Option<String>,
+    input: Option<&'static Mutex<Receiver<Vec<u8>>>>,
+    /// A paste being read, which is how terminals report dropped files.
+    paste: Option<Vec<u8>>,
+    /// Redraw every line next frame, not only the dirty ones.
+    full_redraw: bool,
+    sound: bool,
+    /// Width of the debug panel on screen, 0 when hidden.
+    panel_width: usize,
+    /// Clicks are reported, which they are while the panel is up.
+    mouse: bool,
+    /// The window title was changed and has to be put back.
+    titled: bool,
+}
+
+impl Default for Terminal {
+    fn default() -> Self {
+        Self::new(PixelStyle::default())
+    }
+}
+
+impl Terminal {
+    pub fn new(style: PixelStyle) -> Terminal {
+        Terminal {
+            style,
+            phosphor: Phosphor::new(),
+            saved_mode: None,
+            input: None,
+            paste: None,
+            full_redraw: true,
+            sound: false,
+            panel_width: 0,
+            mouse: false,
+            titled: false,
+        }
+    }
+}
+
+impl Terminal {
+    /// The pixel drawn in a cell. Without `--scale` a cell has two pixels, one
+    /// above the other, and the one lit is likelier to be what was clicked.
+    fn pixel_at(&self, (column, line): (usize, usize)) -> Option<Event> {
+        let scale = self.style.scale.max(1) as usize;
+        let x = column / scale;
+        let top = line * 2 / scale;
+        let bottom = (line * 2 + 1) / scale;
+        if x >= WIDTH || top >= HEIGHT {
+            return None;
+        }
+        let lit = |y: usize| y < HEIGHT && self.phosphor.level(x, y) == 255;
+        let y = if !lit(top) && lit(bottom) {
+            bottom
+        } else {
+            top
+        };
+        Some(Event::Pick { x, y })
+    }
+}
+
+fn push_color(frame: &mut String, layer: u8, color: Rgb) {
+    frame.push_str(&format!(
+        "\x1b[{};2;{};{};{}m",
+        layer, color.0, color.1, color.2
+    ));
+}
+
+/// The thread reading stdin. Its read blocks and can't be stopped, so every
+/// terminal shares the one thread rather than starting its own.
+fn input() -> &'static Mutex<Receiver<Vec<u8>>> {
+    static INPUT: OnceLock<Mutex<Receiver<Vec<u8>>>> = OnceLock::new();
+    INPUT.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buf = [0u8; 256];
+            let mut stdin = io::stdin();
+            while let Ok(n) = stdin.read(&mut buf) {
+                if n == 0 || sender.send(buf[..n].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+        Mutex::new(receiver)
+    })
+}
+
+/// The file in a paste, as terminals paste the paths of dropped files: maybe
+/// quoted, maybe a file:// URL, maybe with backslashes before spaces.
+fn dropped_path(text: &str) -> Option<PathBuf> {
+    let text = text.trim();
+    let text = [('\'', '\''), ('"', '"')]
+        .iter()
+        .find_map(|&(open, close)| text.strip_prefix(open)?.strip_suffix(close))
+        .unwrap_or(text);
+    if text.is_empty() || text.contains('\n') {
+        return None;
+    }
+
+    if let Some(url) = text.strip_prefix("file://") {
+        // percent escapes are bytes of UTF-8
+        let mut bytes = Vec::new();
+        let mut rest = url.as_bytes();
+        while let Some((&byte, tail)) = rest.split_first() {
+            let escaped = tail
+                .get(..2)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match escaped {
+                Some(value) if byte == b'%' => {
+                    bytes.push(value);
+                    rest = &tail[2..];
+                }
+                _ => {
+                    bytes.push(byte);
+                    rest = tail;
+                }
+            }
+        }
+        return String::from_utf8(bytes).ok().map(PathBuf::from);
+    }
+
+    let mut path = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => path.extend(chars.next()),
+            c => path.push(c),
+        }
+    }
+    Some(PathBuf::from(path))
+}
+
+/// The cell of a left click in an SGR mouse report, `\x1b[<0;col;rowM`,
+/// counting from 0.
+fn clicked_cell(report: &[u8]) -> Option<(usize, usize)> {
+    let report = std::str::from_utf8(report.strip_prefix(b"\x1b[<")?).ok()?;
+    let mut fields = report.strip_suffix('M')?.split(';');
+    let button: u32 = fields.next()?.parse().ok()?;
+    let column: usize = fields.next()?.parse().ok()?;
+    let row: usize = fields.next()?.parse().ok()?;
+    if button != 0 {
+        return None;
+    }
+    Some((column.checked_sub(1)?, row.checked_sub(1)?))
+}
+
+/// Runs stty against our stdin, there is no termios in std.
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!("stty {} failed", args.join(" "))));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+impl Frontend for Terminal {
+    fn name(&self) -> &'static str {
+        "terminal"
+    }
+
+    fn init(&mut self) -> io::Result<()> {
+        if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+            return Err(io::Error::other("stdin and stdout must be a terminal"));
+        }
+        if self.saved_mode.is_some() {
+            return Ok(());
+        }
+
+        self.saved_mode = Some(stty(&["-g"])?);
+        stty(&["-icanon", "-echo", "-isig", "min", "1"])?;
+
+        self.input = Some(input());
+        self.full_redraw = true;
+
+        // alternate screen, hide cursor, clear, bracketed paste for dropped files
+        print!("\x1b[?1049h\x1b[?25l\x1b[2J\x1b[?2004h");
+        io::stdout().flush()
+    }
+
+    fn teardown(&mut self) -> io::Result<()> {
+        let Some(mode) = self.saved_mode.take() else {
+            return Ok(());
+        };
+
+        if self.titled {
+            // pop the title pushed by set_title
+            print!("\x1b[23;0t");
+            self.titled = false;
+        }
+        if self.mouse {
+            print!("\x1b[?1006l\x1b[?1000l");
+            self.mouse = false;
+        }
+        print!("\x1b[?2004l\x1b[?25h\x1b[?1049l");
+        io::stdout().flush()?;
+        stty(&[&mode]).map(|_| ())
+    }
+
+    fn present(&mut self, display: &Display) -> io::Result<()> {
+        let changed = self.phosphor.update(display, self.style.ghosting);
+        if !self.full_redraw && changed == 0 {
+            return Ok(());
+        }
+
+        // each pixel is scale columns wide and scale half lines high
+        let scale = self.style.scale.max(1) as usize;
+        let half_rows = HEIGHT * scale;
+
+        // only rewrite the lines that changed, redrawing everything flickers
+        let mut frame = String::with_capacity(WIDTH * HEIGHT * scale * scale * 20);
+        for line in 0..half_rows.div_ceil(2) {
+            let top = line * 2 / scale;
+            let bottom = (line * 2 + 1).min(half_rows - 1) / scale;
+            if !self.full_redraw && changed & (1 << top | 1 << bottom) == 0 {
+                continue;
+            }
+
+            frame.push_str(&format!("\x1b[{};1H", line + 1));
+            let mut last = None;
+            for column in 0..WIDTH * scale {
+                let x = column / scale;
+                let colors = (
+                    self.style.color(self.phosphor.level(x, top)),
+                    self.style.color(self.phosphor.level(x, bottom)),
+                );
+                // the upper half block takes the foreground colour, the rest the background
+                if last != Some(colors) {
+                    push_color(&mut frame, 38, colors.0);
+                    push_color(&mut frame, 48, colors.1);
+                    last = Some(colors);
+                }
+                frame.push('▀');
+            }
+            frame.push_str("\x1b[0m");
+        }
+        self.full_redraw = false;
+
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(frame.as_bytes())?;
+        stdout.flush()
+    }
+
+    fn overlay(&mut self, message: &str) -> io::Result<()> {
+        let scale = self.style.scale.max(1) as usize;
+        let row = HEIGHT * scale / 4 + 1;
+        let col = (WIDTH * scale).saturating_sub(message.chars().count() + 2) / 2 + 1;
+
+        // the overlay covers part of the game, so draw all of it again once it is gone
+        self.full_redraw = true;
+
+        // reverse video so it stands out from the game
+        let mut stdout = io::stdout().lock();
+        write!(stdout, "\x1b[{};{}H\x1b[7m {} \x1b[0m", row, col, message)?;
+        stdout.flush()
+    }
+
+    fn panel(&mut self, lines: &[String]) -> io::Result<()> {
+        let column = WIDTH * self.style.scale.max(1) as usize + 3;
+        let width = lines.iter().map(|line| line.chars().count()).max();
+        let mut stdout = io::stdout().lock();
+
+        // clicks pick pixels while debugging, and select text otherwise
+        if width.is_some() != self.mouse {
+            self.mouse = width.is_some();
+            let mode = if self.mouse { 'h' } else { 'l' };
+            write!(stdout, "\x1b[?1000{}\x1b[?1006{}", mode, mode)?;
+        }
+        match width {
+            Some(width) => {
+                for (row, line) in lines.iter().enumerate() {
+                    // padded, so shorter values don't leave digits behind
+                    write!(stdout, "\x1b[{};{}H{:<width$}", row + 1, column, line)?;
+                }
+                self.panel_width = width;
+            }
+            None if self.panel_width > 0 => {
+                // erase from the panel's column to the end of every line
+                for row in 0..HEIGHT {
+                    write!(stdout, "\x1b[{};{}H\x1b[K", row + 1, column)?;
+                }
+                self.panel_width = 0;
+            }
+            None => {}
+        }
+        stdout.flush()
+    }
+
+    fn screen(&mut self, lines: &[String]) -> io::Result<()> {
+        // the game's pixels are gone, so they all have to be drawn again
+        self.full_redraw = true;
+        self.panel_width = 0;
+
+        let mut stdout = io::stdout().lock();
+        write!(stdout, "\x1b[0m\x1b[2J")?;
+        for (row, line) in lines.iter().enumerate() {
+            write!(stdout, "\x1b[{};1H{}", row + 1, line)?;
+        }
+        stdout.flush()
+    }
+
+    fn poll_events(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        let Some(input) = self.input else {
+            return events;
+        };
+        let input = input
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        const PASTE_START: &[u8] = b"\x1b[200~";
+        const PASTE_END: &[u8] = b"\x1b[201~";
+
+        while let Ok(mut bytes) = input.try_recv() {
+            // pastes can come in several reads
+            if self.paste.is_none() {
+                if let Some(rest) = bytes.strip_prefix(PASTE_START) {
+                    self.paste = Some(Vec::new());
+                    bytes = rest.to_vec();
+                }
+            }
+            if let Some(paste) = &mut self.paste {
+                paste.extend_from_slice(&bytes);
+                if let Some(end) = paste
+                    .windows(PASTE_END.len())
+                    .position(|window| window == PASTE_END)
+                {
+                    let text = String::from_utf8_lossy(&paste[..end]).into_owned();
+                    events.extend(dropped_path(&text).map(Event::Drop));
+                    self.paste = None;
+                }
+                continue;
+            }
+
+            // a lone escape is the Esc key, F2 fast-forwards, F3 switches
+            // engines, F5/F9 handle save states, F6-F8 pause and reset, F12 takes a screenshot, Page Up/Down scroll the
+            // debug panel, the arrows move through menus, clicks pick pixels and
+            // any other escape sequence is ignored
+            match &bytes[..] {
+                [0x1b] => {
+                    events.push(Event::Quit);
+                    continue;
+                }
+                b"\x1bOQ" | b"\x1b[12~" => {
+                    events.push(Event::FastForward);
+                    continue;
+                }
+                b"\x1bOR" | b"\x1b[13~" => {
+                    events.push(Event::SwitchEngine);
+                    continue;
+                }
+                b"\x1b[15~" => {
+                    events.push(Event::SaveState);
+                    continue;
+                }
+                b"\x1b[20~" => {
+                    events.push(Event::LoadState);
+                    continue;
+                }
+                b"\x1b[17~" => {
+                    events.push(Event::Pause);
+                    continue;
+                }
+                b"\x1b[18~" => {
+                    events.push(Event::Reset);
+                    continue;
+                }
+                b"\x1b[19~" => {
+                    events.push(Event::PowerCycle);
+                    continue;
+                }
+                b"\x1b[24~" => {
+                    events.push(Event::Screenshot);
+                    continue;
+                }
+                b"\x1b[5~" => {
+                    events.push(Event::ScrollPanel(-1));
+                    continue;
+                }
+                b"\x1b[6~" => {
+                    events.push(Event::ScrollPanel(1));
+                    continue;
+                }
+                b"\x1b[A" | b"\x1bOA" => {
+                    events.push(Event::Select(-1));
+                    continue;
+                }
+                b"\x1b[B" | b"\x1bOB" => {
+                    events.push(Event::Select(1));
+                    continue;
+                }
+                [0x1b, b'[', b'<', ..] => {
+                    events.extend(clicked_cell(&bytes).and_then(|cell| self.pixel_at(cell)));
+                    continue;
+                }
+                [0x1b, ..] => continue,
+                _ => {}
+            }
+
+            for byte in bytes {
+                match byte {
+                    0x03 => events.push(Event::Quit), // ctrl-c, since isig is off
+                    b'\r' | b'\n' => events.push(Event::Confirm),
+                    0x7f | 0x08 => events.push(Event::Rewind),
+                    b'\t' => events.push(Event::TogglePanel),
+                    byte if byte.is_ascii_graphic() || byte == b' ' => {
+                        events.push(Event::Char(byte as char))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        events
+    }
+
+    /// The best a terminal can do is ring the bell when a beep starts.
+    fn set_sound(&mut self, on: bool) {
+        if on && !self.sound {
+            print!("\x07");
+            let _ = io::stdout().flush();
+        }
+        self.sound = on;
+    }
+
+    fn set_title(&mut self, title: &str) {
+        // most terminal emulators take this as the window title
+        let title: String = title.chars().filter(|c| !c.is_control()).collect();
+        if !self.titled {
+            // push the current title, xterm style
+            print!("\x1b[22;0t");
+            self.titled = true;
+        }
+        print!("\x1b]0;{}\x07", title);
+        let _ = io::stdout().flush();
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.teardown();
+    }
+}
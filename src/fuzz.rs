@@ -0,0 +1,536 @@
+//! Differential fuzzing: a random instruction stream runs on both engines and
+//! on a reference interpreter, and after every step all three have to agree
+//! on the registers, stack, timers, memory and screen.
+//!
+//! The reference is written straight from the instruction table, sharing
+//! nothing with `cpu` but the constants, so a mistake has to be made twice to
+//! go unnoticed. `check` takes arbitrary bytes, which makes it a fuzz target:
+//! `chip8 fuzz` feeds it from the RNG, and `fuzz/` hooks it up to cargo-fuzz
+//! for coverage guided runs.
+
+use std::fmt;
+
+use crate::cpu::{Cpu, Engine, PcOverflow, DEFAULT_PITCH, INSTRUCTIONS_PER_FRAME};
+use crate::display::{HEIGHT, WIDTH};
+use crate::font::{BIG_FONT_ADDR, BIG_FONT_HEIGHT, FONT_ADDR, FONT_HEIGHT};
+use crate::memory::{MEMORY_SIZE, PROGRAM_START};
+use crate::quirks::Quirks;
+use crate::rng::Rng;
+
+/// Bytes at the start of the input that set up the machines rather than
+/// being instructions: quirks, flags, keys and the RNG seed.
+const HEADER: usize = 5;
+
+/// How the machines are set up, taken from the header.
+#[derive(Debug, Clone, Copy)]
+pub struct Setup {
+    pub quirks: Quirks,
+    pub pc_overflow: PcOverflow,
+    pub keys: [bool; 16],
+    pub seed: u64,
+}
+
+impl Setup {
+    /// Missing header bytes count as zero.
+    pub fn from_header(data: &[u8]) -> Setup {
+        let byte = |i: usize| data.get(i).copied().unwrap_or(0);
+        let quirk = |bit: u8| byte(0) & 1 << bit != 0;
+        let keys = u16::from_le_bytes([byte(2), byte(3)]);
+        Setup {
+            quirks: Quirks {
+                shift_uses_vy: quirk(0),
+                load_store_increments_i: quirk(1),
+                jump_uses_vx: quirk(2),
+                logic_resets_vf: quirk(3),
+                wrap_sprites: quirk(4),
+            },
+            pc_overflow: if byte(1) & 1 != 0 {
+                PcOverflow::Wrap
+            } else {
+                PcOverflow::Error
+            },
+            keys: std::array::from_fn(|key| keys & 1 << key != 0),
+            seed: byte(4) as u64,
+        }
+    }
+}
+
+/// Turns the rest of the input into a program of valid instructions: every
+/// opcode that doesn't decode is bent into one that does, and jumps are kept
+/// inside the program so it doesn't just run off into empty memory.
+pub fn program(data: &[u8]) -> Vec<u8> {
+    let words = data.get(HEADER..).unwrap_or_default();
+    let words = &words[..words.len().min(MEMORY_SIZE - PROGRAM_START) & !1];
+    let len = words.len().max(2) as u16;
+    words
+        .chunks(2)
+        .flat_map(|pair| valid(u16::from_be_bytes([pair[0], pair[1]]), len).to_be_bytes())
+        .collect()
+}
+
+fn valid(opcode: u16, len: u16) -> u16 {
+    let (x, y, n, kk) = (
+        opcode & 0x0F00,
+        opcode & 0x00F0,
+        opcode & 0x000F,
+        opcode & 0x00FF,
+    );
+    let within = |addr: u16| PROGRAM_START as u16 + ((addr % len) & !1);
+    match opcode >> 12 {
+        // machine code is not much to fuzz; returning and stopping are kept
+        // rare, they end most runs early otherwise
+        0x0 => match kk {
+            0xFD => 0x00FD,
+            _ if kk % 8 == 0 => 0x00EE,
+            _ => 0x00E0,
+        },
+        0x1 | 0x2 => (opcode & 0xF000) | within(opcode & 0x0FFF),
+        0xB => 0xB000 | within(opcode & 0x0FFF),
+        0x5 | 0x9 => opcode & 0xFFF0,
+        0x8 => {
+            let n = [0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0xE][n as usize % 9];
+            0x8000 | x | y | n
+        }
+        0xE => 0xE000 | x | if kk & 1 == 0 { 0x9E } else { 0xA1 },
+        0xF => {
+            let kinds = [
+                0x02, 0x07, 0x0A, 0x15, 0x18, 0x1E, 0x29, 0x30, 0x33, 0x3A, 0x55, 0x65,
+            ];
+            match kinds[kk as usize % kinds.len()] {
+                // F002 only comes with x = 0
+                0x02 => 0xF002,
+                kind => 0xF000 | x | kind,
+            }
+        }
+        _ => opcode,
+    }
+}
+
+/// Where and how the machines first disagreed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Steps run before the disagreement showed, counting the one that
+    /// caused it.
+    pub steps: usize,
+    /// The engine that disagreed with the reference.
+    pub engine: Engine,
+    pub what: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "after {} steps the {:?} engine and the reference disagree: {}",
+            self.steps, self.engine, self.what
+        )
+    }
+}
+
+/// Runs the program in `data` for up to `steps` instructions on the simple
+/// engine, the cached engine and the reference; the timers tick every
+/// `INSTRUCTIONS_PER_FRAME` steps. Stops early once all of them halt or fail,
+/// and returns the steps run.
+pub fn check(data: &[u8], steps: usize) -> Result<usize, Mismatch> {
+    let setup = Setup::from_header(data);
+    let program = program(data);
+    let mut engines =
+        [Engine::Simple, Engine::Cached].map(|engine| machine(&setup, &program, engine));
+    let mut reference = Reference::new(&engines[0], &setup);
+
+    for step in 1..=steps {
+        let expected = reference.step();
+        for cpu in &mut engines {
+            let result = cpu.step().map_err(|_| ());
+            let mismatch = |what| Mismatch {
+                steps: step,
+                engine: cpu.engine,
+                what,
+            };
+            if result != expected {
+                return Err(mismatch(format!(
+                    "the reference {} but the engine {}",
+                    outcome(&expected),
+                    outcome(&result)
+                )));
+            }
+            if let Some(what) = reference.snapshot().diff(&Snapshot::of(cpu)) {
+                return Err(mismatch(what));
+            }
+        }
+        if expected.is_err() || reference.halted {
+            return Ok(step);
+        }
+        if step % INSTRUCTIONS_PER_FRAME == 0 {
+            reference.tick_timers();
+            for cpu in &mut engines {
+                cpu.tick_timers();
+            }
+        }
+    }
+    Ok(steps)
+}
+
+fn outcome(result: &Result<(), ()>) -> &'static str {
+    match result {
+        Ok(()) => "ran the instruction",
+        Err(()) => "failed",
+    }
+}
+
+fn machine(setup: &Setup, program: &[u8], engine: Engine) -> Cpu {
+    let mut cpu = Cpu::new();
+    cpu.quirks = setup.quirks;
+    cpu.pc_overflow = setup.pc_overflow;
+    cpu.keys = setup.keys;
+    cpu.rng = Rng::new(setup.seed);
+    cpu.engine = engine;
+    cpu.load_rom(program)
+        .expect("programs are cut to fit in memory");
+    cpu
+}
+
+/// Everything the machines are compared on.
+#[derive(PartialEq)]
+struct Snapshot<'a> {
+    registers: [u8; 16],
+    index: u16,
+    program_counter: usize,
+    stack: &'a [u16],
+    delay_timer: u8,
+    sound_timer: u8,
+    audio_pattern: Option<[u8; 16]>,
+    pitch: u8,
+    halted: bool,
+    memory: &'a [u8],
+    pixels: &'a [bool],
+}
+
+impl Snapshot<'_> {
+    fn of(cpu: &Cpu) -> Snapshot<'_> {
+        Snapshot {
+            registers: cpu.registers,
+            index: cpu.index,
+            program_counter: cpu.program_counter,
+            stack: &cpu.stack[..cpu.stack_pointer],
+            delay_timer: cpu.delay_timer,
+            sound_timer: cpu.sound_timer,
+            audio_pattern: cpu.audio_pattern,
+            pitch: cpu.pitch,
+            halted: cpu.halted,
+            memory: cpu.memory.as_slice(),
+            pixels: cpu.display.pixels(),
+        }
+    }
+
+    /// The first difference, expected value first.
+    fn diff(&self, other: &Snapshot) -> Option<String> {
+        if self == other {
+            return None;
+        }
+        if let Some(i) = (0..16).find(|&i| self.registers[i] != other.registers[i]) {
+            return Some(format!(
+                "v{:x} is {:#04x}, not {:#04x}",
+                i, other.registers[i], self.registers[i]
+            ));
+        }
+        let words = [
+            ("I", self.index as usize, other.index as usize),
+            ("pc", self.program_counter, other.program_counter),
+            (
+                "the delay timer",
+                self.delay_timer as usize,
+                other.delay_timer as usize,
+            ),
+            (
+                "the sound timer",
+                self.sound_timer as usize,
+                other.sound_timer as usize,
+            ),
+            ("the pitch", self.pitch as usize, other.pitch as usize),
+        ];
+        if let Some((name, expected, actual)) = words.iter().find(|(_, a, b)| a != b) {
+            return Some(format!(
+                "{} is {:#05x}, not {:#05x}",
+                name, actual, expected
+            ));
+        }
+        if self.stack != other.stack {
+            return Some(format!(
+                "the stack is {:x?}, not {:x?}",
+                other.stack, self.stack
+            ));
+        }
+        if self.audio_pattern != other.audio_pattern {
+            return Some(format!(
+                "the audio pattern is {:x?}, not {:x?}",
+                other.audio_pattern, self.audio_pattern
+            ));
+        }
+        if self.halted != other.halted {
+            return Some(format!("halted is {}, not {}", other.halted, self.halted));
+        }
+        if let Some(addr) = (0..MEMORY_SIZE).find(|&addr| self.memory[addr] != other.memory[addr]) {
+            return Some(format!(
+                "memory at {:#05x} is {:#04x}, not {:#04x}",
+                addr, other.memory[addr], self.memory[addr]
+            ));
+        }
+        if let Some(i) = (0..WIDTH * HEIGHT).find(|&i| self.pixels[i] != other.pixels[i]) {
+            return Some(format!(
+                "pixel ({}, {}) is {}, not {}",
+                i % WIDTH,
+                i / WIDTH,
+                on(other.pixels[i]),
+                on(self.pixels[i])
+            ));
+        }
+        None
+    }
+}
+
+fn on(pixel: bool) -> &'static str {
+    if pixel {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// CHIP-8 as plainly as it can be written. Any failure is just `Err(())`,
+/// the machines only have to agree that something went wrong.
+struct Reference {
+    v: [u8; 16],
+    i: u16,
+    pc: usize,
+    stack: Vec<u16>,
+    memory: [u8; MEMORY_SIZE],
+    /// Row by row.
+    screen: [bool; WIDTH * HEIGHT],
+    delay: u8,
+    sound: u8,
+    pattern: Option<[u8; 16]>,
+    pitch: u8,
+    keys: [bool; 16],
+    quirks: Quirks,
+    wrap_pc: bool,
+    rng: Rng,
+    halted: bool,
+}
+
+impl Reference {
+    /// Starts from `cpu`'s memory, which has the font and the program.
+    fn new(cpu: &Cpu, setup: &Setup) -> Reference {
+        let mut memory = [0; MEMORY_SIZE];
+        memory.copy_from_slice(cpu.memory.as_slice());
+        Reference {
+            v: [0; 16],
+            i: 0,
+            pc: PROGRAM_START,
+            stack: Vec::new(),
+            memory,
+            screen: [false; WIDTH * HEIGHT],
+            delay: 0,
+            sound: 0,
+            pattern: None,
+            pitch: DEFAULT_PITCH,
+            keys: setup.keys,
+            quirks: setup.quirks,
+            wrap_pc: setup.pc_overflow == PcOverflow::Wrap,
+            rng: Rng::new(setup.seed),
+            halted: false,
+        }
+    }
+
+    fn tick_timers(&mut self) {
+        self.delay = self.delay.saturating_sub(1);
+        self.sound = self.sound.saturating_sub(1);
+    }
+
+    fn snapshot(&self) -> Snapshot<'_> {
+        Snapshot {
+            registers: self.v,
+            index: self.i,
+            program_counter: self.pc,
+            stack: &self.stack,
+            delay_timer: self.delay,
+            sound_timer: self.sound,
+            audio_pattern: self.pattern,
+            pitch: self.pitch,
+            halted: self.halted,
+            memory: &self.memory,
+            pixels: &self.screen,
+        }
+    }
+
+    fn step(&mut self) -> Result<(), ()> {
+        if self.wrap_pc {
+            self.pc %= MEMORY_SIZE;
+        } else if self.pc + 1 >= MEMORY_SIZE {
+            return Err(());
+        }
+        let opcode = u16::from_be_bytes([
+            self.memory[self.pc],
+            self.memory[(self.pc + 1) % MEMORY_SIZE],
+        ]);
+        self.pc += 2;
+
+        let x = (opcode >> 8 & 0xF) as usize;
+        let y = (opcode >> 4 & 0xF) as usize;
+        let n = (opcode & 0xF) as usize;
+        let kk = opcode as u8;
+        let nnn = opcode & 0xFFF;
+        let (vx, vy) = (self.v[x], self.v[y]);
+
+        match (opcode >> 12, x, y, n) {
+            (0x0, 0, 0x0, 0x0) => self.halted = true,
+            (0x0, 0, 0xE, 0x0) => self.screen = [false; WIDTH * HEIGHT],
+            (0x0, 0, 0xE, 0xE) => self.pc = self.stack.pop().ok_or(())? as usize,
+            (0x0, 0, 0xF, 0xD) => self.halted = true,
+            // the scrolling and resolution switches of later platforms
+            (0x0, 0, 0xC | 0xD, _) | (0x0, 0, 0xF, 0xB..=0xF) => return Err(()),
+            // machine code routines are ignored
+            (0x0, ..) => {}
+            (0x1, ..) => self.pc = nnn as usize,
+            (0x2, ..) => {
+                if self.stack.len() == 16 {
+                    return Err(());
+                }
+                self.stack.push(self.pc as u16);
+                self.pc = nnn as usize;
+            }
+            (0x3, ..) => self.skip_if(vx == kk),
+            (0x4, ..) => self.skip_if(vx != kk),
+            (0x5, _, _, 0x0) => self.skip_if(vx == vy),
+            (0x6, ..) => self.v[x] = kk,
+            (0x7, ..) => self.v[x] = vx.wrapping_add(kk),
+            (0x8, _, _, 0x0) => self.v[x] = vy,
+            (0x8, _, _, 0x1..=0x3) => {
+                self.v[x] = match n {
+                    0x1 => vx | vy,
+                    0x2 => vx & vy,
+                    _ => vx ^ vy,
+                };
+                if self.quirks.logic_resets_vf {
+                    self.v[0xF] = 0;
+                }
+            }
+            (0x8, _, _, 0x4) => {
+                let sum = vx as u16 + vy as u16;
+                self.v[x] = sum as u8;
+                self.v[0xF] = (sum > 0xFF) as u8;
+            }
+            (0x8, _, _, 0x5) => {
+                self.v[x] = vx.wrapping_sub(vy);
+                self.v[0xF] = (vx >= vy) as u8;
+            }
+            (0x8, _, _, 0x7) => {
+                self.v[x] = vy.wrapping_sub(vx);
+                self.v[0xF] = (vy >= vx) as u8;
+            }
+            (0x8, _, _, 0x6 | 0xE) => {
+                let value = if self.quirks.shift_uses_vy { vy } else { vx };
+                if n == 0x6 {
+                    self.v[x] = value / 2;
+                    self.v[0xF] = value % 2;
+                } else {
+                    self.v[x] = value.wrapping_mul(2);
+                    self.v[0xF] = (value >= 0x80) as u8;
+                }
+            }
+            (0x9, _, _, 0x0) => self.skip_if(vx != vy),
+            (0xA, ..) => self.i = nnn,
+            (0xB, ..) => {
+                let offset = if self.quirks.jump_uses_vx {
+                    vx
+                } else {
+                    self.v[0]
+                };
+                self.pc = nnn as usize + offset as usize;
+            }
+            (0xC, ..) => self.v[x] = self.rng.next_u8() & kk,
+            (0xD, ..) => self.draw(vx as usize, vy as usize, n)?,
+            (0xE, _, 0x9, 0xE) => self.skip_if(self.keys[vx as usize % 16]),
+            (0xE, _, 0xA, 0x1) => self.skip_if(!self.keys[vx as usize % 16]),
+            (0xF, 0x0, 0x0, 0x2) => {
+                let start = self.i as usize;
+                let bytes = self.memory.get(start..start + 16).ok_or(())?;
+                self.pattern = Some(bytes.try_into().expect("16 bytes"));
+            }
+            (0xF, _, 0x0, 0x7) => self.v[x] = self.delay,
+            (0xF, _, 0x0, 0xA) => match (0..16).find(|&key| self.keys[key]) {
+                Some(key) => self.v[x] = key as u8,
+                None => self.pc -= 2,
+            },
+            (0xF, _, 0x1, 0x5) => self.delay = vx,
+            (0xF, _, 0x1, 0x8) => self.sound = vx,
+            (0xF, _, 0x1, 0xE) => self.i = (self.i + vx as u16) % 0x1000,
+            (0xF, _, 0x2, 0x9) => self.i = (FONT_ADDR + vx as usize % 16 * FONT_HEIGHT) as u16,
+            (0xF, _, 0x3, 0x0) => {
+                self.i = (BIG_FONT_ADDR + vx as usize % 16 * BIG_FONT_HEIGHT) as u16
+            }
+            (0xF, _, 0x3, 0xA) => self.pitch = vx,
+            (0xF, _, 0x3, 0x3) => {
+                self.write(0, vx / 100)?;
+                self.write(1, vx / 10 % 10)?;
+                self.write(2, vx % 10)?;
+            }
+            (0xF, _, 0x5, 0x5) => {
+                for r in 0..=x {
+                    self.write(r, self.v[r])?;
+                }
+                if self.quirks.load_store_increments_i {
+                    self.i += x as u16 + 1;
+                }
+            }
+            (0xF, _, 0x6, 0x5) => {
+                for r in 0..=x {
+                    self.v[r] = *self.memory.get(self.i as usize + r).ok_or(())?;
+                }
+                if self.quirks.load_store_increments_i {
+                    self.i += x as u16 + 1;
+                }
+            }
+            _ => return Err(()),
+        }
+        Ok(())
+    }
+
+    fn skip_if(&mut self, condition: bool) {
+        if condition {
+            self.pc += 2;
+        }
+    }
+
+    /// Writes at I + offset.
+    fn write(&mut self, offset: usize, value: u8) -> Result<(), ()> {
+        *self.memory.get_mut(self.i as usize + offset).ok_or(())? = value;
+        Ok(())
+    }
+
+    fn draw(&mut self, x: usize, y: usize, n: usize) -> Result<(), ()> {
+        let i = self.i as usize;
+        let sprite = self.memory.get(i..i + n).ok_or(())?;
+        let (x, y) = (x % WIDTH, y % HEIGHT);
+        let mut collision = false;
+        for (row, byte) in sprite.iter().enumerate() {
+            for column in 0..8 {
+                let (mut px, mut py) = (x + column, y + row);
+                if px >= WIDTH || py >= HEIGHT {
+                    if !self.quirks.wrap_sprites {
+                        continue;
+                    }
+                    px %= WIDTH;
+                    py %= HEIGHT;
+                }
+                if byte >> (7 - column) & 1 == 1 {
+                    let pixel = &mut self.screen[py * WIDTH + px];
+                    collision |= *pixel;
+                    *pixel = !*pixel;
+                }
+            }
+        }
+        self.v[0xF] = collision as u8;
+        Ok(())
+    }
+}
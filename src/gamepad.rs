@@ -0,0 +1,243 @@
+//! Game controller input through the Linux joystick API (`/dev/input/js*`).
+//!
+//! Every device gets a reader thread that forwards events over a channel; the
+//! thread ends when the device goes away, and new devices are picked up by
+//! rescanning `/dev/input` every second.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const INPUT_DIR: &str = "/dev/input";
+const RESCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Axis values past this count as pressed in that direction.
+const AXIS_THRESHOLD: i16 = 16_384;
+
+const JS_EVENT_BUTTON: u8 = 0x01;
+const JS_EVENT_AXIS: u8 = 0x02;
+const JS_EVENT_INIT: u8 = 0x80;
+
+/// A controller input that can be bound to a keypad key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Input {
+    Button(u8),
+    /// Axis pushed towards negative values (left/up).
+    AxisNegative(u8),
+    /// Axis pushed towards positive values (right/down).
+    AxisPositive(u8),
+}
+
+/// Which keypad key each controller input presses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bindings {
+    pub keys: HashMap<Input, u8>,
+}
+
+impl Default for Bindings {
+    /// Stick and d-pad on 2/4/6/8, which is what most games use for directions,
+    /// and the face buttons on 5, 0, A and B.
+    fn default() -> Self {
+        let mut keys = HashMap::new();
+        // axis 0/1 is the left stick, 6/7 the d-pad on most controllers
+        for (x, y) in [(0, 1), (6, 7)] {
+            keys.insert(Input::AxisNegative(x), 0x4);
+            keys.insert(Input::AxisPositive(x), 0x6);
+            keys.insert(Input::AxisNegative(y), 0x2);
+            keys.insert(Input::AxisPositive(y), 0x8);
+        }
+        keys.insert(Input::Button(0), 0x5);
+        keys.insert(Input::Button(1), 0x0);
+        keys.insert(Input::Button(2), 0xA);
+        keys.insert(Input::Button(3), 0xB);
+        keys.insert(Input::Button(7), 0xF); // start
+        Bindings { keys }
+    }
+}
+
+impl Bindings {
+    /// Parses `button0`, `axis1-` or `axis1+`.
+    pub fn parse_input(text: &str) -> Option<Input> {
+        if let Some(button) = text.strip_prefix("button") {
+            return button.parse().ok().map(Input::Button);
+        }
+        let axis = text.strip_prefix("axis")?;
+        if let Some(axis) = axis.strip_suffix('-') {
+            return axis.parse().ok().map(Input::AxisNegative);
+        }
+        axis.strip_suffix('+')?
+            .parse()
+            .ok()
+            .map(Input::AxisPositive)
+    }
+}
+
+/// A controller found in `/dev/input`.
+pub struct GamepadInfo {
+    pub path: PathBuf,
+    pub name: String,
+}
+
+/// Lists the connected controllers.
+pub fn list() -> Vec<GamepadInfo> {
+    let mut pads: Vec<GamepadInfo> = joystick_devices()
+        .into_iter()
+        .map(|path| {
+            let name = path
+                .file_name()
+                .and_then(|device| {
+                    let sys = Path::new("/sys/class/input")
+                        .join(device)
+                        .join("device/name");
+                    fs::read_to_string(sys).ok()
+                })
+                .map_or_else(|| "unknown".to_string(), |name| name.trim().to_string());
+            GamepadInfo { path, name }
+        })
+        .collect();
+    pads.sort_by(|a, b| a.path.cmp(&b.path));
+    pads
+}
+
+fn joystick_devices() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(INPUT_DIR) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("js"))
+        })
+        .collect()
+}
+
+enum Message {
+    Event {
+        device: PathBuf,
+        input: Input,
+        pressed: bool,
+    },
+    Disconnected(PathBuf),
+}
+
+/// All connected controllers, merged into one keypad state.
+pub struct Gamepads {
+    pub bindings: Bindings,
+    sender: Sender<Message>,
+    receiver: Receiver<Message>,
+    /// Inputs currently held, per device.
+    held: HashMap<PathBuf, Vec<Input>>,
+    last_scan: Option<Instant>,
+}
+
+impl Gamepads {
+    pub fn new(bindings: Bindings) -> Gamepads {
+        let (sender, receiver) = mpsc::channel();
+        Gamepads {
+            bindings,
+            sender,
+            receiver,
+            held: HashMap::new(),
+            last_scan: None,
+        }
+    }
+
+    /// Handles hotplugging and pending events. Call once per frame.
+    pub fn poll(&mut self) {
+        if self
+            .last_scan
+            .is_none_or(|scan| scan.elapsed() >= RESCAN_INTERVAL)
+        {
+            self.scan();
+        }
+
+        while let Ok(message) = self.receiver.try_recv() {
+            match message {
+                Message::Event {
+                    device,
+                    input,
+                    pressed,
+                } => {
+                    let held = self.held.entry(device).or_default();
+                    held.retain(|&held| held != input);
+                    if pressed {
+                        held.push(input);
+                    }
+                }
+                Message::Disconnected(device) => {
+                    self.held.remove(&device);
+                }
+            }
+        }
+    }
+
+    /// Keypad keys pressed on any controller.
+    pub fn keys(&self) -> [bool; 16] {
+        let mut keys = [false; 16];
+        for input in self.held.values().flatten() {
+            if let Some(&key) = self.bindings.keys.get(input) {
+                keys[key as usize & 0xF] = true;
+            }
+        }
+        keys
+    }
+
+    pub fn connected(&self) -> usize {
+        self.held.len()
+    }
+
+    fn scan(&mut self) {
+        self.last_scan = Some(Instant::now());
+
+        for device in joystick_devices() {
+            if self.held.contains_key(&device) {
+                continue;
+            }
+            let Ok(file) = File::open(&device) else {
+                continue;
+            };
+
+            self.held.insert(device.clone(), Vec::new());
+            let sender = self.sender.clone();
+            thread::spawn(move || read_events(device, file, sender));
+        }
+    }
+}
+
+/// Forwards `struct js_event { u32 time; i16 value; u8 type; u8 number; }` records.
+fn read_events(device: PathBuf, mut file: File, sender: Sender<Message>) {
+    let mut event = [0u8; 8];
+    while file.read_exact(&mut event).is_ok() {
+        let value = i16::from_ne_bytes([event[4], event[5]]);
+        let number = event[7];
+
+        let events = match event[6] & !JS_EVENT_INIT {
+            JS_EVENT_BUTTON => vec![(Input::Button(number), value != 0)],
+            JS_EVENT_AXIS => vec![
+                (Input::AxisNegative(number), value <= -AXIS_THRESHOLD),
+                (Input::AxisPositive(number), value >= AXIS_THRESHOLD),
+            ],
+            _ => continue,
+        };
+
+        for (input, pressed) in events {
+            let message = Message::Event {
+                device: device.clone(),
+                input,
+                pressed,
+            };
+            if sender.send(message).is_err() {
+                return;
+            }
+        }
+    }
+
+    let _ = sender.send(Message::Disconnected(device));
+}
@@ -0,0 +1,160 @@
+//! Animated GIFs of a play session, written frame by frame as it goes.
+//!
+//! The screen changes far less often than 60 times a second, so a frame is
+//! only written when it differs from the last one, shown for as long as the
+//! screen stayed that way. GIF delays are in hundredths of a second, so the
+//! delays are rounded such that they add up to the real time.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::image::Image;
+
+/// Two colours need the smallest code size GIF allows.
+const MIN_CODE_SIZE: u8 = 2;
+const MAX_CODE: u16 = 4095;
+
+pub struct GifWriter<W: Write> {
+    out: W,
+    width: usize,
+    height: usize,
+    /// The frame waiting for its delay, which is only known once the screen
+    /// changes again.
+    pending: Option<Vec<u8>>,
+    /// 60Hz frames so far, and the hundredths of a second written for them.
+    frames: u64,
+    written: u64,
+    /// Frames actually written to the file.
+    images: usize,
+}
+
+impl<W: Write> GifWriter<W> {
+    /// Writes the header, with `first`'s size and palette for the whole
+    /// animation.
+    pub fn new(mut out: W, first: &Image) -> io::Result<GifWriter<W>> {
+        out.write_all(b"GIF89a")?;
+        out.write_all(&(first.width as u16).to_le_bytes())?;
+        out.write_all(&(first.height as u16).to_le_bytes())?;
+        // a global colour table of 2 entries, background colour 0
+        out.write_all(&[0x80, 0, 0])?;
+        for colour in first.palette {
+            out.write_all(&[colour.0, colour.1, colour.2])?;
+        }
+        // loop forever
+        out.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+
+        Ok(GifWriter {
+            out,
+            width: first.width,
+            height: first.height,
+            pending: None,
+            frames: 0,
+            written: 0,
+            images: 0,
+        })
+    }
+
+    /// Adds one 60Hz frame. It has to be the size of the first.
+    pub fn frame(&mut self, image: &Image) -> io::Result<()> {
+        if self.pending.as_ref() != Some(&image.pixels) {
+            self.flush()?;
+            self.pending = Some(image.pixels.clone());
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Writes the last frame and the trailer, returning how many images the
+    /// animation has.
+    pub fn finish(mut self) -> io::Result<usize> {
+        self.flush()?;
+        self.out.write_all(&[0x3B])?;
+        self.out.flush()?;
+        Ok(self.images)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let Some(pixels) = self.pending.take() else {
+            return Ok(());
+        };
+        let total = self.frames * 100 / 60;
+        let delay = (total - self.written).clamp(1, u16::MAX as u64);
+        self.written += delay;
+
+        // graphic control extension with the delay, then the image descriptor
+        self.out.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+        self.out.write_all(&(delay as u16).to_le_bytes())?;
+        self.out.write_all(&[0x00, 0x00, 0x2C, 0, 0, 0, 0])?;
+        self.out.write_all(&(self.width as u16).to_le_bytes())?;
+        self.out.write_all(&(self.height as u16).to_le_bytes())?;
+        self.out.write_all(&[0x00, MIN_CODE_SIZE])?;
+        for block in lzw(&pixels).chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0x00])?;
+        self.images += 1;
+        Ok(())
+    }
+}
+
+/// GIF's variable width LZW, codes packed least significant bit first.
+fn lzw(pixels: &[u8]) -> Vec<u8> {
+    let clear: u16 = 1 << MIN_CODE_SIZE;
+    let end = clear + 1;
+    let mut out = Vec::new();
+    let (mut bits, mut count) = (0u32, 0u32);
+    let mut emit = |code: u16, size: u32, out: &mut Vec<u8>| {
+        bits |= (code as u32) << count;
+        count += size;
+        while count >= 8 {
+            out.push(bits as u8);
+            bits >>= 8;
+            count -= 8;
+        }
+    };
+
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    let mut size = MIN_CODE_SIZE as u32 + 1;
+    emit(clear, size, &mut out);
+
+    let Some((&first, rest)) = pixels.split_first() else {
+        emit(end, size, &mut out);
+        emit(0, 7, &mut out);
+        return out;
+    };
+    let mut prefix = first as u16;
+    for &pixel in rest {
+        if let Some(&code) = table.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+        emit(prefix, size, &mut out);
+        if next > MAX_CODE {
+            // the table is full, start a new one
+            emit(clear, size, &mut out);
+            table.clear();
+            next = end + 1;
+            size = MIN_CODE_SIZE as u32 + 1;
+        } else {
+            // the decoder adds this entry after reading the next code, at
+            // which point it may need a bit more
+            if next >= 1 << size && size < 12 {
+                size += 1;
+            }
+            table.insert((prefix, pixel), next);
+            next += 1;
+        }
+        prefix = pixel as u16;
+    }
+    emit(prefix, size, &mut out);
+    // the decoder still adds an entry for that code before reading the next
+    if next >= 1 << size && size < 12 {
+        size += 1;
+    }
+    emit(end, size, &mut out);
+    // pad the last byte
+    emit(0, 7, &mut out);
+    out
+}
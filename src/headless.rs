@@ -0,0 +1,146 @@
+use crate::cpu::Cpu;
+use crate::error::Error;
+use crate::rng::Rng;
+use crate::savestate::State;
+
+/// Process exit codes of the headless runner, so CI pipelines can branch on the result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ExitStatus {
+    /// Everything ran and every check passed.
+    Ok = 0,
+    /// A check failed, e.g. the framebuffer hash did not match.
+    CheckFailed = 1,
+    /// Bad arguments or unreadable files.
+    Usage = 2,
+    /// The ROM hit an emulation error such as an unknown opcode or stack overflow.
+    EmulationError = 3,
+    /// The ROM was expected to halt but used up its frames first.
+    Timeout = 4,
+    /// An assertion ROM reported a failure.
+    AssertionFailed = 5,
+}
+
+impl ExitStatus {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+/// How a headless run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The ROM executed 0000.
+    Halted,
+    /// All requested frames ran without the ROM halting.
+    FramesElapsed,
+    Error(Error),
+}
+
+pub struct Run {
+    pub cpu: Cpu,
+    /// Frames actually run, fewer than requested if the ROM halted or failed.
+    pub frames: usize,
+    pub outcome: Outcome,
+}
+
+/// A machine set up for reproducible runs: the RNG is seeded with 0 and test
+/// ROM assertions are enabled.
+pub fn machine() -> Cpu {
+    let mut cpu = Cpu::new();
+    cpu.rng = Rng::new(0);
+    cpu.assertions = true;
+    cpu
+}
+
+/// Runs `rom` on a fresh `machine()` for up to `frames` frames.
+pub fn run_rom(rom: &[u8], frames: usize) -> Result<Run, Error> {
+    run_machine(machine(), rom, frames)
+}
+
+/// Runs `rom` without any frontend for up to `frames` frames and returns the machine,
+/// so callers can inspect or hash the resulting state. Only loading the ROM can
+/// fail; emulation errors end up in the outcome.
+pub fn run_machine(cpu: Cpu, rom: &[u8], frames: usize) -> Result<Run, Error> {
+    run_machine_with(cpu, rom, frames, |_| {})
+}
+
+/// Like `run_machine`, but calls `input` before every frame, e.g. to press keys.
+pub fn run_machine_with(
+    mut cpu: Cpu,
+    rom: &[u8],
+    frames: usize,
+    input: impl FnMut(&mut Cpu),
+) -> Result<Run, Error> {
+    cpu.load_rom(rom)?;
+    Ok(resume(cpu, frames, input))
+}
+
+/// Like `run_machine_with`, for a machine that already has a program in
+/// memory, e.g. one restored from a save state.
+pub fn resume(cpu: Cpu, frames: usize, input: impl FnMut(&mut Cpu)) -> Run {
+    resume_with(cpu, frames, input, Cpu::run_frame)
+}
+
+/// Like `resume`, with something else running each frame, e.g. a script.
+pub fn resume_with(
+    mut cpu: Cpu,
+    frames: usize,
+    mut input: impl FnMut(&mut Cpu),
+    mut run_frame: impl FnMut(&mut Cpu) -> Result<(), Error>,
+) -> Run {
+    for frame in 0..frames {
+        if cpu.halted {
+            return Run {
+                cpu,
+                frames: frame,
+                outcome: Outcome::Halted,
+            };
+        }
+        input(&mut cpu);
+        if let Err(err) = run_frame(&mut cpu) {
+            return Run {
+                cpu,
+                frames: frame + 1,
+                outcome: Outcome::Error(err),
+            };
+        }
+    }
+
+    let outcome = if cpu.halted {
+        Outcome::Halted
+    } else {
+        Outcome::FramesElapsed
+    };
+    Run {
+        cpu,
+        frames,
+        outcome,
+    }
+}
+
+/// Runs two machines side by side, e.g. the same program on both engines, and
+/// returns the first frame after which they no longer agree. `input` is applied
+/// to the first and its keys copied to the second.
+pub fn first_divergence(
+    mut a: Cpu,
+    mut b: Cpu,
+    frames: usize,
+    mut input: impl FnMut(&mut Cpu),
+) -> Option<usize> {
+    for frame in 0..frames {
+        if a.halted && b.halted {
+            return None;
+        }
+        input(&mut a);
+        b.keys = a.keys;
+
+        let result = a.run_frame();
+        if result != b.run_frame() || State::capture(&a) != State::capture(&b) {
+            return Some(frame + 1);
+        }
+        if result.is_err() {
+            return None;
+        }
+    }
+    None
+}
@@ -0,0 +1,203 @@
+//! What the core needs from the machine it runs on: somewhere to show the
+//! screen, a buzzer, the keypad and a place to load ROMs from. Nothing here
+//! needs the standard library, so a microcontroller board can implement the
+//! traits, an RP2040 with an SSD1306 OLED say, and run frames with
+//! `run_frame`:
+//!
+//! ```ignore
+//! loop {
+//!     host::run_frame(&mut cpu, &mut oled, &mut buzzer, &mut buttons)?;
+//!     wait_for_next_tick(); // 60Hz
+//! }
+//! ```
+//!
+//! Applications that draw the screen themselves, a game engine or a GUI
+//! toolkit, can pull frames instead with `Cpu::poll_frame`: one call per
+//! 60Hz tick, with the keys in and the screen, the sound and what happened
+//! out.
+//!
+//! ```ignore
+//! let frame = cpu.poll_frame(&InputState { keys });
+//! if frame.changed {
+//!     texture.update(frame.display.pixels());
+//! }
+//! for event in frame.events.iter() {
+//!     // halted, exited, an error, an assertion, a collision break
+//! }
+//! ```
+//!
+//! The terminal frontend has the richer `frontend::Frontend` instead, with
+//! menus and hotkeys on top.
+
+use crate::assertion::Assertion;
+use crate::cpu::Cpu;
+use crate::display::Display;
+use crate::error::Error;
+use crate::memory::{MEMORY_SIZE, PROGRAM_START};
+
+/// Shows the 64x32 display, e.g. by packing it into an OLED's pages.
+pub trait Screen {
+    /// Called when rows changed; `Display::is_dirty` says which.
+    fn show(&mut self, display: &Display);
+}
+
+pub trait Buzzer {
+    /// Called every frame with whether the sound timer is running.
+    fn set_sound(&mut self, on: bool);
+}
+
+/// Boards without one can pass `&mut ()`.
+impl Buzzer for () {
+    fn set_sound(&mut self, _on: bool) {}
+}
+
+pub trait Keypad {
+    /// Which of the keys 0-F are held right now.
+    fn keys(&mut self) -> [bool; 16];
+}
+
+/// Where a ROM's bytes come from: flash, an SD card, a serial link.
+pub trait RomSource {
+    type Error: From<Error>;
+
+    /// Copies the whole ROM into `buf`, returning its length.
+    fn read_rom(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+/// A ROM already in memory, e.g. `include_bytes!`.
+impl RomSource for &[u8] {
+    type Error = Error;
+
+    fn read_rom(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let rom = buf
+            .get_mut(..self.len())
+            .ok_or(Error::RomTooLarge { size: self.len() })?;
+        rom.copy_from_slice(self);
+        Ok(self.len())
+    }
+}
+
+/// Loads a ROM from `source` like `Cpu::load_rom`.
+pub fn load_rom<S: RomSource>(cpu: &mut Cpu, source: &mut S) -> Result<(), S::Error> {
+    let mut rom = [0; MEMORY_SIZE - PROGRAM_START];
+    let len = source.read_rom(&mut rom)?;
+    cpu.load_rom(&rom[..len])?;
+    Ok(())
+}
+
+/// One 60Hz frame: reads the keypad, runs the frame, shows the screen if it
+/// changed and switches the buzzer.
+pub fn run_frame(
+    cpu: &mut Cpu,
+    screen: &mut impl Screen,
+    buzzer: &mut impl Buzzer,
+    keypad: &mut impl Keypad,
+) -> Result<(), Error> {
+    cpu.keys = keypad.keys();
+    let result = cpu.run_frame();
+    if cpu.display.any_dirty() {
+        screen.show(&cpu.display);
+        cpu.display.clear_dirty();
+    }
+    buzzer.set_sound(cpu.sound_active());
+    result
+}
+
+/// What goes into a frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InputState {
+    /// Which of the keys 0-F are held.
+    pub keys: [bool; 16],
+}
+
+/// Something the machine did during a frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// It ran 0000, or an assertion ROM finished; it stays stopped until
+    /// `Cpu::reset`.
+    Halted,
+    /// SUPER-CHIP's exit with `OnExit::Menu`, see `Cpu::exited`.
+    Exited,
+    /// The frame stopped at an instruction that failed. The machine is left
+    /// as it was, what to do next is up to the caller.
+    Error(Error),
+    /// A test ROM reported a result.
+    Assertion(Assertion),
+    /// `Cpu::break_on_collision` paused after the Dxyn at `pc`.
+    Collision { pc: usize },
+}
+
+/// The events of a frame, without allocating: each happens at most once.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Events {
+    events: [Option<Event>; 5],
+    len: usize,
+}
+
+impl Events {
+    fn push(&mut self, event: Event) {
+        self.events[self.len] = Some(event);
+        self.len += 1;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Event> {
+        self.events[..self.len].iter().flatten()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// What came out of a frame.
+pub struct FrameOutput<'a> {
+    pub display: &'a Display,
+    /// Whether the screen changed since the last frame.
+    pub changed: bool,
+    /// Whether the sound timer is running.
+    pub sound: bool,
+    pub events: Events,
+}
+
+impl Cpu {
+    /// Runs one 60Hz frame with `input` and returns how it went. The single
+    /// entry point for embedding: it takes care of the keys, the timers and
+    /// the screen's dirty rows.
+    pub fn poll_frame(&mut self, input: &InputState) -> FrameOutput<'_> {
+        let halted = self.halted;
+        let exited = self.exited;
+        let assertion = self.assertion.is_some();
+        let collision = self.collision.is_some();
+
+        self.keys = input.keys;
+        let result = self.run_frame();
+
+        let mut events = Events::default();
+        if let Err(err) = result {
+            events.push(Event::Error(err));
+        }
+        if let (false, Some(found)) = (assertion, &self.assertion) {
+            events.push(Event::Assertion(found.clone()));
+        }
+        if !collision {
+            if let Some(found) = &self.collision {
+                events.push(Event::Collision { pc: found.pc });
+            }
+        }
+        if !exited && self.exited {
+            events.push(Event::Exited);
+        }
+        if !halted && self.halted {
+            events.push(Event::Halted);
+        }
+
+        let changed = self.display.any_dirty();
+        self.display.clear_dirty();
+        FrameOutput {
+            display: &self.display,
+            changed,
+            sound: self.sound_active(),
+            events,
+        }
+    }
+}
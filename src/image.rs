@@ -0,0 +1,253 @@
+//! Two-colour images of the screen, and PNG files of them. There is no image
+//! crate to lean on, so the PNG encoder is written out here, with just enough
+//! DEFLATE (fixed Huffman codes, greedy matching) for scaled up pixel art.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::display::{Display, HEIGHT, WIDTH};
+use crate::frontend::{PixelStyle, Rgb};
+use crate::stats;
+
+/// A frame as palette indices, 0 for background and 1 for foreground.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub palette: [Rgb; 2],
+    /// `width * height` indices, row by row.
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// The display in the style's colours, each pixel `scale` times as big.
+    /// Ghosting is left out, it is a frontend effect.
+    pub fn of(display: &Display, style: &PixelStyle) -> Image {
+        let scale = style.scale.max(1) as usize;
+        let (width, height) = (WIDTH * scale, HEIGHT * scale);
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                pixels.push(display.pixel(x / scale, y / scale) as u8);
+            }
+        }
+        Image {
+            width,
+            height,
+            palette: [style.background, style.foreground],
+            pixels,
+        }
+    }
+
+    /// The image as a 1 bit per pixel palette PNG.
+    pub fn png(&self) -> Vec<u8> {
+        // every row starts with its filter type, 0 for none
+        let stride = self.width.div_ceil(8);
+        let mut raw = Vec::with_capacity((stride + 1) * self.height);
+        for row in self.pixels.chunks(self.width.max(1)) {
+            raw.push(0);
+            raw.extend(row.chunks(8).map(|bits| {
+                bits.iter()
+                    .enumerate()
+                    .fold(0, |byte, (i, &bit)| byte | bit << (7 - i))
+            }));
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // bit depth 1, palette colour, deflate, no filtering variant, not interlaced
+        header.extend_from_slice(&[1, 3, 0, 0, 0]);
+        let palette: Vec<u8> = self
+            .palette
+            .iter()
+            .flat_map(|&Rgb(r, g, b)| [r, g, b])
+            .collect();
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        chunk(&mut png, b"IHDR", &header);
+        chunk(&mut png, b"PLTE", &palette);
+        chunk(&mut png, b"IDAT", &zlib(&raw));
+        chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.png())
+    }
+}
+
+/// Where the next screenshot of a ROM goes,
+/// `<data dir>/screenshots/<rom file name>-<n>.png` with the first `n` not
+/// taken yet.
+pub fn path_for(rom: &Path) -> Option<PathBuf> {
+    let name = rom.file_name()?.to_string_lossy().into_owned();
+    let dir = stats::data_dir()?.join("screenshots");
+    (1..)
+        .map(|n| dir.join(format!("{}-{}.png", name, n)))
+        .find(|path| !path.exists())
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const WINDOW: usize = 32 * 1024;
+const HASH_BITS: u32 = 14;
+
+/// DEFLATE bits go out least significant first.
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.bits |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are defined most significant bit first.
+    fn write_code(&mut self, code: u32, count: u32) {
+        self.write(code.reverse_bits() >> (32 - count), count);
+    }
+
+    /// A literal byte or length symbol, in the fixed code.
+    fn symbol(&mut self, symbol: u16) {
+        let symbol = symbol as u32;
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn matched(&mut self, length: usize, distance: usize) {
+        let code = LENGTH_BASE
+            .iter()
+            .rposition(|&base| base as usize <= length)
+            .unwrap();
+        self.symbol(257 + code as u16);
+        self.write(
+            (length - LENGTH_BASE[code] as usize) as u32,
+            LENGTH_EXTRA[code] as u32,
+        );
+        let code = DISTANCE_BASE
+            .iter()
+            .rposition(|&base| base as usize <= distance)
+            .unwrap();
+        self.write_code(code as u32, 5);
+        self.write(
+            (distance - DISTANCE_BASE[code] as usize) as u32,
+            DISTANCE_EXTRA[code] as u32,
+        );
+    }
+}
+
+/// `data` as a zlib stream of a single fixed Huffman block.
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter {
+        out: vec![0x78, 0x01],
+        bits: 0,
+        count: 0,
+    };
+    // final block, fixed codes
+    writer.write(1, 1);
+    writer.write(1, 2);
+
+    let hash = |pos: usize| {
+        let value = u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], 0]);
+        (value.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+    };
+    // last position each 3 byte sequence was seen at
+    let mut table = vec![usize::MAX; 1 << HASH_BITS];
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut length = 0;
+        let mut distance = 0;
+        if pos + MIN_MATCH <= data.len() {
+            let slot = hash(pos);
+            let candidate = table[slot];
+            table[slot] = pos;
+            if candidate != usize::MAX && pos - candidate <= WINDOW {
+                let limit = (data.len() - pos).min(MAX_MATCH);
+                length = (0..limit)
+                    .take_while(|&i| data[candidate + i] == data[pos + i])
+                    .count();
+                distance = pos - candidate;
+            }
+        }
+
+        if length >= MIN_MATCH {
+            writer.matched(length, distance);
+            pos += length;
+        } else {
+            writer.symbol(data[pos] as u16);
+            pos += 1;
+        }
+    }
+    writer.symbol(256);
+    // pad the last byte
+    writer.write(0, (8 - writer.count) % 8);
+
+    let mut out = writer.out;
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
@@ -0,0 +1,137 @@
+//! The keypad as the game sees it, frame by frame, for stream overlays and
+//! input viewers. Every frame makes a line:
+//!
+//! ```text
+//! 1234 0120 58
+//! ```
+//!
+//! the frame number (counting from 0, frames the game ran, so a paused game
+//! adds none), the 16 keys as a hex bit mask, bit n for key n, and the held
+//! keys as hex digits, `-` for none.
+//!
+//! `--input-view <file>` keeps the line of the last frame in the file,
+//! written when the keys change, for an OBS text source to show;
+//! `--input-view <port>` (or `<addr:port>`) streams every line over TCP to
+//! whoever connects, for overlays that want every frame, in builds with the
+//! `net` feature.
+
+use std::fs;
+use std::io;
+#[cfg(feature = "net")]
+use std::io::Write;
+use std::net::SocketAddr;
+#[cfg(feature = "net")]
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+
+enum Sink {
+    File {
+        path: PathBuf,
+        last: Option<[bool; 16]>,
+    },
+    #[cfg(feature = "net")]
+    Socket {
+        listener: TcpListener,
+        clients: Vec<TcpStream>,
+    },
+}
+
+pub struct InputView {
+    sink: Sink,
+    frame: u64,
+}
+
+impl InputView {
+    /// A port or an address to listen on, or else a file to write.
+    pub fn open(target: &str) -> io::Result<InputView> {
+        let addr = match target.parse::<u16>() {
+            Ok(port) => Some(SocketAddr::from(([127, 0, 0, 1], port))),
+            Err(_) => target.parse().ok(),
+        };
+        let sink = match addr {
+            #[cfg(feature = "net")]
+            Some(addr) => {
+                let listener = TcpListener::bind(addr)?;
+                listener.set_nonblocking(true)?;
+                Sink::Socket {
+                    listener,
+                    clients: Vec::new(),
+                }
+            }
+            #[cfg(not(feature = "net"))]
+            Some(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "streaming needs a build with the net feature",
+                ))
+            }
+            None => {
+                let path = PathBuf::from(target);
+                // fail now rather than on the first key press
+                fs::write(&path, "")?;
+                Sink::File { path, last: None }
+            }
+        };
+        Ok(InputView { sink, frame: 0 })
+    }
+
+    /// Where overlays connect, for a socket.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match &self.sink {
+            #[cfg(feature = "net")]
+            Sink::Socket { listener, .. } => listener.local_addr().ok(),
+            Sink::File { .. } => None,
+        }
+    }
+
+    /// Call right before emulating each frame, with the keys it runs with.
+    pub fn frame(&mut self, keys: &[bool; 16]) -> io::Result<()> {
+        let line = line(self.frame, keys);
+        self.frame += 1;
+        match &mut self.sink {
+            Sink::File { path, last } => {
+                if *last == Some(*keys) {
+                    return Ok(());
+                }
+                *last = Some(*keys);
+                // renamed into place, so the overlay never reads half a line
+                let partial = path.with_extension("partial");
+                fs::write(&partial, line)?;
+                fs::rename(&partial, path)
+            }
+            #[cfg(feature = "net")]
+            Sink::Socket { listener, clients } => {
+                // until there is nobody new, or someone who gave up connecting
+                while let Ok((client, _)) = listener.accept() {
+                    if client.set_nonblocking(true).is_ok() {
+                        clients.push(client);
+                    }
+                }
+                // a viewer that went away or can't keep up is dropped
+                clients.retain_mut(|client| client.write_all(line.as_bytes()).is_ok());
+                Ok(())
+            }
+        }
+    }
+}
+
+fn line(frame: u64, keys: &[bool; 16]) -> String {
+    let bits = keys
+        .iter()
+        .enumerate()
+        .fold(0u16, |bits, (key, &held)| bits | (held as u16) << key);
+    let held: String = (0..16)
+        .filter(|&key| keys[key])
+        .map(|key| {
+            char::from_digit(key as u32, 16)
+                .unwrap_or('?')
+                .to_ascii_uppercase()
+        })
+        .collect();
+    let held = if held.is_empty() {
+        "-".to_string()
+    } else {
+        held
+    };
+    format!("{} {:04x} {}\n", frame, bits, held)
+}
@@ -0,0 +1,338 @@
+use core::fmt;
+
+/// A decoded CHIP-8 instruction.
+///
+/// x and y are registers (0-F), kk is a byte, n is a nibble
+/// and addr is an address between 0 and 4095.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// 0nnn: call machine code routine at addr (ignored by most interpreters).
+    /// Addresses 0E0, 0EE and 0FD are never decoded as Sys, those opcodes are
+    /// Cls, Ret and Exit, so Sys with one of them encodes to that instruction.
+    Sys { addr: u16 },
+    /// 00E0: clear the screen
+    Cls,
+    /// 00EE: return from the current sub-routine
+    Ret,
+    /// 00FD: exit the interpreter (SUPER-CHIP), see `OnExit`
+    Exit,
+    /// 1nnn: jump to addr
+    Jump { addr: u16 },
+    /// 2nnn: call sub-routine at addr
+    Call { addr: u16 },
+    /// 3xkk: skip if vx == kk
+    SeXkk { x: u8, kk: u8 },
+    /// 4xkk: skip if vx != kk
+    SneXkk { x: u8, kk: u8 },
+    /// 5xy0: skip if vx == vy
+    SeXy { x: u8, y: u8 },
+    /// 6xkk: set vx to kk
+    Set { x: u8, kk: u8 },
+    /// 7xkk: add kk to vx
+    Add { x: u8, kk: u8 },
+    /// 8xy0: set vx to vy
+    SetXy { x: u8, y: u8 },
+    /// 8xy1: vx |= vy
+    OrXy { x: u8, y: u8 },
+    /// 8xy2: vx &= vy
+    AndXy { x: u8, y: u8 },
+    /// 8xy3: vx ^= vy
+    XorXy { x: u8, y: u8 },
+    /// 8xy4: vx += vy, vf = carry
+    AddXy { x: u8, y: u8 },
+    /// 8xy5: vx -= vy, vf = not borrow
+    SubXy { x: u8, y: u8 },
+    /// 8xy6: vx >>= 1, vf = shifted out bit
+    ShrXy { x: u8, y: u8 },
+    /// 8xy7: vx = vy - vx, vf = not borrow
+    SubnXy { x: u8, y: u8 },
+    /// 8xyE: vx <<= 1, vf = shifted out bit
+    ShlXy { x: u8, y: u8 },
+    /// 9xy0: skip if vx != vy
+    SneXy { x: u8, y: u8 },
+    /// Annn: set I to addr
+    SetI { addr: u16 },
+    /// Bnnn: jump to addr + v0
+    JumpV0 { addr: u16 },
+    /// Cxkk: set vx to a random byte masked with kk
+    Rand { x: u8, kk: u8 },
+    /// Dxyn: draw an n byte sprite from I at (vx, vy), vf = collision
+    Draw { x: u8, y: u8, n: u8 },
+    /// Ex9E: skip if the key in vx is pressed
+    SkipKey { x: u8 },
+    /// ExA1: skip if the key in vx is not pressed
+    SkipNotKey { x: u8 },
+    /// F002: load the 16 byte audio pattern at I (XO-CHIP)
+    Audio,
+    /// Fx07: set vx to the delay timer
+    GetDelay { x: u8 },
+    /// Fx0A: wait for a key press and store it in vx
+    WaitKey { x: u8 },
+    /// Fx15: set the delay timer to vx
+    SetDelay { x: u8 },
+    /// Fx18: set the sound timer to vx
+    SetSound { x: u8 },
+    /// Fx1E: add vx to I
+    AddI { x: u8 },
+    /// Fx29: point I at the font sprite for the digit in vx
+    Font { x: u8 },
+    /// Fx30: point I at the big font sprite for the digit in vx (SUPER-CHIP)
+    BigFont { x: u8 },
+    /// Fx3A: set the audio pitch to vx (XO-CHIP)
+    Pitch { x: u8 },
+    /// Fx33: store the BCD of vx at I, I+1 and I+2
+    Bcd { x: u8 },
+    /// Fx55: store v0 to vx in memory starting at I
+    Store { x: u8 },
+    /// Fx65: load v0 to vx from memory starting at I
+    Load { x: u8 },
+}
+
+/// The opcode does not correspond to any known instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError {
+    pub opcode: u16,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown opcode {:04x}", self.opcode)
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+impl Instruction {
+    pub fn decode(opcode: u16) -> Result<Instruction, DecodeError> {
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+
+        let kk = (opcode & 0x00FF) as u8;
+        let n = (opcode & 0x000F) as u8;
+        let addr = opcode & 0x0FFF;
+
+        let instruction = match opcode {
+            0x00E0 => Instruction::Cls,
+            0x00EE => Instruction::Ret,
+            0x00FD => Instruction::Exit,
+            0x0000..=0x0FFF => Instruction::Sys { addr },
+            0x1000..=0x1FFF => Instruction::Jump { addr },
+            0x2000..=0x2FFF => Instruction::Call { addr },
+            0x3000..=0x3FFF => Instruction::SeXkk { x, kk },
+            0x4000..=0x4FFF => Instruction::SneXkk { x, kk },
+            0x5000..=0x5FFF if n == 0 => Instruction::SeXy { x, y },
+            0x6000..=0x6FFF => Instruction::Set { x, kk },
+            0x7000..=0x7FFF => Instruction::Add { x, kk },
+            0x8000..=0x8FFF => match n {
+                0x0 => Instruction::SetXy { x, y },
+                0x1 => Instruction::OrXy { x, y },
+                0x2 => Instruction::AndXy { x, y },
+                0x3 => Instruction::XorXy { x, y },
+                0x4 => Instruction::AddXy { x, y },
+                0x5 => Instruction::SubXy { x, y },
+                0x6 => Instruction::ShrXy { x, y },
+                0x7 => Instruction::SubnXy { x, y },
+                0xE => Instruction::ShlXy { x, y },
+                _ => return Err(DecodeError { opcode }),
+            },
+            0x9000..=0x9FFF if n == 0 => Instruction::SneXy { x, y },
+            0xA000..=0xAFFF => Instruction::SetI { addr },
+            0xB000..=0xBFFF => Instruction::JumpV0 { addr },
+            0xC000..=0xCFFF => Instruction::Rand { x, kk },
+            0xD000..=0xDFFF => Instruction::Draw { x, y, n },
+            0xE000..=0xEFFF => match kk {
+                0x9E => Instruction::SkipKey { x },
+                0xA1 => Instruction::SkipNotKey { x },
+                _ => return Err(DecodeError { opcode }),
+            },
+            0xF002 => Instruction::Audio,
+            0xF000..=0xFFFF => match kk {
+                0x07 => Instruction::GetDelay { x },
+                0x0A => Instruction::WaitKey { x },
+                0x15 => Instruction::SetDelay { x },
+                0x18 => Instruction::SetSound { x },
+                0x1E => Instruction::AddI { x },
+                0x29 => Instruction::Font { x },
+                0x30 => Instruction::BigFont { x },
+                0x3A => Instruction::Pitch { x },
+                0x33 => Instruction::Bcd { x },
+                0x55 => Instruction::Store { x },
+                0x65 => Instruction::Load { x },
+                _ => return Err(DecodeError { opcode }),
+            },
+            _ => return Err(DecodeError { opcode }),
+        };
+
+        Ok(instruction)
+    }
+
+    /// The opcode with its operands as letters, e.g. `Dxyn`, the same for
+    /// every instruction of a kind.
+    pub fn pattern(&self) -> &'static str {
+        match self {
+            Instruction::Sys { .. } => "0nnn",
+            Instruction::Cls => "00E0",
+            Instruction::Ret => "00EE",
+            Instruction::Exit => "00FD",
+            Instruction::Jump { .. } => "1nnn",
+            Instruction::Call { .. } => "2nnn",
+            Instruction::SeXkk { .. } => "3xkk",
+            Instruction::SneXkk { .. } => "4xkk",
+            Instruction::SeXy { .. } => "5xy0",
+            Instruction::Set { .. } => "6xkk",
+            Instruction::Add { .. } => "7xkk",
+            Instruction::SetXy { .. } => "8xy0",
+            Instruction::OrXy { .. } => "8xy1",
+            Instruction::AndXy { .. } => "8xy2",
+            Instruction::XorXy { .. } => "8xy3",
+            Instruction::AddXy { .. } => "8xy4",
+            Instruction::SubXy { .. } => "8xy5",
+            Instruction::ShrXy { .. } => "8xy6",
+            Instruction::SubnXy { .. } => "8xy7",
+            Instruction::ShlXy { .. } => "8xyE",
+            Instruction::SneXy { .. } => "9xy0",
+            Instruction::SetI { .. } => "Annn",
+            Instruction::JumpV0 { .. } => "Bnnn",
+            Instruction::Rand { .. } => "Cxkk",
+            Instruction::Draw { .. } => "Dxyn",
+            Instruction::SkipKey { .. } => "Ex9E",
+            Instruction::SkipNotKey { .. } => "ExA1",
+            Instruction::Audio => "F002",
+            Instruction::GetDelay { .. } => "Fx07",
+            Instruction::WaitKey { .. } => "Fx0A",
+            Instruction::SetDelay { .. } => "Fx15",
+            Instruction::SetSound { .. } => "Fx18",
+            Instruction::AddI { .. } => "Fx1E",
+            Instruction::Font { .. } => "Fx29",
+            Instruction::BigFont { .. } => "Fx30",
+            Instruction::Bcd { .. } => "Fx33",
+            Instruction::Pitch { .. } => "Fx3A",
+            Instruction::Store { .. } => "Fx55",
+            Instruction::Load { .. } => "Fx65",
+        }
+    }
+
+    pub fn encode(&self) -> u16 {
+        let x_only = |base: u16, x: u8| base | ((x & 0xF) as u16) << 8;
+        let xy = |base: u16, x: u8, y: u8| x_only(base, x) | ((y & 0xF) as u16) << 4;
+        let xkk = |base: u16, x: u8, kk: u8| x_only(base, x) | kk as u16;
+
+        match *self {
+            Instruction::Sys { addr } => addr & 0x0FFF,
+            Instruction::Cls => 0x00E0,
+            Instruction::Ret => 0x00EE,
+            Instruction::Exit => 0x00FD,
+            Instruction::Jump { addr } => 0x1000 | (addr & 0x0FFF),
+            Instruction::Call { addr } => 0x2000 | (addr & 0x0FFF),
+            Instruction::SeXkk { x, kk } => xkk(0x3000, x, kk),
+            Instruction::SneXkk { x, kk } => xkk(0x4000, x, kk),
+            Instruction::SeXy { x, y } => xy(0x5000, x, y),
+            Instruction::Set { x, kk } => xkk(0x6000, x, kk),
+            Instruction::Add { x, kk } => xkk(0x7000, x, kk),
+            Instruction::SetXy { x, y } => xy(0x8000, x, y),
+            Instruction::OrXy { x, y } => xy(0x8001, x, y),
+            Instruction::AndXy { x, y } => xy(0x8002, x, y),
+            Instruction::XorXy { x, y } => xy(0x8003, x, y),
+            Instruction::AddXy { x, y } => xy(0x8004, x, y),
+            Instruction::SubXy { x, y } => xy(0x8005, x, y),
+            Instruction::ShrXy { x, y } => xy(0x8006, x, y),
+            Instruction::SubnXy { x, y } => xy(0x8007, x, y),
+            Instruction::ShlXy { x, y } => xy(0x800E, x, y),
+            Instruction::SneXy { x, y } => xy(0x9000, x, y),
+            Instruction::SetI { addr } => 0xA000 | (addr & 0x0FFF),
+            Instruction::JumpV0 { addr } => 0xB000 | (addr & 0x0FFF),
+            Instruction::Rand { x, kk } => xkk(0xC000, x, kk),
+            Instruction::Draw { x, y, n } => xy(0xD000, x, y) | (n & 0xF) as u16,
+            Instruction::SkipKey { x } => x_only(0xE09E, x),
+            Instruction::SkipNotKey { x } => x_only(0xE0A1, x),
+            Instruction::Audio => 0xF002,
+            Instruction::GetDelay { x } => x_only(0xF007, x),
+            Instruction::WaitKey { x } => x_only(0xF00A, x),
+            Instruction::SetDelay { x } => x_only(0xF015, x),
+            Instruction::SetSound { x } => x_only(0xF018, x),
+            Instruction::AddI { x } => x_only(0xF01E, x),
+            Instruction::Font { x } => x_only(0xF029, x),
+            Instruction::BigFont { x } => x_only(0xF030, x),
+            Instruction::Pitch { x } => x_only(0xF03A, x),
+            Instruction::Bcd { x } => x_only(0xF033, x),
+            Instruction::Store { x } => x_only(0xF055, x),
+            Instruction::Load { x } => x_only(0xF065, x),
+        }
+    }
+}
+
+/// Cowgod's mnemonics, e.g. `LD V1, 0x2A` or `DRW V0, V1, 5`.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::Sys { addr } => write!(f, "SYS {:#05x}", addr),
+            Instruction::Cls => write!(f, "CLS"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::Ret => write!(f, "RET"),
+            Instruction::Jump { addr } => write!(f, "JP {:#05x}", addr),
+            Instruction::Call { addr } => write!(f, "CALL {:#05x}", addr),
+            Instruction::SeXkk { x, kk } => write!(f, "SE V{:X}, {:#04x}", x, kk),
+            Instruction::SneXkk { x, kk } => write!(f, "SNE V{:X}, {:#04x}", x, kk),
+            Instruction::SeXy { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::Set { x, kk } => write!(f, "LD V{:X}, {:#04x}", x, kk),
+            Instruction::Add { x, kk } => write!(f, "ADD V{:X}, {:#04x}", x, kk),
+            Instruction::SetXy { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::OrXy { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::AndXy { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::XorXy { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::AddXy { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::SubXy { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShrXy { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubnXy { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShlXy { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SneXy { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::SetI { addr } => write!(f, "LD I, {:#05x}", addr),
+            Instruction::JumpV0 { addr } => write!(f, "JP V0, {:#05x}", addr),
+            Instruction::Rand { x, kk } => write!(f, "RND V{:X}, {:#04x}", x, kk),
+            Instruction::Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipKey { x } => write!(f, "SKP V{:X}", x),
+            Instruction::SkipNotKey { x } => write!(f, "SKNP V{:X}", x),
+            Instruction::Audio => write!(f, "AUDIO"),
+            Instruction::GetDelay { x } => write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitKey { x } => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelay { x } => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSound { x } => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddI { x } => write!(f, "ADD I, V{:X}", x),
+            Instruction::Font { x } => write!(f, "LD F, V{:X}", x),
+            Instruction::BigFont { x } => write!(f, "LD HF, V{:X}", x),
+            Instruction::Pitch { x } => write!(f, "LD PITCH, V{:X}", x),
+            Instruction::Bcd { x } => write!(f, "LD B, V{:X}", x),
+            Instruction::Store { x } => write!(f, "LD [I], V{:X}", x),
+            Instruction::Load { x } => write!(f, "LD V{:X}, [I]", x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_opcode_round_trips() {
+        for opcode in 0..=0xFFFF {
+            if let Ok(instruction) = Instruction::decode(opcode) {
+                assert_eq!(instruction.encode(), opcode, "{}", instruction);
+                assert_eq!(Instruction::decode(instruction.encode()), Ok(instruction));
+            }
+        }
+    }
+
+    #[test]
+    fn sys_aliases() {
+        for addr in 0..=0x0FFF {
+            let decoded = Instruction::decode(Instruction::Sys { addr }.encode()).unwrap();
+            let expected = match addr {
+                0x0E0 => Instruction::Cls,
+                0x0EE => Instruction::Ret,
+                0x0FD => Instruction::Exit,
+                _ => Instruction::Sys { addr },
+            };
+            assert_eq!(decoded, expected);
+        }
+    }
+}
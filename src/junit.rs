@@ -0,0 +1,83 @@
+//! JUnit XML, the test report format most CI systems can show.
+
+use std::fmt::Write;
+use std::time::Duration;
+
+pub struct TestCase {
+    pub name: String,
+    pub time: Duration,
+    pub result: TestResult,
+}
+
+pub enum TestResult {
+    Passed,
+    /// The test ran and something was not as expected.
+    Failed(String),
+    /// The test could not finish, e.g. the ROM crashed.
+    Error(String),
+}
+
+/// The report of one suite.
+pub fn report(suite: &str, cases: &[TestCase]) -> String {
+    let count = |error: bool| {
+        cases
+            .iter()
+            .filter(|case| match case.result {
+                TestResult::Passed => false,
+                TestResult::Failed(_) => !error,
+                TestResult::Error(_) => error,
+            })
+            .count()
+    };
+    let time: Duration = cases.iter().map(|case| case.time).sum();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+    let _ = writeln!(
+        xml,
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">",
+        escape(suite),
+        cases.len(),
+        count(false),
+        count(true),
+        time.as_secs_f64()
+    );
+    for case in cases {
+        let _ = write!(
+            xml,
+            "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+            escape(&case.name),
+            escape(suite),
+            case.time.as_secs_f64()
+        );
+        let _ = match &case.result {
+            TestResult::Passed => writeln!(xml, "/>"),
+            TestResult::Failed(message) => writeln!(
+                xml,
+                ">\n      <failure message=\"{}\"/>\n    </testcase>",
+                escape(message)
+            ),
+            TestResult::Error(message) => writeln!(
+                xml,
+                ">\n      <error message=\"{}\"/>\n    </testcase>",
+                escape(message)
+            ),
+        };
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
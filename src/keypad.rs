@@ -0,0 +1,35 @@
+/// Maps host keys to the 16 key hex keypad.
+///
+/// The default puts the COSMAC VIP layout on the left of a QWERTY keyboard:
+///
+/// ```text
+/// 1 2 3 C      1 2 3 4
+/// 4 5 6 D  ->  q w e r
+/// 7 8 9 E      a s d f
+/// A 0 B F      z x c v
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    /// Host key for each keypad key 0-F.
+    pub keys: [char; 16],
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap {
+            keys: [
+                'x', '1', '2', '3', 'q', 'w', 'e', 'a', 's', 'd', 'z', 'c', '4', 'r', 'f', 'v',
+            ],
+        }
+    }
+}
+
+impl Keymap {
+    pub fn key_for(&self, host: char) -> Option<u8> {
+        let host = host.to_ascii_lowercase();
+        self.keys
+            .iter()
+            .position(|&key| key == host)
+            .map(|key| key as u8)
+    }
+}
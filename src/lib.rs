@@ -0,0 +1,100 @@
+//! A CHIP-8 emulator. The core, `cpu` and what it is made of, builds without
+//! the standard library when the default `std` feature is off, see `host`;
+//! everything else needs it.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod archive;
+#[cfg(feature = "std")]
+pub mod asm;
+pub mod assertion;
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod bisect;
+#[cfg(feature = "compress")]
+pub mod compress;
+#[cfg(feature = "std")]
+pub mod config;
+pub mod cpu;
+#[cfg(feature = "std")]
+pub mod crash;
+#[cfg(feature = "std")]
+pub mod database;
+#[cfg(feature = "devices")]
+pub mod device;
+#[cfg(feature = "std")]
+pub mod disasm;
+pub mod display;
+pub mod emulator;
+pub mod error;
+pub mod font;
+#[cfg(feature = "std")]
+pub mod frontend;
+#[cfg(feature = "std")]
+pub mod fuzz;
+#[cfg(feature = "std")]
+pub mod gamepad;
+#[cfg(feature = "std")]
+pub mod gif;
+#[cfg(feature = "std")]
+pub mod headless;
+pub mod host;
+#[cfg(feature = "std")]
+pub mod image;
+#[cfg(feature = "std")]
+pub mod input_view;
+pub mod instruction;
+#[cfg(feature = "std")]
+pub mod json;
+#[cfg(feature = "std")]
+pub mod junit;
+pub mod keypad;
+#[cfg(feature = "std")]
+pub mod lint;
+pub mod log;
+pub mod memory;
+#[cfg(feature = "net")]
+pub mod netplay;
+pub mod overrides;
+pub mod platform;
+#[cfg(feature = "std")]
+pub mod pool;
+#[cfg(feature = "alloc")]
+pub mod profile;
+#[cfg(feature = "alloc")]
+pub mod provenance;
+pub mod quirks;
+#[cfg(feature = "remote-debug")]
+pub mod remote;
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "std")]
+pub mod rewind;
+pub mod rng;
+#[cfg(feature = "std")]
+pub mod savestate;
+#[cfg(feature = "std")]
+pub mod scenario;
+#[cfg(feature = "scripting")]
+pub mod script;
+#[cfg(feature = "alloc")]
+pub mod self_modify;
+#[cfg(feature = "std")]
+pub mod session_log;
+#[cfg(feature = "std")]
+pub mod sha1;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod template;
+#[cfg(feature = "std")]
+pub mod testing;
+#[cfg(feature = "std")]
+pub mod time_limit;
+#[cfg(feature = "std")]
+pub mod watch;
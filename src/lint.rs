@@ -0,0 +1,150 @@
+//! Checks and a summary of what a ROM does, based on `disasm::trace`.
+
+use std::fmt;
+
+use crate::disasm;
+use crate::instruction::Instruction;
+use crate::memory::{MEMORY_SIZE, PROGRAM_START};
+use crate::platform::Platform;
+use crate::replay::rom_hash;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Issue {
+    /// A reachable opcode that is not an instruction.
+    UnknownOpcode { opcode: u16 },
+    /// Execution falls past the last byte of the ROM.
+    RunsOffEnd,
+    /// The ROM does not fit in memory.
+    TooLarge { size: usize },
+    /// An instruction of a later platform, which this emulator does not run.
+    LaterPlatform { opcode: u16, platform: Platform },
+    /// A jump or call leaving the ROM, e.g. into the font area.
+    TargetOutsideRom { target: usize },
+    /// A jump or call to an odd address, fine on the VIP but often a typo.
+    UnalignedTarget { target: usize },
+    /// 0nnn other than 0000: a machine code routine, which is ignored.
+    MachineCode { routine: u16 },
+    /// Bnnn: the target depends on V0, so tracing stops here.
+    ComputedJump,
+}
+
+impl Issue {
+    /// Errors break the ROM on every interpreter, the rest may be intended.
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            Issue::UnknownOpcode { .. } | Issue::RunsOffEnd | Issue::TooLarge { .. }
+        )
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::UnknownOpcode { opcode } => write!(f, "unknown opcode {:04x}", opcode),
+            Issue::RunsOffEnd => write!(f, "execution runs past the end of the ROM"),
+            Issue::TooLarge { size } => write!(f, "{} bytes do not fit in memory", size),
+            Issue::LaterPlatform { opcode, platform } => {
+                write!(f, "{} instruction {:04x}", platform, opcode)
+            }
+            Issue::TargetOutsideRom { target } => {
+                write!(f, "jump to {:#05x}, outside the ROM", target)
+            }
+            Issue::UnalignedTarget { target } => write!(f, "jump to odd address {:#05x}", target),
+            Issue::MachineCode { routine } => {
+                write!(f, "machine code routine {:#05x} is ignored", routine)
+            }
+            Issue::ComputedJump => write!(f, "computed jump, code after it is not checked"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Finding {
+    pub addr: usize,
+    pub issue: Issue,
+}
+
+/// Everything worth pointing out about a ROM.
+pub fn lint(rom: &[u8]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    if rom.len() > MEMORY_SIZE - PROGRAM_START {
+        findings.push(Finding {
+            addr: PROGRAM_START,
+            issue: Issue::TooLarge { size: rom.len() },
+        });
+    }
+    findings.extend(disasm::trace(rom).findings);
+    findings
+}
+
+/// A one line summary of a ROM, for looking over collections.
+pub struct RomInfo {
+    pub size: usize,
+    pub hash: u64,
+    pub instructions: usize,
+    /// Quirks the ROM's behaviour depends on, by their config names.
+    pub quirks: Vec<&'static str>,
+    /// The latest platform whose instructions are used.
+    pub platform: Platform,
+    pub errors: usize,
+}
+
+pub fn info(rom: &[u8]) -> RomInfo {
+    let trace = disasm::trace(rom);
+    let mut quirks = Vec::new();
+    let mut platform = Platform::Chip8;
+
+    for &addr in &trace.code {
+        let offset = addr - PROGRAM_START;
+        let opcode = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+        if let Some(later) = Platform::introducing(opcode) {
+            platform = platform.max(later);
+        }
+        let quirk = match Instruction::decode(opcode) {
+            Ok(Instruction::ShrXy { .. } | Instruction::ShlXy { .. }) => "shift_uses_vy",
+            Ok(Instruction::Store { .. } | Instruction::Load { .. }) => "load_store_increments_i",
+            Ok(Instruction::JumpV0 { .. }) => "jump_uses_vx",
+            Ok(
+                Instruction::OrXy { .. } | Instruction::AndXy { .. } | Instruction::XorXy { .. },
+            ) => "logic_resets_vf",
+            Ok(Instruction::Draw { .. }) => "wrap_sprites",
+            _ => continue,
+        };
+        if !quirks.contains(&quirk) {
+            quirks.push(quirk);
+        }
+    }
+
+    RomInfo {
+        size: rom.len(),
+        hash: rom_hash(rom),
+        instructions: trace.code.len(),
+        quirks,
+        platform,
+        errors: lint(rom)
+            .iter()
+            .filter(|finding| finding.issue.is_error())
+            .count(),
+    }
+}
+
+impl fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes, {} instructions, hash {:016x}",
+            self.size, self.instructions, self.hash
+        )?;
+        if !self.quirks.is_empty() {
+            write!(f, ", quirks: {}", self.quirks.join(" "))?;
+        }
+        if self.platform > Platform::Chip8 {
+            write!(f, ", needs {}", self.platform)?;
+        }
+        if self.errors > 0 {
+            write!(f, ", {} errors", self.errors)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,267 @@
+//! Diagnostics in the style of `tracing`, without the dependency: leveled
+//! events with a target, inside spans, filtered like `RUST_LOG`.
+//!
+//! ```text
+//! RUST_LOG=chip8::cpu=trace            every instruction
+//! RUST_LOG=info,chip8::keypad=debug    key presses, and anything at info
+//! ```
+//!
+//! A directive is `target=level` or just a level for everything else; the
+//! longest matching target wins. Targets are `chip8::cpu` (instructions at
+//! trace, emulation errors at error), `chip8::timers` (ticks, trace) and
+//! `chip8::keypad` (key changes, debug). Events go to stderr unless an
+//! embedding application installs its own sink with `set_sink`.
+//!
+//! Nothing is formatted unless it passes the filter, so leaving logging off
+//! costs an atomic load per event. Without std there is nowhere to log to,
+//! and the events compile down to nothing.
+
+use core::fmt;
+#[cfg(feature = "std")]
+use std::cell::RefCell;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicU8, Ordering};
+#[cfg(feature = "std")]
+use std::sync::RwLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    /// In any case.
+    pub fn parse(name: &str) -> Option<Level> {
+        [
+            Level::Error,
+            Level::Warn,
+            Level::Info,
+            Level::Debug,
+            Level::Trace,
+        ]
+        .into_iter()
+        .find(|level| name.eq_ignore_ascii_case(level.name()))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+/// Which events get through: a level per target, and one for the rest.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    default: Option<Level>,
+    targets: Vec<(String, Option<Level>)>,
+}
+
+#[cfg(feature = "std")]
+impl Filter {
+    /// `RUST_LOG` syntax: comma separated `target=level` or `level`. `off`
+    /// turns a target off.
+    pub fn parse(text: &str) -> Result<Filter, String> {
+        let mut filter = Filter::default();
+        let level = |name: &str| match name {
+            "off" => Ok(None),
+            _ => Level::parse(name)
+                .map(Some)
+                .ok_or_else(|| format!("unknown log level {}", name)),
+        };
+        for directive in text.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((target, name)) => filter.targets.push((target.to_string(), level(name)?)),
+                None => match level(directive) {
+                    Ok(default) => filter.default = default,
+                    // a bare target means everything in it
+                    Err(_) => filter
+                        .targets
+                        .push((directive.to_string(), Some(Level::Trace))),
+                },
+            }
+        }
+        Ok(filter)
+    }
+
+    pub fn level_for(&self, target: &str) -> Option<Level> {
+        self.targets
+            .iter()
+            .filter(|(prefix, _)| {
+                target == prefix
+                    || target
+                        .strip_prefix(prefix.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    /// The most verbose level anything is let through at.
+    fn max(&self) -> Option<Level> {
+        self.targets
+            .iter()
+            .map(|&(_, level)| level)
+            .chain([self.default])
+            .max()
+            .flatten()
+    }
+}
+
+/// An event, for sinks.
+#[cfg(feature = "std")]
+pub struct Record<'a> {
+    pub level: Level,
+    pub target: &'a str,
+    /// The spans it happened in, outermost first, e.g. `frame{n=12}`.
+    pub spans: &'a [String],
+    pub message: fmt::Arguments<'a>,
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for Record<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>5} ", self.level)?;
+        for span in self.spans {
+            write!(f, "{}: ", span)?;
+        }
+        write!(f, "{}: {}", self.target, self.message)
+    }
+}
+
+#[cfg(feature = "std")]
+type Sink = Box<dyn Fn(&Record) + Send + Sync>;
+
+/// The filter's `max` as a number, 0 for off, checked before anything else.
+#[cfg(feature = "std")]
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(0);
+#[cfg(feature = "std")]
+static FILTER: RwLock<Option<Filter>> = RwLock::new(None);
+#[cfg(feature = "std")]
+static SINK: RwLock<Option<Sink>> = RwLock::new(None);
+
+#[cfg(feature = "std")]
+thread_local! {
+    static SPANS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Starts letting events through `filter`.
+#[cfg(feature = "std")]
+pub fn init(filter: Filter) {
+    MAX_LEVEL.store(
+        filter.max().map_or(0, |level| level as u8),
+        Ordering::Relaxed,
+    );
+    *FILTER.write().unwrap_or_else(|err| err.into_inner()) = Some(filter);
+}
+
+/// Sets up the filter from `RUST_LOG`, if it is set.
+#[cfg(feature = "std")]
+pub fn init_from_env() -> Result<(), String> {
+    match std::env::var("RUST_LOG") {
+        Ok(text) => Filter::parse(&text).map(init),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Sends events to `sink` instead of stderr.
+#[cfg(feature = "std")]
+pub fn set_sink(sink: impl Fn(&Record) + Send + Sync + 'static) {
+    *SINK.write().unwrap_or_else(|err| err.into_inner()) = Some(Box::new(sink));
+}
+
+#[cfg(feature = "std")]
+pub fn enabled(level: Level, target: &str) -> bool {
+    if level as u8 > MAX_LEVEL.load(Ordering::Relaxed) {
+        return false;
+    }
+    FILTER
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .as_ref()
+        .and_then(|filter| filter.level_for(target))
+        .is_some_and(|max| level <= max)
+}
+
+#[cfg(not(feature = "std"))]
+pub fn enabled(_level: Level, _target: &str) -> bool {
+    false
+}
+
+/// Sends an event to the sink; `event!` checks the filter first.
+#[cfg(feature = "std")]
+pub fn log(level: Level, target: &str, message: fmt::Arguments) {
+    SPANS.with(|spans| {
+        let spans = spans.borrow();
+        let record = Record {
+            level,
+            target,
+            spans: &spans,
+            message,
+        };
+        match &*SINK.read().unwrap_or_else(|err| err.into_inner()) {
+            Some(sink) => sink(&record),
+            None => eprintln!("{}", record),
+        }
+    });
+}
+
+#[cfg(not(feature = "std"))]
+pub fn log(_level: Level, _target: &str, _message: fmt::Arguments) {}
+
+/// Logs `format!` style arguments at a level, for a target.
+macro_rules! event {
+    ($level:expr, $target:expr, $($arg:tt)+) => {
+        if $crate::log::enabled($level, $target) {
+            $crate::log::log($level, $target, format_args!($($arg)+));
+        }
+    };
+}
+pub(crate) use event;
+
+/// Entered with `span`, left when dropped.
+pub struct Span {
+    #[cfg(feature = "std")]
+    entered: bool,
+}
+
+/// Enters a span for the events that follow on this thread, `name{fields}`.
+/// `fields` is only formatted when logging is on.
+#[cfg(feature = "std")]
+pub fn span(name: &str, fields: fmt::Arguments) -> Span {
+    let entered = MAX_LEVEL.load(Ordering::Relaxed) > 0;
+    if entered {
+        let span = format!("{}{{{}}}", name, fields);
+        SPANS.with(|spans| spans.borrow_mut().push(span));
+    }
+    Span { entered }
+}
+
+#[cfg(not(feature = "std"))]
+pub fn span(_name: &str, _fields: fmt::Arguments) -> Span {
+    Span {}
+}
+
+#[cfg(feature = "std")]
+impl Drop for Span {
+    fn drop(&mut self) {
+        if self.entered {
+            SPANS.with(|spans| spans.borrow_mut().pop());
+        }
+    }
+}
@@ -1,190 +1,2766 @@
-struct CPU {
-    registers: [u8; 16],
-    program_counter: usize, // position in memory
-    memory: [u8; 0x1000],
-    stack: [u16; 16],
-    stack_pointer: usize,
-}
-
-/// vx and vy are registers (0-F)
-/// kk is a number between 0 and 255.
-/// addr is an address between 0 and 4095.
-impl CPU {
-    fn run(&mut self) {
+use std::env;
+use std::fs;
+use std::io::{BufWriter, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chip_8_emulate::archive::{self, Index};
+use chip_8_emulate::asm;
+use chip_8_emulate::batch;
+use chip_8_emulate::bisect;
+use chip_8_emulate::config::{self, Config, FastForwardTimers};
+use chip_8_emulate::cpu::{Cpu, Engine, OddPc, OnExit, PcOverflow};
+use chip_8_emulate::crash::{self, Bundle, CrashRecorder};
+use chip_8_emulate::database::{self, Metadata};
+#[cfg(feature = "devices")]
+use chip_8_emulate::device;
+use chip_8_emulate::disasm;
+#[cfg(feature = "net")]
+use chip_8_emulate::display::Display;
+use chip_8_emulate::error::Error;
+use chip_8_emulate::font::{self, Font};
+use chip_8_emulate::frontend::{self, Browser, Event, Frontend, Panel, PixelStyle, Rgb};
+use chip_8_emulate::fuzz;
+use chip_8_emulate::gamepad::{self, Gamepads};
+use chip_8_emulate::gif::GifWriter;
+use chip_8_emulate::headless::{self, ExitStatus, Outcome};
+use chip_8_emulate::image;
+use chip_8_emulate::input_view::InputView;
+use chip_8_emulate::instruction::Instruction;
+use chip_8_emulate::junit::{self, TestCase, TestResult};
+use chip_8_emulate::lint;
+use chip_8_emulate::log;
+use chip_8_emulate::memory::{MAILBOX_ADDR, MEMORY_SIZE, PROGRAM_START};
+#[cfg(feature = "net")]
+use chip_8_emulate::netplay::{self, Guest, Host, Notice};
+use chip_8_emulate::overrides::Overrides;
+use chip_8_emulate::platform::{self, Platform};
+use chip_8_emulate::quirks::{self, Quirks};
+#[cfg(feature = "remote-debug")]
+use chip_8_emulate::remote::RemoteDebugger;
+use chip_8_emulate::replay::{self, Player, Recorder, Replay};
+use chip_8_emulate::rewind::Rewind;
+use chip_8_emulate::rng::Rng;
+use chip_8_emulate::savestate::{self, State};
+use chip_8_emulate::scenario::Scenario;
+#[cfg(feature = "scripting")]
+use chip_8_emulate::script::Script;
+use chip_8_emulate::session_log::{self, SessionLog};
+use chip_8_emulate::sha1;
+use chip_8_emulate::stats::{self, Stats};
+use chip_8_emulate::template;
+use chip_8_emulate::time_limit::{self, TimeLimit};
+use chip_8_emulate::watch::Watcher;
+
+const USAGE: &str = "usage:
+    chip8 [run] [--dir <dir>]      pick a ROM from the ROM directory
+    chip8 run <rom> [--frontend terminal] [--time-limit 15m] [--check] [display options] [machine options]
+        keypad: 1234/qwer/asdf/zxcv by default, Esc quits, F5/F9 save/load state,
+        F6 pauses, F7 resets, F8 also clears memory (power cycle), F12 saves a screenshot,
+        F2 fast-forwards, F3 switches engines, Backspace rewinds a second, Tab shows registers and memory (Page Up/Down scroll),
+        dropping a ROM file onto the terminal loads it; playing needs the terminal feature
+        --record <file>        save every key press to replay the session later
+        --record <file.gif>    or save what the screen shows, as an animated GIF
+        --replay <file>        play a recording back, then hand over to the keyboard
+        --remote-debug <port>  let a GDB remote protocol debugger attach on localhost
+        --script <file>        run a script's handlers along with the ROM, for cheats
+                               and automation (needs the scripting feature)
+        --break-on-collision   pause right after every Dxyn that sets vf, with the
+                               screen before and after it in the debug panel
+        --host <addr:port>     let a second player join over the network, e.g. 0.0.0.0:7000
+                               (needs the net feature)
+        --join <host:port>     be the second player of a game hosted with --host
+        --input-delay N        frames before key presses take effect when playing over
+                               the network, for both players (default 3)
+        --input-view <file|port>
+                               the keys of every frame for stream overlays: the last
+                               frame's in a file, or all of them over TCP on localhost
+                               (a port needs the net feature)
+        --fast-forward-timers emulated|wall
+                               whether the timers speed up with fast-forward (emulated,
+                               the default) or keep to the clock so sounds don't shorten
+        --session-log <file>   append what happens (ROMs loaded, saves, errors) as JSON
+                               lines, to see how a kiosk or a study was played
+    chip8 test <rom> [--frames N] [--expect HASH] [--until-halt] [--replay <file>] [--state <file>] [--differential] [--script <file>] [machine options]
+    chip8 test --manifest <file> [machine options]
+    chip8 test <project dir> [--junit <file>] [machine options]
+        assemble src/main.asm and play every tests/*.scenario against it
+    chip8 bench <rom> [--instructions N] [machine options]
+    chip8 bisect <rom> <replay> [machine options]
+                               find the second in which a replay stops matching its
+                               recording, and save the machine on either side of it
+    chip8 repro <bundle> <rom> [--from-start]
+                               play a crash bundle saved by chip8 run back to its crash,
+                               from the snapshot before it or from the start
+    chip8 fuzz [--runs N] [--steps N] [--seed N] [--save <file>]
+                               run random programs on both engines and a reference
+                               interpreter, stopping at the first disagreement
+    chip8 asm <source> [--out <rom>] [--watch [--run [run options]]]
+        --watch                assemble again whenever the source is saved
+        --run                  run the ROM and reload it on every change
+    chip8 new <name>               start a homebrew project in a new directory
+    chip8 fetch <title> [--index <url|file>] [--dir <dir>]
+                               download a ROM from the community archive
+    chip8 disasm <rom|dir>... [--out <dir>]
+    chip8 lint <rom|dir>...
+    chip8 info <rom|dir>...
+    chip8 config init [--config <file>] [--force]
+    chip8 demo
+    chip8 --list-gamepads
+
+display options:
+    --fg #rrggbb               foreground colour
+    --bg #rrggbb               background colour
+    --scale N                  integer scaling factor, 1-16
+    --ghosting                 let pixels fade out like phosphor, reduces flicker
+
+machine options:
+    --config <file>            settings file, default ./chip8.toml or ~/.config/chip8/chip8.toml
+    --speed N                  instructions per frame
+    --seed N                   seed for the random number generator (Cxkk)
+    --platform vip|chip48|schip|xochip|eti660|modern
+                               the machine the ROM was written for: sets the quirks,
+                               speed, font and colours, and which instructions the ROM
+                               may use (not all of SUPER-CHIP's and XO-CHIP's run)
+    --quirks chip8|chip48|schip|xochip|modern
+                               quirks profile, overriding the platform's
+    --font chip48|vip|eti660|dream6800|octo
+                               digit sprites, overriding the platform's
+    --debug-mailbox            print bytes written to 0x1FF to stderr
+    --pc-overflow error|wrap   what to do when the program counter runs off memory
+    --odd-pc allow|warn|trap   what to do when code runs from an odd address
+    --on-exit halt|reset|menu  what SUPER-CHIP's exit (00FD) does: stop, start over,
+                               or go back to the ROM browser
+    --engine simple|cached     decode every step, or cache decoded instructions (faster)
+    --profile <file|->         count what runs and report the hot addresses, loops and
+                               instruction mix at exit, as JSON for a .json file
+    --self-modify              warn about code the ROM wrote itself before running it,
+                               with a summary of what wrote where at exit
+    --provenance               remember which Dxyn drew each pixel, for the
+                               chip8::display trace log (always on when playing)
+    --device console@ADDR|clock@ADDR
+                               map an experimental pseudo-device at a hex address, for
+                               homebrew only (needs the devices feature, repeatable)";
+
+const DEFAULT_TEST_FRAMES: usize = 600;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(err) = log::init_from_env() {
+        eprintln!("warning: RUST_LOG: {}", err);
+    }
+
+    let result = match args.first().map(String::as_str) {
+        None => browse(&[]),
+        Some("--dir") => browse(&args),
+        Some("run") => run(&args[1..]),
+        Some("test") => test(&args[1..]),
+        Some("bench") => bench(&args[1..]),
+        Some("bisect") => bisect_command(&args[1..]),
+        Some("repro") => repro_command(&args[1..]),
+        Some("fuzz") => fuzz_command(&args[1..]),
+        Some("asm") => asm_command(&args[1..]),
+        Some("new") => new_project(&args[1..]),
+        Some("fetch") => fetch(&args[1..]),
+        Some("disasm") => disasm_command(&args[1..]),
+        Some("lint") => lint_command(&args[1..]),
+        Some("info") => info_command(&args[1..]),
+        Some("config") => config_command(&args[1..]),
+        Some("demo") => demo(),
+        Some("--list-gamepads") => list_gamepads(),
+        _ => Err(USAGE.to_string()),
+    };
+
+    match result {
+        Ok(status) => process::exit(status.code()),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(ExitStatus::Usage.code());
+        }
+    }
+}
+
+/// Looks up the value following `flag`.
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Result<Option<&'a str>, String> {
+    match args.iter().position(|arg| arg == flag) {
+        Some(i) => args
+            .get(i + 1)
+            .map(|value| Some(value.as_str()))
+            .ok_or_else(|| format!("{} needs a value", flag)),
+        None => Ok(None),
+    }
+}
+
+/// Loads the config file named by --config, or the default one, with what the
+/// ROM database knows about the ROM and then the --platform preset on top.
+fn load_config(
+    args: &[String],
+    metadata: Option<&Metadata>,
+) -> Result<(Config, Option<PathBuf>), String> {
+    let path = flag_value(args, "--config")?.map(Path::new);
+    let (mut config, path) = Config::resolve(path).map_err(|err| err.to_string())?;
+    if let Some(metadata) = metadata {
+        metadata.apply(&mut config);
+    }
+    if let Some(name) = flag_value(args, "--platform")? {
+        platform::preset(name)
+            .ok_or_else(|| {
+                format!(
+                    "unknown platform {}, expected one of: {}",
+                    name,
+                    platform::preset_names()
+                )
+            })?
+            .apply(&mut config);
+    }
+    Ok((config, path))
+}
+
+/// Flags shared by run and test that configure the machine itself, falling
+/// back to the config from `load_config`.
+#[derive(Clone)]
+struct MachineOptions {
+    speed: usize,
+    platform: Platform,
+    quirks: Quirks,
+    font: &'static Font,
+    debug_mailbox: bool,
+    pc_overflow: PcOverflow,
+    odd_pc: OddPc,
+    on_exit: OnExit,
+    seed: Option<u64>,
+    engine: Engine,
+    /// Where to write the report of `Cpu::profile`, `-` for stderr.
+    profile: Option<String>,
+    /// Set `Cpu::self_modify` and report what it found.
+    self_modify: bool,
+    /// Set `Cpu::provenance`.
+    provenance: bool,
+    overrides: Overrides,
+    /// Pseudo-devices to map, from `--device`.
+    #[cfg(feature = "devices")]
+    devices: Vec<device::Spec>,
+}
+
+impl MachineOptions {
+    fn parse(args: &[String], config: &Config) -> Result<MachineOptions, String> {
+        let speed = match flag_value(args, "--speed")? {
+            Some(speed) => speed
+                .parse()
+                .ok()
+                .filter(|&speed| speed > 0)
+                .ok_or_else(|| format!("invalid speed: {}", speed))?,
+            None => config.speed,
+        };
+        let quirks = match flag_value(args, "--quirks")? {
+            Some(name) => Quirks::profile(name).ok_or_else(|| {
+                format!(
+                    "unknown quirks profile {}, expected one of: {}",
+                    name,
+                    quirks::PROFILES.join(", ")
+                )
+            })?,
+            None => config.quirks,
+        };
+        let font = match flag_value(args, "--font")? {
+            Some(name) => font::font(name).ok_or_else(|| {
+                format!(
+                    "unknown font {}, expected one of: {}",
+                    name,
+                    font::font_names()
+                )
+            })?,
+            None => config.font,
+        };
+        let pc_overflow = match flag_value(args, "--pc-overflow")? {
+            None => PcOverflow::Error,
+            Some(name) => {
+                parse_pc_overflow(name).ok_or_else(|| format!("invalid --pc-overflow: {}", name))?
+            }
+        };
+        let odd_pc = match flag_value(args, "--odd-pc")? {
+            None | Some("allow") => OddPc::Allow,
+            Some("warn") => OddPc::Warn,
+            Some("trap") => OddPc::Trap,
+            Some(other) => return Err(format!("invalid --odd-pc: {}", other)),
+        };
+        let on_exit = match flag_value(args, "--on-exit")? {
+            None => OnExit::Halt,
+            Some(name) => {
+                parse_on_exit(name).ok_or_else(|| format!("invalid --on-exit: {}", name))?
+            }
+        };
+        let engine = match flag_value(args, "--engine")? {
+            None | Some("simple") => Engine::Simple,
+            Some("cached") => Engine::Cached,
+            Some(other) => return Err(format!("invalid --engine: {}", other)),
+        };
+        let seed = flag_value(args, "--seed")?
+            .map(|seed| seed.parse().map_err(|_| format!("invalid seed: {}", seed)))
+            .transpose()?;
+        let devices: Vec<&str> = args
+            .windows(2)
+            .filter(|pair| pair[0] == "--device")
+            .map(|pair| pair[1].as_str())
+            .collect();
+        if !devices.is_empty() && !cfg!(feature = "devices") {
+            return Err("--device needs a build with the devices feature".to_string());
+        }
+        #[cfg(feature = "devices")]
+        let devices = devices
+            .into_iter()
+            .map(device::Spec::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        #[cfg(feature = "devices")]
+        if !devices.is_empty() {
+            eprintln!("warning: experimental devices mapped, ROMs that use them run nowhere else");
+        }
+
+        Ok(MachineOptions {
+            speed,
+            platform: config.preset.platform,
+            quirks,
+            font,
+            debug_mailbox: args.iter().any(|arg| arg == "--debug-mailbox"),
+            pc_overflow,
+            odd_pc,
+            on_exit,
+            seed,
+            engine,
+            profile: flag_value(args, "--profile")?.map(str::to_string),
+            self_modify: args.iter().any(|arg| arg == "--self-modify"),
+            provenance: args.iter().any(|arg| arg == "--provenance"),
+            overrides: config.opcodes,
+            #[cfg(feature = "devices")]
+            devices,
+        })
+    }
+
+    fn apply(&self, cpu: &mut Cpu) {
+        if self.debug_mailbox {
+            cpu.memory.mailbox = Some(MAILBOX_ADDR);
+        }
+        #[cfg(feature = "devices")]
+        {
+            cpu.memory.unmap_all();
+            for spec in &self.devices {
+                cpu.memory
+                    .map(spec.addr, spec.create())
+                    .expect("checked when parsed");
+            }
+        }
+        cpu.pc_overflow = self.pc_overflow;
+        cpu.odd_pc = self.odd_pc;
+        cpu.on_exit = self.on_exit;
+        cpu.engine = self.engine;
+        cpu.overrides = self.overrides;
+        if self.profile.is_some() {
+            cpu.profile = Some(Box::default());
+        }
+        if self.self_modify {
+            cpu.self_modify = Some(Box::default());
+        }
+        if self.provenance {
+            cpu.provenance = Some(Box::default());
+        }
+        if let Some(seed) = self.seed {
+            cpu.rng = Rng::new(seed);
+        }
+        cpu.speed = self.speed;
+        cpu.quirks = self.quirks;
+        cpu.load_font(self.font);
+    }
+}
+
+struct RunOptions {
+    rom: String,
+    /// The command line after the ROM, kept in crash bundles.
+    args: Vec<String>,
+    config: Config,
+    config_path: Option<PathBuf>,
+    frontend: String,
+    style: PixelStyle,
+    time_limit: Option<Duration>,
+    machine: MachineOptions,
+    check: bool,
+    /// Pause after every Dxyn that collides, see `Cpu::break_on_collision`.
+    break_on_collision: bool,
+    record: Option<String>,
+    /// `--record` with a `.gif` file: a video of the session rather than its
+    /// key presses.
+    gif: Option<String>,
+    replay: Option<String>,
+    remote_debug: Option<u16>,
+    script: Option<PathBuf>,
+    /// Where to wait for a second player, or the game to join as one.
+    host: Option<String>,
+    join: Option<String>,
+    #[cfg(feature = "net")]
+    input_delay: u32,
+    /// Where to send the keys of every frame, see `input_view`.
+    input_view: Option<String>,
+    session_log: Option<PathBuf>,
+    /// Assembly source to reassemble into `rom` and reload when it changes.
+    watch: Option<PathBuf>,
+    /// Started from the ROM browser, which a halted ROM goes back to.
+    from_browser: bool,
+    /// What the ROM database knows about the ROM.
+    metadata: Option<Metadata>,
+}
+
+impl RunOptions {
+    fn parse(args: &[String]) -> Result<RunOptions, String> {
+        let rom = args
+            .first()
+            .filter(|arg| !arg.starts_with("--"))
+            .ok_or_else(|| USAGE.to_string())?;
+        // an unreadable ROM is reported when it is loaded
+        let metadata = match fs::read(rom) {
+            Ok(bytes) => {
+                database::lookup(Path::new(rom), &bytes, database::default_path().as_deref())?
+            }
+            Err(_) => None,
+        };
+        let (mut config, config_path) = load_config(args, metadata.as_ref())?;
+        if let Some(timers) = flag_value(args, "--fast-forward-timers")? {
+            config.fast_forward_timers = FastForwardTimers::parse(timers)
+                .ok_or_else(|| format!("invalid fast-forward timers: {}", timers))?;
+        }
+        let frontend = flag_value(args, "--frontend")?.unwrap_or(&config.frontend);
+        let remote_debug = flag_value(args, "--remote-debug")?
+            .map(|port| port.parse().map_err(|_| format!("invalid port: {}", port)))
+            .transpose()?;
+        let time_limit = flag_value(args, "--time-limit")?
+            .map(|limit| {
+                time_limit::parse_duration(limit)
+                    .ok_or_else(|| format!("invalid time limit: {}", limit))
+            })
+            .transpose()?;
+        #[cfg(feature = "net")]
+        let input_delay = match flag_value(args, "--input-delay")? {
+            Some(delay) => delay
+                .parse()
+                .ok()
+                .filter(|&delay| delay <= 60)
+                .ok_or_else(|| format!("invalid input delay: {}", delay))?,
+            None => netplay::DEFAULT_INPUT_DELAY,
+        };
+        let record = flag_value(args, "--record")?.map(str::to_string);
+        let (gif, record) = match record {
+            Some(path) if path.ends_with(".gif") => (Some(path), None),
+            record => (None, record),
+        };
+
+        Ok(RunOptions {
+            rom: rom.clone(),
+            args: args[1..].to_vec(),
+            frontend: frontend.to_string(),
+            style: parse_style(args, &config)?,
+            time_limit,
+            machine: MachineOptions::parse(args, &config)?,
+            config,
+            config_path,
+            check: args.iter().any(|arg| arg == "--check"),
+            break_on_collision: args.iter().any(|arg| arg == "--break-on-collision"),
+            record,
+            gif,
+            replay: flag_value(args, "--replay")?.map(str::to_string),
+            remote_debug,
+            script: flag_value(args, "--script")?.map(PathBuf::from),
+            host: flag_value(args, "--host")?.map(str::to_string),
+            join: flag_value(args, "--join")?.map(str::to_string),
+            #[cfg(feature = "net")]
+            input_delay,
+            input_view: flag_value(args, "--input-view")?.map(str::to_string),
+            session_log: flag_value(args, "--session-log")?.map(PathBuf::from),
+            watch: None,
+            from_browser: false,
+            metadata,
+        })
+    }
+}
+
+fn parse_on_exit(name: &str) -> Option<OnExit> {
+    match name {
+        "halt" => Some(OnExit::Halt),
+        "reset" => Some(OnExit::Reset),
+        "menu" => Some(OnExit::Menu),
+        _ => None,
+    }
+}
+
+fn parse_pc_overflow(name: &str) -> Option<PcOverflow> {
+    match name {
+        "error" => Some(PcOverflow::Error),
+        "wrap" => Some(PcOverflow::Wrap),
+        _ => None,
+    }
+}
+
+/// The display flags, falling back to the config file.
+fn parse_style(args: &[String], config: &Config) -> Result<PixelStyle, String> {
+    let mut style = config.style;
+    let color = |flag: &str| -> Result<Option<Rgb>, String> {
+        flag_value(args, flag)?
+            .map(|text| Rgb::parse(text).ok_or_else(|| format!("invalid {}: {}", flag, text)))
+            .transpose()
+    };
+
+    if let Some(foreground) = color("--fg")? {
+        style.foreground = foreground;
+    }
+    if let Some(background) = color("--bg")? {
+        style.background = background;
+    }
+    if let Some(scale) = flag_value(args, "--scale")? {
+        style.scale = scale
+            .parse()
+            .ok()
+            .filter(|scale| (1..=16).contains(scale))
+            .ok_or_else(|| format!("invalid scale: {}", scale))?;
+    }
+    if args.iter().any(|arg| arg == "--ghosting") {
+        style.ghosting = true;
+    }
+
+    Ok(style)
+}
+
+fn load_frontend(name: &str, style: PixelStyle) -> Result<Box<dyn Frontend>, String> {
+    frontend::by_name(name, style).ok_or_else(|| {
+        if name == "terminal" {
+            // only when it was left out of the build
+            return "the terminal frontend needs a build with the terminal feature".to_string();
+        }
+        format!(
+            "unknown frontend {}, expected one of: {}",
+            name,
+            frontend::FRONTENDS.join(", ")
+        )
+    })
+}
+
+fn run(args: &[String]) -> Result<ExitStatus, String> {
+    if args.first().is_none_or(|arg| arg.starts_with("--")) {
+        return browse(args);
+    }
+    let mut args = args.to_vec();
+    loop {
+        match run_with(RunOptions::parse(&args)?)? {
+            Ended::Quit(status) => return Ok(status),
+            // everything is worked out again for the new ROM, from its
+            // database entry on
+            Ended::Load(rom) => args[0] = rom.to_string_lossy().into_owned(),
+            // the browser takes the same options, less the ROM
+            Ended::Menu => return browse(&args[1..]),
+        }
+    }
+}
+
+/// How a game stopped.
+enum Ended {
+    Quit(ExitStatus),
+    /// Another ROM was dropped onto the window.
+    Load(PathBuf),
+    /// The ROM exited with `--on-exit menu`.
+    Menu,
+}
+
+/// The ROM directory, from `--dir`, the config or the data directory.
+fn rom_dir(args: &[String], config: &Config) -> Result<PathBuf, String> {
+    match flag_value(args, "--dir")? {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => config
+            .rom_dir
+            .clone()
+            .or_else(|| stats::data_dir().map(|dir| dir.join("roms")))
+            .ok_or("no data directory, pass --dir".to_string()),
+    }
+}
+
+/// Lists the ROM directory to pick a ROM from, and comes back to the list when
+/// the game ends. `args` are run options for every game.
+fn browse(args: &[String]) -> Result<ExitStatus, String> {
+    if !std::io::stdin().is_terminal() {
+        return Err(USAGE.to_string());
+    }
+    let (config, _) = load_config(args, None)?;
+    let dir = rom_dir(args, &config)?;
+    let roms = batch::collect_roms(std::slice::from_ref(&dir))
+        .map_err(|err| format!("{}: {}", dir.display(), err))?;
+    if roms.is_empty() {
+        return Err(format!(
+            "no ROMs in {}, chip8 fetch <title> downloads some",
+            dir.display()
+        ));
+    }
+    let mut browser = Browser::new(dir, roms);
+    // --dir is the browser's
+    let args: Vec<String> = args
+        .iter()
+        .enumerate()
+        .filter(|&(i, arg)| arg != "--dir" && (i == 0 || args[i - 1] != "--dir"))
+        .map(|(_, arg)| arg.clone())
+        .collect();
+
+    loop {
+        let Some(mut rom) = pick_rom(&mut browser, &config)? else {
+            return Ok(ExitStatus::Ok);
+        };
+        browser.message = None;
         loop {
-            let p = self.program_counter;
-
-            let op_byte1 = self.memory[p] as u16;
-            let op_byte2 = self.memory[p + 1] as u16;
-            let opcode = (op_byte1 << 8) | op_byte2;
-
-            let x = ((opcode & 0x0F00) >> 8) as u8;
-            let y = ((opcode & 0x00F0) >> 4) as u8;
-
-            let kk = (opcode & 0x00FF) as u8;
-            let op_minor = (opcode & 0x000F) as u8;
-            let addr = opcode & 0x0FFF;
-
-            self.program_counter += 2; // 1 opcode = 2 u8
-
-            match opcode {
-                0x0000 => return,
-                0x00E0 => { /* CLRSCR */ }
-                0x00EE => self.ret(),
-                0x1000..=0x1FFF => self.jump(addr),
-                0x2000..=0x2FFF => self.call(addr),
-                0x3000..=0x3FFF => self.se_xkk(x, kk),
-                0x4000..=0x4FFF => self.sne(self.registers[x as usize], kk),
-                0x5000..=0x5FFF => self.se_xy(x, y),
-                0x6000..=0x6FFF => self.set(x, kk),
-                0x7000..=0x7FFF => self.add(x, kk),
-                0x8000..=0x8FFF => match op_minor {
-                    0 => {
-                        let vy = self.registers[y as usize];
-                        self.set(x, vy);
-                    }
-                    1 => self.or_xy(x, y),
-                    2 => self.and_xy(x, y),
-                    3 => self.xor_xy(x, y),
-                    4 => self.add_xy(x, y),
-                    _ => todo!("opcode: {:04x}", opcode),
-                },
-                _ => todo!("opcode {:04x}", opcode),
+            let mut run_args = vec![rom.to_string_lossy().into_owned()];
+            run_args.extend(args.iter().cloned());
+            let options = RunOptions::parse(&run_args).map(|options| RunOptions {
+                from_browser: true,
+                ..options
+            });
+            match options.and_then(run_with) {
+                Ok(Ended::Load(next)) => rom = next,
+                Ok(Ended::Quit(_) | Ended::Menu) => break,
+                Err(err) => {
+                    browser.message = Some(err);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Shows the browser until a ROM is picked or dropped, None if it was closed.
+fn pick_rom(browser: &mut Browser, config: &Config) -> Result<Option<PathBuf>, String> {
+    // again every time, the game that just ended counts
+    browser.stats = Stats::load_default().unwrap_or_default();
+    let mut frontend = load_frontend(&config.frontend, config.style)?;
+    frontend
+        .init()
+        .map_err(|err| format!("{}: {}", frontend.name(), err))?;
+    let io_err = |err: std::io::Error| err.to_string();
+
+    let mut redraw = true;
+    let picked = 'browse: loop {
+        if redraw {
+            frontend.screen(&browser.lines()).map_err(io_err)?;
+            redraw = false;
+        }
+        for event in frontend.poll_events() {
+            match event {
+                Event::Quit => break 'browse None,
+                Event::Confirm => break 'browse Some(browser.selected().to_path_buf()),
+                Event::Drop(path) => break 'browse Some(path),
+                Event::Select(entries) => browser.select_by(entries),
+                Event::ScrollPanel(pages) => browser.select_by(pages * 10),
+                _ => continue,
+            }
+            redraw = true;
+        }
+        thread::sleep(Duration::from_millis(16));
+    };
+    frontend
+        .teardown()
+        .map_err(|err| format!("{}: {}", frontend.name(), err))?;
+    Ok(picked)
+}
+
+fn run_with(options: RunOptions) -> Result<Ended, String> {
+    if options.remote_debug.is_some() && !cfg!(feature = "remote-debug") {
+        return Err("--remote-debug needs a build with the remote-debug feature".to_string());
+    }
+    if options.script.is_some() && !cfg!(feature = "scripting") {
+        return Err("--script needs a build with the scripting feature".to_string());
+    }
+    if options.script.is_some() && options.remote_debug.is_some() {
+        return Err("--script and --remote-debug can't be combined".to_string());
+    }
+    if (options.host.is_some() || options.join.is_some()) && !cfg!(feature = "net") {
+        return Err("--host and --join need a build with the net feature".to_string());
+    }
+    if options.host.is_some() && options.join.is_some() {
+        return Err("--host and --join can't be combined".to_string());
+    }
+    if options.host.is_some() && options.replay.is_some() {
+        return Err("--host and --replay can't be combined".to_string());
+    }
+    if options.check {
+        return check(&options).map(Ended::Quit);
+    }
+
+    let rom = fs::read(&options.rom).map_err(|err| format!("{}: {}", options.rom, err))?;
+    #[cfg(feature = "net")]
+    if let Some(addr) = &options.join {
+        return join(&options, &rom, addr).map(Ended::Quit);
+    }
+    let required = platform::required(&rom);
+    if let Some(problem) = required
+        .filter(|required| !required.met_by(options.machine.platform))
+        .and_then(|required| required.problem(options.machine.platform))
+    {
+        return Err(format!("{}: {}", options.rom, problem));
+    }
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&rom).map_err(|err| err.to_string())?;
+    options.machine.apply(&mut cpu);
+    cpu.break_on_collision = options.break_on_collision;
+    // for the debug panel to say what drew a pixel clicked
+    cpu.provenance = Some(Box::default());
+
+    if options.record.is_some() && options.replay.is_some() {
+        return Err("--record and --replay can't be combined".to_string());
+    }
+    let replay = options
+        .replay
+        .as_deref()
+        .map(|path| load_replay(Path::new(path), &rom))
+        .transpose()?;
+    if let Some(replay) = &replay {
+        replay.apply(&mut cpu);
+    }
+    if options.watch.is_some() && (options.record.is_some() || replay.is_some()) {
+        return Err("recordings can't be combined with reloading".to_string());
+    }
+    let mut recorder = options.record.as_ref().map(|_| {
+        let seed = options.machine.seed.unwrap_or_else(Rng::time_seed);
+        Recorder::start(&mut cpu, &rom, seed)
+    });
+
+    // a ROM reassembled on the fly is no longer the one the bundle names
+    let mut crash = options.watch.is_none().then(|| {
+        let name = Path::new(&options.rom)
+            .file_name()
+            .map_or(options.rom.clone(), |name| {
+                name.to_string_lossy().into_owned()
+            });
+        CrashRecorder::start(&cpu, &name, &rom, &options.args)
+    });
+
+    if let Some(metadata) = &options.metadata {
+        eprintln!("{}", metadata.describe());
+    }
+    let mut gif = match &options.gif {
+        Some(path) => {
+            let file = fs::File::create(path).map_err(|err| format!("{}: {}", path, err))?;
+            let first = cpu.screenshot(&options.style);
+            let gif = GifWriter::new(BufWriter::new(file), &first)
+                .map_err(|err| format!("{}: {}", path, err))?;
+            Some(gif)
+        }
+        None => None,
+    };
+    #[cfg(feature = "net")]
+    let mut host = match &options.host {
+        Some(addr) => Some(
+            Host::bind(addr, &rom, options.input_delay)
+                .map_err(|err| format!("{}: {}", addr, err))?,
+        ),
+        None => None,
+    };
+    let mut log = match &options.session_log {
+        Some(path) => {
+            Some(SessionLog::open(path).map_err(|err| format!("{}: {}", path.display(), err))?)
+        }
+        None => None,
+    };
+    if let Some(log) = &mut log {
+        log.rom_loaded(
+            &options.rom,
+            &rom,
+            options.machine.platform.name(),
+            options
+                .metadata
+                .as_ref()
+                .map(|metadata| metadata.title.as_str()),
+        );
+    }
+    let frontend = load_frontend(&options.frontend, options.style).and_then(|mut frontend| {
+        frontend
+            .init()
+            .map_err(|err| format!("{}: {}", frontend.name(), err))?;
+        Ok(frontend)
+    });
+    let mut frontend = match (frontend, &mut log) {
+        (Ok(frontend), _) => frontend,
+        // the ROM was logged as loaded, so the log says what became of it
+        (Err(err), Some(log)) => {
+            log.event("error", &[("message", session_log::text(err.as_str()))]);
+            log.event(
+                "rom_ended",
+                &[
+                    ("frames", session_log::number(0)),
+                    ("seconds", session_log::number(0)),
+                    ("how", session_log::text("error")),
+                ],
+            );
+            return Err(err);
+        }
+        (Err(err), None) => return Err(err),
+    };
+    if let Some(metadata) = &options.metadata {
+        frontend.set_title(&metadata.describe());
+    }
+
+    let started = Instant::now();
+    let attached = Attached {
+        replay: replay.as_ref(),
+        recorder: recorder.as_mut(),
+        gif: gif.as_mut(),
+        #[cfg(feature = "net")]
+        host: host.as_mut(),
+        log: log.as_mut(),
+        crash: crash.as_mut(),
+    };
+    let result = run_loop(&mut cpu, frontend.as_mut(), &options, attached);
+    let bundle = match (&result, crash) {
+        (Err((ExitStatus::EmulationError, message)), Some(crash)) => {
+            offer_bundle(frontend.as_mut(), &cpu, crash, &options, message)
+        }
+        _ => None,
+    };
+    let teardown = frontend.teardown();
+    record_session(&options.rom, started.elapsed());
+    if let Some(log) = &mut log {
+        let how = match &result {
+            Ok(Ended::Quit(_)) => "quit",
+            Ok(Ended::Menu) => "menu",
+            Ok(Ended::Load(_)) => "load",
+            Err((_, message)) => {
+                log.event("error", &[("message", session_log::text(message.as_str()))]);
+                "error"
+            }
+        };
+        log.event(
+            "rom_ended",
+            &[
+                ("frames", session_log::number(log.frames())),
+                ("seconds", session_log::number(started.elapsed().as_secs())),
+                ("how", session_log::text(how)),
+            ],
+        );
+    }
+
+    if let (Some(path), Some(gif)) = (&options.gif, gif) {
+        let images = gif.finish().map_err(|err| format!("{}: {}", path, err))?;
+        eprintln!("recorded {} images to {}", images, path);
+    }
+
+    // saved even if the ROM crashed, that is when a recording is most useful
+    if let (Some(path), Some(recorder)) = (&options.record, recorder) {
+        let recording = recorder.finish();
+        recording
+            .save(Path::new(path))
+            .map_err(|err| format!("{}: {}", path, err))?;
+        eprintln!("recorded {} frames to {}", recording.frames, path);
+    }
+
+    for line in cpu.memory.take_mailbox_lines(true) {
+        eprintln!("{}", line);
+    }
+    if let Some(path) = &options.machine.profile {
+        write_profile(path, &cpu)?;
+    }
+    for pc in &cpu.odd_pcs {
+        eprintln!("warning: executed code at odd address {:#05x}", pc);
+    }
+    for line in self_modified(&cpu) {
+        eprintln!("warning: self-modifying code at {}", line);
+    }
+    if let Some(problem) = required.and_then(|required| required.problem(options.machine.platform))
+    {
+        eprintln!("warning: {}", problem);
+    }
+    if let (Some(path), Some(err)) = (
+        &options.session_log,
+        log.as_ref().and_then(SessionLog::failed),
+    ) {
+        eprintln!("warning: {}: {}", path.display(), err);
+    }
+    // only report once the frontend has given the terminal back
+    let ended = match result {
+        Ok(ended) => ended,
+        Err((status, err)) => {
+            eprintln!("{}", err);
+            if let Some(line) = bundle {
+                eprintln!("{}", line);
+            }
+            return Ok(Ended::Quit(status));
+        }
+    };
+    teardown.map_err(|err| format!("{}: {}", frontend.name(), err))?;
+    Ok(ended)
+}
+
+/// Terminals only report key presses, so a key counts as held for this many
+/// frames after its last press (key repeat keeps it held).
+const KEY_HOLD_FRAMES: u8 = 6;
+
+fn load_replay(path: &Path, rom: &[u8]) -> Result<Replay, String> {
+    let replay = Replay::load(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    if replay.rom != replay::rom_hash(rom) {
+        return Err(format!("{}: recorded with a different ROM", path.display()));
+    }
+    Ok(replay)
+}
+
+/// What `run_with` set up around the game for `run_loop`.
+struct Attached<'a> {
+    replay: Option<&'a Replay>,
+    recorder: Option<&'a mut Recorder>,
+    gif: Option<&'a mut GifWriter<BufWriter<fs::File>>>,
+    #[cfg(feature = "net")]
+    host: Option<&'a mut Host>,
+    log: Option<&'a mut SessionLog>,
+    crash: Option<&'a mut CrashRecorder>,
+}
+
+fn run_loop(
+    cpu: &mut Cpu,
+    frontend: &mut dyn Frontend,
+    options: &RunOptions,
+    attached: Attached,
+) -> Result<Ended, (ExitStatus, String)> {
+    let Attached {
+        replay,
+        mut recorder,
+        mut gif,
+        #[cfg(feature = "net")]
+        mut host,
+        mut log,
+        mut crash,
+    } = attached;
+    let frame = Duration::from_secs(1) / 60;
+    let mut next_frame = Instant::now();
+    let mut time_limit = options.time_limit.map(TimeLimit::new);
+    let keymap = &options.config.keymap;
+    let mut key_hold = [0u8; 16];
+    let mut gamepads = Gamepads::new(options.config.gamepad.clone());
+    let mut player = replay.map(Player::new);
+    let mut notice: Option<(String, u32)> = None;
+    let mut rewind = Rewind::new(options.config.rewind);
+    let mut watcher = options.watch.as_deref().map(Watcher::new);
+    let mut config_watcher = options.config_path.as_deref().map(Watcher::new);
+    let mut frames = 0u64;
+    let mut panel = Panel::default();
+    let mut fast_forward = false;
+    #[cfg(feature = "net")]
+    let hosting = host.is_some();
+    #[cfg(not(feature = "net"))]
+    let hosting = false;
+    #[cfg(feature = "net")]
+    if let Some(host) = &host {
+        if let Ok(addr) = host.local_addr() {
+            notice = Some((format!("waiting for player 2 on {}", addr), NOTICE_FRAMES));
+        }
+    }
+
+    #[cfg(feature = "remote-debug")]
+    let mut debugger = match options.remote_debug {
+        Some(port) => {
+            let debugger = RemoteDebugger::bind(port)
+                .map_err(|err| (ExitStatus::Usage, format!("port {}: {}", port, err)))?;
+            notice = Some((format!("debugger port {}", port), NOTICE_FRAMES));
+            Some(debugger)
+        }
+        None => None,
+    };
+    #[cfg(feature = "scripting")]
+    let mut script = options
+        .script
+        .as_deref()
+        .map(load_script)
+        .transpose()
+        .map_err(|err| (ExitStatus::Usage, err))?;
+    let io_err = |err: std::io::Error| (ExitStatus::Usage, err.to_string());
+    let mut input_view = match &options.input_view {
+        Some(target) => {
+            let view = InputView::open(target)
+                .map_err(|err| (ExitStatus::Usage, format!("{}: {}", target, err)))?;
+            if let Some(addr) = view.local_addr() {
+                notice = Some((format!("input view on {}", addr), NOTICE_FRAMES));
+            }
+            Some(view)
+        }
+        None => None,
+    };
+
+    loop {
+        let expired = time_limit.as_ref().is_some_and(TimeLimit::expired);
+        // a ROM from the browser that halted or got stuck is kept as it was, to
+        // look at until Enter goes back
+        let stopped = options.from_browser && (cpu.halted || cpu.stuck());
+
+        frames += 1;
+        if let Some(watcher) = &mut watcher {
+            if frames.is_multiple_of(WATCH_FRAMES) && watcher.changed() {
+                let message = match reload(cpu, options) {
+                    Ok(size) => {
+                        rewind = Rewind::new(options.config.rewind);
+                        format!("reloaded, {} bytes", size)
+                    }
+                    Err(err) => err,
+                };
+                notice = Some((message, NOTICE_FRAMES));
+            }
+        }
+        if let (Some(config_watcher), Some(path)) = (&mut config_watcher, &options.config_path) {
+            if frames.is_multiple_of(WATCH_FRAMES) && config_watcher.changed() {
+                if let Some(message) = reload_overrides(cpu, path, options) {
+                    notice = Some((message, NOTICE_FRAMES));
+                    // the bundle keeps the overrides the crash ran with
+                    if let Some(crash) = crash.as_deref_mut() {
+                        crash.restart(cpu);
+                    }
+                }
+            }
+        }
+
+        let events = frontend.poll_events();
+        // a crash bundle's recording can't follow jumps in time, it starts
+        // over from wherever they land (a failed one does no harm)
+        let jumps = events.iter().any(|event| {
+            matches!(
+                event,
+                Event::Rewind | Event::LoadState | Event::Reset | Event::PowerCycle
+            )
+        });
+        for event in events {
+            match event {
+                Event::Quit => return Ok(Ended::Quit(ExitStatus::Ok)),
+                Event::Drop(path) => {
+                    // all of these are tied to the ROM that is running
+                    if watcher.is_some()
+                        || recorder.is_some()
+                        || replay.is_some()
+                        || gif.is_some()
+                        || hosting
+                    {
+                        let message = "can't load another ROM while watching, recording or hosting";
+                        notice = Some((message.to_string(), NOTICE_FRAMES));
+                    } else {
+                        return Ok(Ended::Load(path));
+                    }
+                }
+                Event::Confirm if stopped => return Ok(Ended::Menu),
+                Event::Confirm if expired => {
+                    if let Some(limit) = &mut time_limit {
+                        limit.reset();
+                    }
+                }
+                Event::Rewind => {
+                    // the recording would not match what happens anymore
+                    let message = if recorder.is_some() || replay.is_some() {
+                        Some("can't rewind during a recording")
+                    } else if options.config.rewind == 0 {
+                        Some("rewinding is turned off")
+                    } else if !rewind.step_back(cpu) {
+                        Some("nothing left to rewind")
+                    } else {
+                        None
+                    };
+                    if let Some(log) = log.as_deref_mut() {
+                        let result = message.unwrap_or("rewound");
+                        log.event("rewind", &[("result", session_log::text(result))]);
+                    }
+                    if let Some(message) = message {
+                        notice = Some((message.to_string(), NOTICE_FRAMES));
+                    }
+                }
+                Event::TogglePanel => {
+                    panel.visible = !panel.visible;
+                    if !panel.visible {
+                        frontend.panel(&[]).map_err(io_err)?;
+                    }
+                }
+                Event::ScrollPanel(rows) => panel.scroll_by(rows),
+                Event::Pick { x, y } => notice = Some((describe_pixel(cpu, x, y), NOTICE_FRAMES)),
+                Event::SaveState | Event::LoadState => {
+                    let name = if event == Event::SaveState {
+                        "state_saved"
+                    } else {
+                        "state_loaded"
+                    };
+                    let message = save_state_hotkey(cpu, event, options, recorder.is_some());
+                    if let Some(log) = log.as_deref_mut() {
+                        log.event(name, &[("result", session_log::text(message.as_str()))]);
+                    }
+                    notice = Some((message, NOTICE_FRAMES));
+                }
+                // the time limit only counts time played
+                Event::Pause if cpu.paused => {
+                    cpu.resume();
+                    cpu.display.mark_all_dirty();
+                    if let Some(limit) = &mut time_limit {
+                        limit.resume();
+                    }
+                }
+                Event::Pause => {
+                    cpu.pause();
+                    if let Some(limit) = &mut time_limit {
+                        limit.pause();
+                    }
+                }
+                Event::FastForward => {
+                    // the guest would see the game run away from them
+                    let wall = options.config.fast_forward_timers == FastForwardTimers::Wall;
+                    let message = if hosting {
+                        "can't fast-forward while hosting".to_string()
+                    } else if wall && recorder.is_some() {
+                        // held timers aren't in the recording, so it wouldn't replay
+                        "can't fast-forward with wall clock timers while recording".to_string()
+                    } else {
+                        fast_forward = !fast_forward;
+                        if fast_forward {
+                            format!("fast-forward on ({}x)", options.config.fast_forward)
+                        } else {
+                            "fast-forward off".to_string()
+                        }
+                    };
+                    notice = Some((message, NOTICE_FRAMES));
+                }
+                Event::SwitchEngine => {
+                    let engine = match cpu.engine {
+                        Engine::Simple => Engine::Cached,
+                        Engine::Cached => Engine::Simple,
+                    };
+                    let message = match savestate::switch_engine(cpu, engine) {
+                        Ok(()) => format!("{:?} engine", engine).to_lowercase(),
+                        Err(err) => format!("can't switch engines: {}", err),
+                    };
+                    notice = Some((message, NOTICE_FRAMES));
+                }
+                Event::Reset | Event::PowerCycle => {
+                    let message = if recorder.is_some() || replay.is_some() {
+                        "can't reset during a recording"
+                    } else if event == Event::Reset {
+                        cpu.reset();
+                        "reset"
+                    } else {
+                        cpu.power_cycle();
+                        "switched off and on"
+                    };
+                    if let Some(log) = log.as_deref_mut() {
+                        let name = if event == Event::Reset {
+                            "reset"
+                        } else {
+                            "power_cycle"
+                        };
+                        log.event(name, &[("result", session_log::text(message))]);
+                    }
+                    notice = Some((message.to_string(), NOTICE_FRAMES));
+                }
+                Event::Screenshot => {
+                    let message = match image::path_for(Path::new(&options.rom)) {
+                        Some(path) => match cpu.screenshot(&options.style).save(&path) {
+                            Ok(()) => format!("saved {}", path.display()),
+                            Err(err) => format!("screenshot failed: {}", err),
+                        },
+                        None => "no data directory for screenshots".to_string(),
+                    };
+                    if let Some(log) = log.as_deref_mut() {
+                        log.event(
+                            "screenshot",
+                            &[("result", session_log::text(message.as_str()))],
+                        );
+                    }
+                    notice = Some((message, NOTICE_FRAMES));
+                }
+                Event::Char(c) => {
+                    if let Some(key) = keymap.key_for(c) {
+                        key_hold[key as usize] = KEY_HOLD_FRAMES;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let (true, Some(crash)) = (jumps, crash.as_deref_mut()) {
+            crash.restart(cpu);
+        }
+
+        if expired {
+            if let Some(limit) = &mut time_limit {
+                limit.pause();
+            }
+            frontend
+                .overlay("TIME'S UP - press Enter")
+                .map_err(io_err)?;
+        } else {
+            gamepads.poll();
+            cpu.keys = held_keys(&mut key_hold, gamepads.keys());
+            #[cfg(feature = "net")]
+            if let Some(message) = host.as_deref_mut().and_then(Host::poll) {
+                notice = Some((describe_notice(&message), NOTICE_FRAMES));
+            }
+            #[cfg(feature = "remote-debug")]
+            let held = debugger.as_mut().is_some_and(|debugger| {
+                debugger.poll(cpu);
+                debugger.stopped()
+            });
+            #[cfg(not(feature = "remote-debug"))]
+            let held = false;
+
+            let count = if fast_forward {
+                options.config.fast_forward
+            } else {
+                1
             };
+            for step in 0..count {
+                if cpu.halted || cpu.paused || held || stopped {
+                    break;
+                }
+                // only the last of the frames shown ticks the timers
+                cpu.hold_timers = options.config.fast_forward_timers == FastForwardTimers::Wall
+                    && step + 1 < count;
+                // the keyboard is ignored until the recording is over
+                if let Some(keys) = player.as_mut().and_then(Player::frame) {
+                    cpu.keys = keys;
+                }
+                #[cfg(feature = "net")]
+                if let Some(host) = host.as_deref_mut() {
+                    cpu.keys = host.keys(cpu.keys);
+                }
+                if let Some(recorder) = recorder.as_deref_mut() {
+                    recorder.frame(cpu);
+                }
+                if let Some(crash) = crash.as_deref_mut() {
+                    crash.frame(cpu);
+                }
+                if let (Some(view), Some(target)) = (&mut input_view, &options.input_view) {
+                    view.frame(&cpu.keys)
+                        .map_err(|err| (ExitStatus::Usage, format!("{}: {}", target, err)))?;
+                }
+
+                let result = run_frame(
+                    cpu,
+                    #[cfg(feature = "remote-debug")]
+                    debugger.as_mut(),
+                    #[cfg(feature = "scripting")]
+                    script.as_mut(),
+                );
+                result.map_err(|err| {
+                    let message = format!("{:#05x}: {}", cpu.program_counter, err);
+                    (ExitStatus::EmulationError, message)
+                })?;
+                rewind.frame(cpu);
+                if let Some(log) = log.as_deref_mut() {
+                    log.frame();
+                    if cpu.halted || cpu.exited {
+                        let name = if cpu.exited { "exited" } else { "halted" };
+                        log.event(name, &[("frames", session_log::number(log.frames()))]);
+                    }
+                }
+                #[cfg(feature = "scripting")]
+                if let Some(running) = &mut script {
+                    // there is only room for the last line
+                    if let Some(line) = running.take_output().pop() {
+                        notice = Some((line, NOTICE_FRAMES));
+                    }
+                    if let Some(failure) = running.failure() {
+                        notice = Some((format!("script stopped, {}", failure), NOTICE_FRAMES));
+                        script = None;
+                    }
+                }
+                if cpu.exited {
+                    return Ok(Ended::Menu);
+                }
+                // the panel has the before and after of the collision
+                if cpu.collision.is_some() {
+                    panel.visible = true;
+                }
+            }
+            cpu.hold_timers = false;
+            frontend.present(&cpu.display).map_err(io_err)?;
+            #[cfg(feature = "net")]
+            let sound = cpu.sound_active() && !cpu.paused;
+            #[cfg(feature = "net")]
+            if let Some(message) = host
+                .as_deref_mut()
+                .and_then(|host| host.send_frame(&cpu.display, sound))
+            {
+                notice = Some((describe_notice(&message), NOTICE_FRAMES));
+            }
+            cpu.display.clear_dirty();
+            if let (Some(gif), Some(path)) = (gif.as_deref_mut(), &options.gif) {
+                gif.frame(&cpu.screenshot(&options.style))
+                    .map_err(|err| (ExitStatus::Usage, format!("{}: {}", path, err)))?;
+            }
+            if panel.visible {
+                frontend.panel(&panel.lines(cpu)).map_err(io_err)?;
+            }
+            if let Some((message, frames)) = &mut notice {
+                frontend.overlay(message).map_err(io_err)?;
+                *frames -= 1;
+                if *frames == 0 {
+                    notice = None;
+                    cpu.display.mark_all_dirty();
+                }
+            } else if let Some(collision) = cpu.collision.as_ref().filter(|_| cpu.paused) {
+                let message = format!("COLLISION at {:#05x} - F6 resumes", collision.pc);
+                frontend.overlay(&message).map_err(io_err)?;
+            } else if cpu.paused {
+                frontend.overlay("PAUSED - F6 resumes").map_err(io_err)?;
+            } else if stopped {
+                frontend
+                    .overlay("HALTED - Enter goes back to the ROMs")
+                    .map_err(io_err)?;
+            }
+            frontend.set_sound(options.config.audio && cpu.sound_active() && !cpu.paused);
+        }
+
+        next_frame += frame;
+        let now = Instant::now();
+        if next_frame > now {
+            thread::sleep(next_frame - now);
+        } else {
+            next_frame = now;
+        }
+    }
+}
+
+/// Asks whether to save a crash bundle after `message` ended the game, and
+/// gives the line to print about it once the terminal is back.
+fn offer_bundle(
+    frontend: &mut dyn Frontend,
+    cpu: &Cpu,
+    crash: CrashRecorder,
+    options: &RunOptions,
+    message: &str,
+) -> Option<String> {
+    loop {
+        frontend
+            .overlay("CRASHED - Enter saves a crash bundle, Esc quits")
+            .ok()?;
+        for event in frontend.poll_events() {
+            match event {
+                Event::Quit => return None,
+                Event::Confirm => {
+                    let Some(path) = crash::path_for(Path::new(&options.rom)) else {
+                        return Some("no data directory for crash bundles".to_string());
+                    };
+                    let line = match crash.bundle(cpu, message).save(&path) {
+                        Ok(()) => format!(
+                            "crash bundle saved to {}, chip8 repro {} {} plays it back",
+                            path.display(),
+                            path.display(),
+                            options.rom
+                        ),
+                        Err(err) => format!("{}: {}", path.display(), err),
+                    };
+                    return Some(line);
+                }
+                _ => {}
+            }
+        }
+        thread::sleep(Duration::from_secs(1) / 60);
+    }
+}
+
+/// How long save state notices stay up.
+const NOTICE_FRAMES: u32 = 90;
+
+/// The keypad keys held this frame: pressed on the keyboard recently, or
+/// held on a gamepad.
+fn held_keys(key_hold: &mut [u8; 16], pad: [bool; 16]) -> [bool; 16] {
+    std::array::from_fn(|key| {
+        let held = key_hold[key] > 0 || pad[key];
+        key_hold[key] = key_hold[key].saturating_sub(1);
+        held
+    })
+}
+
+/// What the debugger says about a pixel clicked.
+fn describe_pixel(cpu: &Cpu, x: usize, y: usize) -> String {
+    let state = if cpu.display.pixel(x, y) { "on" } else { "off" };
+    let origin = cpu
+        .provenance
+        .as_ref()
+        .and_then(|provenance| provenance.get(x, y));
+    match origin {
+        Some(origin) => format!(
+            "pixel {},{} is {}, drawn by {:#05x} in frame {}",
+            x, y, state, origin.pc, origin.frame
+        ),
+        None => format!("pixel {},{} is {}, never drawn", x, y, state),
+    }
+}
+
+#[cfg(feature = "net")]
+fn describe_notice(notice: &Notice) -> String {
+    match notice {
+        Notice::Joined(addr) => format!("player 2 joined from {}", addr),
+        Notice::Refused(addr) => format!("{} tried to join with a different ROM", addr),
+        Notice::Left => "player 2 left".to_string(),
+    }
+}
+
+/// Plays as the second player of a game hosted elsewhere: shows the host's
+/// screen and sends it the keys pressed here, see `netplay`.
+#[cfg(feature = "net")]
+fn join(options: &RunOptions, rom: &[u8], addr: &str) -> Result<ExitStatus, String> {
+    if options.record.is_some()
+        || options.gif.is_some()
+        || options.replay.is_some()
+        || options.script.is_some()
+        || options.remote_debug.is_some()
+    {
+        return Err("the host runs the machine, --join only takes display options".to_string());
+    }
+    let mut guest = Guest::join(addr, rom).map_err(|err| format!("{}: {}", addr, err))?;
+    let mut frontend = load_frontend(&options.frontend, options.style)?;
+    frontend
+        .init()
+        .map_err(|err| format!("{}: {}", frontend.name(), err))?;
+    let result = join_loop(&mut guest, frontend.as_mut(), options);
+    let teardown = frontend.teardown();
+    // reported once the terminal is back
+    let status = match result {
+        Ok(status) => status,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitStatus::Ok
+        }
+    };
+    teardown.map_err(|err| format!("{}: {}", frontend.name(), err))?;
+    Ok(status)
+}
+
+#[cfg(feature = "net")]
+fn join_loop(
+    guest: &mut Guest,
+    frontend: &mut dyn Frontend,
+    options: &RunOptions,
+) -> Result<ExitStatus, String> {
+    let frame = Duration::from_secs(1) / 60;
+    let mut next_frame = Instant::now();
+    let keymap = &options.config.keymap;
+    let mut key_hold = [0u8; 16];
+    let mut gamepads = Gamepads::new(options.config.gamepad.clone());
+    let mut display = Display::new();
+    let io_err = |err: std::io::Error| err.to_string();
+
+    loop {
+        for event in frontend.poll_events() {
+            match event {
+                Event::Quit => return Ok(ExitStatus::Ok),
+                Event::Char(c) => {
+                    if let Some(key) = keymap.key_for(c) {
+                        key_hold[key as usize] = KEY_HOLD_FRAMES;
+                    }
+                }
+                _ => {}
+            }
+        }
+        gamepads.poll();
+        let keys = held_keys(&mut key_hold, gamepads.keys());
+        guest.poll(&mut display).map_err(io_err)?;
+        guest.send_keys(keys).map_err(io_err)?;
+
+        frontend.present(&display).map_err(io_err)?;
+        display.clear_dirty();
+        frontend.set_sound(options.config.audio && guest.sound());
+
+        next_frame += frame;
+        let now = Instant::now();
+        if next_frame > now {
+            thread::sleep(next_frame - now);
+        } else {
+            next_frame = now;
         }
     }
+}
+
+/// How often a watched source is checked for changes.
+const WATCH_FRAMES: u64 = 15;
+
+/// Assembles the watched source again and restarts the machine on the new
+/// ROM. On errors the old one keeps running.
+fn reload(cpu: &mut Cpu, options: &RunOptions) -> Result<usize, String> {
+    let source = options.watch.as_deref().expect("only called when watching");
+    let rom = assemble_file(source, Path::new(&options.rom))?;
+
+    let mut fresh = Cpu::new();
+    fresh.load_rom(&rom).map_err(|err| err.to_string())?;
+    options.machine.apply(&mut fresh);
+    fresh.break_on_collision = options.break_on_collision;
+    fresh.provenance = Some(Box::default());
+    *cpu = fresh;
+    Ok(rom.len())
+}
+
+/// Assembles `source` and writes the ROM to `out`.
+fn assemble_file(source: &Path, out: &Path) -> Result<Vec<u8>, String> {
+    let text =
+        fs::read_to_string(source).map_err(|err| format!("{}: {}", source.display(), err))?;
+    let rom = asm::assemble(&text).map_err(|err| format!("{}: {}", source.display(), err))?;
+    fs::write(out, &rom).map_err(|err| format!("{}: {}", out.display(), err))?;
+    Ok(rom)
+}
 
-    /// 00EE: return from the current sub-routine
-    fn ret(&mut self) {
-        if self.stack_pointer == 0 {
-            panic!("Stack underflow!")
+fn asm_command(args: &[String]) -> Result<ExitStatus, String> {
+    let source = args
+        .first()
+        .filter(|arg| !arg.starts_with("--"))
+        .map(PathBuf::from)
+        .ok_or_else(|| USAGE.to_string())?;
+    let out = match flag_value(args, "--out")? {
+        Some(out) => PathBuf::from(out),
+        None => source.with_extension("ch8"),
+    };
+    let watch = args.iter().any(|arg| arg == "--watch");
+
+    if args.iter().any(|arg| arg == "--run") {
+        if !watch {
+            return Err("--run needs --watch, use chip8 run for a one-off".to_string());
         }
+        assemble_file(&source, &out)?;
+        // the run options follow the source, with the ROM in its place
+        let mut run_args = vec![out.to_string_lossy().into_owned()];
+        run_args.extend(args[1..].iter().cloned());
+        let mut options = RunOptions::parse(&run_args)?;
+        options.watch = Some(source);
+        // dropped ROMs are refused while watching
+        return run_with(options).map(|ended| match ended {
+            Ended::Quit(status) => status,
+            Ended::Load(_) | Ended::Menu => ExitStatus::Ok,
+        });
+    }
 
-        self.stack_pointer -= 1;
-        let call_addr = self.stack[self.stack_pointer];
-        self.program_counter = call_addr as usize;
+    let mut watcher = Watcher::new(&source);
+    loop {
+        match assemble_file(&source, &out) {
+            Ok(rom) => println!("wrote {} bytes to {}", rom.len(), out.display()),
+            Err(err) if watch => eprintln!("{}", err),
+            Err(err) => return Err(err),
+        }
+        if !watch {
+            return Ok(ExitStatus::Ok);
+        }
+        while !watcher.changed() {
+            thread::sleep(Duration::from_secs(1) / 4);
+        }
     }
+}
 
-    /// 1nnn: jump to nnn address
-    fn jump(&mut self, addr: u16) {
-        self.program_counter = addr as usize;
+/// Runs a frame through the debugger or the script, if there is one. They
+/// can't be combined.
+fn run_frame(
+    cpu: &mut Cpu,
+    #[cfg(feature = "remote-debug")] debugger: Option<&mut RemoteDebugger>,
+    #[cfg(feature = "scripting")] script: Option<&mut Script>,
+) -> Result<(), Error> {
+    #[cfg(feature = "remote-debug")]
+    if let Some(debugger) = debugger {
+        return debugger.run_frame(cpu);
+    }
+    #[cfg(feature = "scripting")]
+    if let Some(script) = script {
+        return script.run_frame(cpu);
     }
+    cpu.run_frame()
+}
 
-    /// 2nnn: call sub-routine at addr
-    fn call(&mut self, addr: u16) {
-        let stack_ptr = self.stack_pointer;
-        let stack = &mut self.stack;
+#[cfg(feature = "scripting")]
+fn load_script(path: &Path) -> Result<Script, String> {
+    let source = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    Script::parse(&source).map_err(|err| format!("{}: {}", path.display(), err))
+}
 
-        if stack_ptr > stack.len() {
-            panic!("Stack overflow!")
+/// Saves or loads the ROM's save state and says how it went.
+fn save_state_hotkey(cpu: &mut Cpu, event: Event, options: &RunOptions, recording: bool) -> String {
+    let Some(path) = savestate::path_for(Path::new(&options.rom)) else {
+        return "no data directory for save states".to_string();
+    };
+
+    if event == Event::SaveState {
+        match State::capture(cpu).save(&path) {
+            Ok(()) => "state saved".to_string(),
+            Err(err) => format!("save failed: {}", err),
+        }
+    } else if recording || options.replay.is_some() {
+        // jumping around would make the recording meaningless
+        "can't load states during a recording".to_string()
+    } else {
+        match State::load(&path) {
+            Ok(state) => {
+                state.restore(cpu);
+                "state loaded".to_string()
+            }
+            Err(err) => format!("load failed: {}", err),
         }
+    }
+}
+
+/// Stats are a nicety, failing to save them should not fail the run.
+fn record_session(rom: &str, played: Duration) {
+    let name = Path::new(rom)
+        .file_name()
+        .map_or(rom.to_string(), |name| name.to_string_lossy().into_owned());
 
-        self.stack[stack_ptr] = self.program_counter as u16;
-        self.stack_pointer += 1;
-        self.program_counter = addr as usize;
+    if let Ok(mut stats) = Stats::load_default() {
+        stats.record_launch(&name);
+        stats.add_playtime(&name, played);
+        let _ = stats.save();
     }
+}
+
+/// Dry run: validates the ROM, prints the resolved settings and brings the
+/// frontend up and down again without running anything.
+fn check(options: &RunOptions) -> Result<ExitStatus, String> {
+    let mut status = ExitStatus::Ok;
 
-    /// 3xkk: store if vx == kk
-    fn se_xkk(&mut self, x: u8, kk: u8) {
-        if self.registers[x as usize] == kk {
-            self.program_counter += 2;
+    match fs::read(&options.rom) {
+        Ok(rom) if rom.is_empty() => {
+            println!("rom      {}: file is empty", options.rom);
+            status = ExitStatus::CheckFailed;
+        }
+        Ok(rom) if rom.len() > MEMORY_SIZE - PROGRAM_START => {
+            println!(
+                "rom      {}: {} bytes, at most {} fit in memory",
+                options.rom,
+                rom.len(),
+                MEMORY_SIZE - PROGRAM_START
+            );
+            status = ExitStatus::CheckFailed;
+        }
+        Ok(rom) => {
+            let first = ((rom[0] as u16) << 8) | *rom.get(1).unwrap_or(&0) as u16;
+            match Instruction::decode(first) {
+                Ok(_) => println!("rom      {}: {} bytes ok", options.rom, rom.len()),
+                Err(err) => {
+                    println!("rom      {}: first instruction: {}", options.rom, err);
+                    status = ExitStatus::CheckFailed;
+                }
+            }
+            if let Some(metadata) = &options.metadata {
+                println!("rom      {}: {}", options.rom, metadata.describe());
+            }
+            if let Some(required) = platform::required(&rom) {
+                if let Some(problem) = required.problem(options.machine.platform) {
+                    println!("rom      {}: {}", options.rom, problem);
+                }
+                if !required.met_by(options.machine.platform) {
+                    status = ExitStatus::CheckFailed;
+                }
+            }
+        }
+        Err(err) => {
+            println!("rom      {}: {}", options.rom, err);
+            status = ExitStatus::CheckFailed;
         }
     }
 
-    /// 4xkk: store if vx not equal kk
-    fn sne(&mut self, vx: u8, kk: u8) {
-        if vx != kk {
-            self.program_counter += 2;
+    match &options.config_path {
+        Some(path) => println!("config   file = {}", path.display()),
+        None => println!("config   file = none, using defaults"),
+    }
+    println!("config   frontend = {}", options.frontend);
+    println!(
+        "config   instructions per frame = {}",
+        options.machine.speed
+    );
+    println!(
+        "config   platform = {} ({})",
+        options.config.preset.name, options.config.preset.description
+    );
+    println!("config   quirks = {:?}", options.machine.quirks);
+    println!(
+        "config   font = {} ({})",
+        options.machine.font.name, options.machine.font.description
+    );
+    println!("config   pc overflow = {:?}", options.machine.pc_overflow);
+    println!("config   odd pc = {:?}", options.machine.odd_pc);
+    println!("config   on exit = {:?}", options.machine.on_exit);
+    println!("config   engine = {:?}", options.machine.engine);
+    let keys: String = options.config.keymap.keys.iter().collect();
+    println!("config   keys 0-F = {}", keys);
+    println!(
+        "config   display = scale {}, {} on {}, ghosting {}",
+        options.style.scale,
+        options.style.foreground,
+        options.style.background,
+        if options.style.ghosting { "on" } else { "off" }
+    );
+    println!("config   audio = {}", options.config.audio);
+    println!("config   rewind = {}s", options.config.rewind);
+    if !options.machine.overrides.is_empty() {
+        let rules: Vec<String> = options
+            .machine
+            .overrides
+            .rules()
+            .map(|rule| rule.to_string())
+            .collect();
+        println!("config   opcodes = {}", rules.join(", "));
+    }
+    println!(
+        "config   fast-forward = {}x, {:?} timers",
+        options.config.fast_forward, options.config.fast_forward_timers
+    );
+    match options.time_limit {
+        Some(limit) => println!("config   time limit = {}s", limit.as_secs()),
+        None => println!("config   time limit = none"),
+    }
+
+    match load_frontend(&options.frontend, options.style) {
+        Ok(mut frontend) => {
+            let result = frontend.init().and_then(|_| frontend.teardown());
+            match result {
+                Ok(()) => println!("frontend {}: ok", frontend.name()),
+                Err(err) => {
+                    println!("frontend {}: {}", frontend.name(), err);
+                    status = ExitStatus::CheckFailed;
+                }
+            }
+        }
+        Err(err) => {
+            println!("frontend {}", err);
+            status = ExitStatus::CheckFailed;
         }
     }
 
-    /// 5xy0: store if vx == vy
-    fn se_xy(&mut self, x: u8, y: u8) {
-        let vx = self.registers[x as usize];
-        let vy = self.registers[y as usize];
-        if vx == vy {
-            self.program_counter += 2;
+    Ok(status)
+}
+
+fn parse_hash(text: &str) -> Result<u64, String> {
+    u64::from_str_radix(text.trim_start_matches("0x"), 16)
+        .map_err(|_| format!("invalid hash: {}", text))
+}
+
+/// Runs a ROM headlessly and compares the framebuffer hash.
+fn test(args: &[String]) -> Result<ExitStatus, String> {
+    let (config, _) = load_config(args, None)?;
+    let machine = MachineOptions::parse(args, &config)?;
+    if machine.profile.is_some()
+        && (flag_value(args, "--manifest")?.is_some()
+            || args.first().is_some_and(|rom| Path::new(rom).is_dir()))
+    {
+        return Err("--profile takes a single ROM".to_string());
+    }
+    if let Some(manifest) = flag_value(args, "--manifest")? {
+        return test_manifest(Path::new(manifest), &machine);
+    }
+
+    let rom = args
+        .first()
+        .filter(|arg| !arg.starts_with("--"))
+        .ok_or_else(|| USAGE.to_string())?;
+    if Path::new(rom).is_dir() {
+        let junit = flag_value(args, "--junit")?.map(Path::new);
+        return test_project(Path::new(rom), &machine, junit);
+    }
+    let frames = match flag_value(args, "--frames")? {
+        Some(frames) => Some(
+            frames
+                .replace('_', "")
+                .parse()
+                .map_err(|_| format!("invalid frame count: {}", frames))?,
+        ),
+        None => None,
+    };
+    let check = Check {
+        rom: PathBuf::from(rom),
+        frames,
+        expected: flag_value(args, "--expect")?.map(parse_hash).transpose()?,
+        until_halt: args.iter().any(|arg| arg == "--until-halt"),
+        until_error: false,
+        replay: flag_value(args, "--replay")?.map(PathBuf::from),
+        state: flag_value(args, "--state")?.map(PathBuf::from),
+        differential: args.iter().any(|arg| arg == "--differential"),
+        script: flag_value(args, "--script")?.map(PathBuf::from),
+    };
+
+    check_rom(&check, &machine)
+}
+
+/// Each manifest line is `<rom> <frames> <hash> [halt] [error] [differential] [replay=<file>]
+/// [state=<file>] [font=<name>] [on-exit=<mode>] [pc-overflow=<mode>] [script=<file>]
+/// [provenance]`, with paths relative to the manifest. `halt` means the ROM must halt within
+/// its frames, `error` that it must stop on an emulation error instead, `differential` that
+/// both engines must agree, `provenance` runs it tracking what drew each pixel. A hash of `-`
+/// skips the framebuffer check, for ROMs that report through assertions. The exit status is
+/// the worst of all lines.
+fn test_manifest(manifest: &Path, machine: &MachineOptions) -> Result<ExitStatus, String> {
+    let contents =
+        fs::read_to_string(manifest).map_err(|err| format!("{}: {}", manifest.display(), err))?;
+    let base = manifest.parent().unwrap_or(Path::new("."));
+    let mut status = ExitStatus::Ok;
+
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let usage = || {
+            format!(
+                "{}:{}: expected <rom> <frames> <hash> [halt] [error] [differential] [replay=<file>] [state=<file>] [font=<name>] [on-exit=<mode>] [pc-overflow=<mode>] [script=<file>] [provenance]",
+                manifest.display(),
+                number + 1
+            )
+        };
+        let [rom, frames, hash, ref options @ ..] = fields[..] else {
+            return Err(usage());
+        };
+        let frames = frames
+            .parse()
+            .map_err(|_| format!("{}:{}: invalid frame count", manifest.display(), number + 1))?;
+        let mut check = Check {
+            rom: base.join(rom),
+            frames: Some(frames),
+            expected: match hash {
+                "-" => None,
+                hash => Some(parse_hash(hash)?),
+            },
+            until_halt: false,
+            until_error: false,
+            replay: None,
+            state: None,
+            differential: false,
+            script: None,
+        };
+        let mut machine = machine.clone();
+        for &option in options {
+            if option == "halt" {
+                check.until_halt = true;
+            } else if option == "error" {
+                check.until_error = true;
+            } else if option == "differential" {
+                check.differential = true;
+            } else if option == "provenance" {
+                machine.provenance = true;
+            } else if let Some(replay) = option.strip_prefix("replay=") {
+                check.replay = Some(base.join(replay));
+            } else if let Some(state) = option.strip_prefix("state=") {
+                check.state = Some(base.join(state));
+            } else if let Some(name) = option.strip_prefix("font=") {
+                machine.font = font::font(name).ok_or_else(usage)?;
+            } else if let Some(script) = option.strip_prefix("script=") {
+                check.script = Some(base.join(script));
+            } else if let Some(name) = option.strip_prefix("on-exit=") {
+                machine.on_exit = parse_on_exit(name).ok_or_else(usage)?;
+            } else if let Some(name) = option.strip_prefix("pc-overflow=") {
+                machine.pc_overflow = parse_pc_overflow(name).ok_or_else(usage)?;
+            } else {
+                return Err(usage());
+            }
         }
+
+        status = status.max(check_rom(&check, &machine)?);
     }
 
-    /// 6xkk: set register x to kk
-    fn set(&mut self, x: u8, kk: u8) {
-        self.registers[x as usize] = kk;
+    Ok(status)
+}
+
+/// Tests a project made by `chip8 new`: assembles src/main.asm and plays every
+/// tests/*.scenario against a fresh machine. `junit` also gets the results as
+/// JUnit XML, for CI. The exit status is the worst of all scenarios.
+fn test_project(
+    dir: &Path,
+    machine: &MachineOptions,
+    junit: Option<&Path>,
+) -> Result<ExitStatus, String> {
+    let source = dir.join("src").join("main.asm");
+    let text =
+        fs::read_to_string(&source).map_err(|err| format!("{}: {}", source.display(), err))?;
+    let rom = asm::assemble(&text).map_err(|err| format!("{}: {}", source.display(), err))?;
+
+    let tests = dir.join("tests");
+    let mut paths: Vec<PathBuf> = fs::read_dir(&tests)
+        .and_then(|entries| {
+            entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect()
+        })
+        .map_err(|err| format!("{}: {}", tests.display(), err))?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "scenario"));
+    paths.sort();
+    if paths.is_empty() {
+        return Err(format!("{}: no .scenario files", tests.display()));
     }
 
-    /// 7xkk: add kk to register x
-    fn add(&mut self, vx: u8, kk: u8) {
-        self.registers[vx as usize] += kk;
+    // all of them up front, so a typo doesn't show up halfway through the results
+    let scenarios = paths
+        .iter()
+        .map(|path| {
+            let text =
+                fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+            Scenario::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut status = ExitStatus::Ok;
+    let mut cases = Vec::new();
+    for (path, scenario) in paths.iter().zip(&scenarios) {
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut cpu = headless::machine();
+        machine.apply(&mut cpu);
+        cpu.load_rom(&rom)
+            .map_err(|err| format!("{}: {}", source.display(), err))?;
+        let started = Instant::now();
+        let mut report = scenario.run(cpu);
+        let time = started.elapsed();
+
+        for line in report.cpu.memory.take_mailbox_lines(true) {
+            eprintln!("{}: {}", name, line);
+        }
+        let result = match report.failure {
+            None => {
+                println!("PASS {} ({} frames)", name, report.frames);
+                TestResult::Passed
+            }
+            Some(failure) => {
+                println!("FAIL {}: {}", name, failure);
+                status = status.max(failure.status);
+                let message = format!("{}:{}: {}", path.display(), failure.line, failure.message);
+                if failure.status == ExitStatus::EmulationError {
+                    TestResult::Error(message)
+                } else {
+                    TestResult::Failed(message)
+                }
+            }
+        };
+        cases.push(TestCase { name, time, result });
     }
 
-    fn and_xy(&mut self, x: u8, y: u8) {
-        let vx = self.registers[x as usize];
-        let vy = self.registers[y as usize];
+    let failed = cases
+        .iter()
+        .filter(|case| !matches!(case.result, TestResult::Passed))
+        .count();
+    println!(
+        "{} scenario{}, {} failed",
+        cases.len(),
+        if cases.len() == 1 { "" } else { "s" },
+        failed
+    );
 
-        self.registers[x as usize] = vx & vy;
+    if let Some(junit) = junit {
+        let suite = fs::canonicalize(dir)
+            .ok()
+            .and_then(|dir| {
+                dir.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+            })
+            .unwrap_or_else(|| dir.display().to_string());
+        fs::write(junit, junit::report(&suite, &cases))
+            .map_err(|err| format!("{}: {}", junit.display(), err))?;
     }
+    Ok(status)
+}
+
+/// A ROM to run headlessly and what to expect of it.
+struct Check {
+    rom: PathBuf,
+    /// Defaults to the length of the replay, or DEFAULT_TEST_FRAMES.
+    frames: Option<usize>,
+    expected: Option<u64>,
+    until_halt: bool,
+    /// The ROM has to stop on an emulation error, for fixtures of the errors.
+    until_error: bool,
+    /// Press the keys of this recording.
+    replay: Option<PathBuf>,
+    /// Start from this save state instead of a fresh machine.
+    state: Option<PathBuf>,
+    /// Also run the cached engine next to the simple one and compare them
+    /// after every frame.
+    differential: bool,
+    /// Run the machine through this script.
+    script: Option<PathBuf>,
+}
+
+fn check_rom(check: &Check, machine: &MachineOptions) -> Result<ExitStatus, String> {
+    let path = check.rom.as_path();
+    let rom = fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let recording = check
+        .replay
+        .as_deref()
+        .map(|replay| load_replay(replay, &rom))
+        .transpose()?;
+    let state = check
+        .state
+        .as_deref()
+        .map(|state| State::load(state).map_err(|err| format!("{}: {}", state.display(), err)))
+        .transpose()?;
+    let prepare = |engine: Engine| -> Result<Cpu, String> {
+        let mut cpu = headless::machine();
+        machine.apply(&mut cpu);
+        cpu.engine = engine;
+        if let Some(recording) = &recording {
+            recording.apply(&mut cpu);
+        }
+        cpu.load_rom(&rom)
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        if let Some(state) = &state {
+            state.restore(&mut cpu);
+        }
+        Ok(cpu)
+    };
 
-    fn or_xy(&mut self, x: u8, y: u8) {
-        let vx = self.registers[x as usize];
-        let vy = self.registers[y as usize];
+    let frames = check
+        .frames
+        .or(recording.as_ref().map(|recording| recording.frames))
+        .unwrap_or(DEFAULT_TEST_FRAMES);
+    let until_halt = check.until_halt;
+    let press_keys = || {
+        let mut player = recording.as_ref().map(Player::new);
+        move |cpu: &mut Cpu| {
+            if let Some(player) = &mut player {
+                cpu.keys = player.frame().unwrap_or([false; 16]);
+            }
+        }
+    };
 
-        self.registers[x as usize] = vx | vy;
+    if check.differential {
+        let simple = prepare(Engine::Simple)?;
+        let cached = prepare(Engine::Cached)?;
+        if let Some(frame) = headless::first_divergence(simple, cached, frames, press_keys()) {
+            println!(
+                "FAIL {}: the cached engine diverges in frame {}",
+                path.display(),
+                frame
+            );
+            return Ok(ExitStatus::CheckFailed);
+        }
     }
 
-    fn xor_xy(&mut self, x: u8, y: u8) {
-        let vx = self.registers[x as usize];
-        let vy = self.registers[y as usize];
+    #[cfg(not(feature = "scripting"))]
+    if check.script.is_some() {
+        return Err("--script needs a build with the scripting feature".to_string());
+    }
+    #[cfg(feature = "scripting")]
+    let mut script = check.script.as_deref().map(load_script).transpose()?;
+    #[cfg(feature = "scripting")]
+    let mut run = match &mut script {
+        Some(script) => {
+            headless::resume_with(prepare(machine.engine)?, frames, press_keys(), |cpu| {
+                script.run_frame(cpu)
+            })
+        }
+        None => headless::resume(prepare(machine.engine)?, frames, press_keys()),
+    };
+    #[cfg(not(feature = "scripting"))]
+    let mut run = headless::resume(prepare(machine.engine)?, frames, press_keys());
 
-        self.registers[x as usize] = vx ^ vy;
+    // on stderr, so the results on stdout stay easy to parse
+    for line in run.cpu.memory.take_mailbox_lines(true) {
+        eprintln!("{}: {}", path.display(), line);
+    }
+    if let Some(profile) = &machine.profile {
+        write_profile(profile, &run.cpu)?;
+    }
+    for pc in &run.cpu.odd_pcs {
+        eprintln!(
+            "{}: warning: executed code at odd address {:#05x}",
+            path.display(),
+            pc
+        );
+    }
+    for line in self_modified(&run.cpu) {
+        eprintln!(
+            "{}: warning: self-modifying code at {}",
+            path.display(),
+            line
+        );
+    }
+    if let Some(problem) =
+        platform::required(&rom).and_then(|required| required.problem(machine.platform))
+    {
+        eprintln!("{}: warning: {}", path.display(), problem);
+    }
+    #[cfg(feature = "scripting")]
+    if let Some(script) = &mut script {
+        for line in script.take_output() {
+            eprintln!("{}: {}", path.display(), line);
+        }
+        if let Some(failure) = script.failure() {
+            println!("FAIL {}: script {}", path.display(), failure);
+            return Ok(ExitStatus::AssertionFailed);
+        }
     }
 
-    /// 8xy4: add vy to vx
-    fn add_xy(&mut self, x: u8, y: u8) {
-        let vx = self.registers[x as usize];
-        let vy = self.registers[y as usize];
+    match run.outcome {
+        Outcome::Error(err) if check.until_error => {
+            println!("PASS {}: {} in frame {}", path.display(), err, run.frames);
+            return Ok(ExitStatus::Ok);
+        }
+        _ if check.until_error => {
+            println!(
+                "FAIL {}: no emulation error within {} frames",
+                path.display(),
+                frames
+            );
+            return Ok(ExitStatus::CheckFailed);
+        }
+        Outcome::Error(err) => {
+            println!(
+                "FAIL {}: {} at {:#05x} in frame {}",
+                path.display(),
+                err,
+                run.cpu.program_counter,
+                run.frames
+            );
+            return Ok(ExitStatus::EmulationError);
+        }
+        Outcome::FramesElapsed if until_halt => {
+            println!(
+                "FAIL {}: did not halt within {} frames",
+                path.display(),
+                frames
+            );
+            return Ok(ExitStatus::Timeout);
+        }
+        _ => {}
+    }
 
-        let (val, overflow) = vx.overflowing_add(vy);
-        self.registers[x as usize] = val;
+    if let Some(assertion) = &run.cpu.assertion {
+        if !assertion.passed {
+            println!("{} {}", path.display(), assertion);
+            return Ok(ExitStatus::AssertionFailed);
+        }
+        if check.expected.is_none() {
+            println!("{} {}", path.display(), assertion);
+            return Ok(ExitStatus::Ok);
+        }
+    }
 
-        // last register of CHIP-8 is a carry flag.
-        // if set indicates that an operation has overflowed the u8 register size
-        if overflow {
-            self.registers[0xF] = 1;
-        } else {
-            self.registers[0xF] = 0;
+    let hash = run.cpu.display.hash();
+    match check.expected {
+        Some(expected) if expected == hash => {
+            println!("PASS {} {:016x}", path.display(), hash);
+            Ok(ExitStatus::Ok)
+        }
+        Some(expected) => {
+            println!(
+                "FAIL {}: expected {:016x}, got {:016x}",
+                path.display(),
+                expected,
+                hash
+            );
+            Ok(ExitStatus::CheckFailed)
+        }
+        None => {
+            print!("{} {} {:016x}", path.display(), frames, hash);
+            if let Some(replay) = &check.replay {
+                print!(" replay={}", replay.display());
+            }
+            if let Some(state) = &check.state {
+                print!(" state={}", state.display());
+            }
+            println!();
+            Ok(ExitStatus::Ok)
         }
     }
 }
 
-fn main() {
-    let mut cpu = CPU {
-        registers: [0; 16],
-        memory: [0; 4096],
-        program_counter: 0,
-        stack: [0; 16],
-        stack_pointer: 0,
+fn config_command(args: &[String]) -> Result<ExitStatus, String> {
+    if args.first().map(String::as_str) != Some("init") {
+        return Err(USAGE.to_string());
+    }
+
+    let path = flag_value(args, "--config")?.unwrap_or(config::FILE_NAME);
+    let force = args.iter().any(|arg| arg == "--force");
+    if Path::new(path).exists() && !force {
+        return Err(format!(
+            "{} already exists, use --force to overwrite it",
+            path
+        ));
+    }
+
+    fs::write(path, config::default_file()).map_err(|err| format!("{}: {}", path, err))?;
+    println!("wrote {}", path);
+    Ok(ExitStatus::Ok)
+}
+
+const DEFAULT_BENCH_INSTRUCTIONS: u64 = 10_000_000;
+
+/// Runs a ROM headlessly as fast as possible and reports the instruction rate.
+/// Timers still tick every `speed` instructions so timing loops behave.
+fn bench(args: &[String]) -> Result<ExitStatus, String> {
+    let rom_path = args
+        .first()
+        .filter(|arg| !arg.starts_with("--"))
+        .ok_or_else(|| USAGE.to_string())?;
+    let (config, _) = load_config(args, None)?;
+    let machine = MachineOptions::parse(args, &config)?;
+    let instructions = match flag_value(args, "--instructions")? {
+        Some(count) => count
+            .replace('_', "")
+            .parse()
+            .ok()
+            .filter(|&count| count > 0)
+            .ok_or_else(|| format!("invalid instruction count: {}", count))?,
+        None => DEFAULT_BENCH_INSTRUCTIONS,
     };
 
+    let rom = fs::read(rom_path).map_err(|err| format!("{}: {}", rom_path, err))?;
+    let mut cpu = headless::machine();
+    machine.apply(&mut cpu);
+    cpu.load_rom(&rom).map_err(|err| err.to_string())?;
+
+    let started = Instant::now();
+    let mut executed = 0;
+    let mut status = ExitStatus::Ok;
+    while executed < instructions {
+        if cpu.halted {
+            println!("halted after {} instructions", executed);
+            break;
+        }
+        if let Err(err) = cpu.step() {
+            println!(
+                "{:#05x}: {} after {} instructions",
+                cpu.program_counter, err, executed
+            );
+            status = ExitStatus::EmulationError;
+            break;
+        }
+        executed += 1;
+        if executed % cpu.speed as u64 == 0 {
+            cpu.tick_timers();
+        }
+    }
+    let elapsed = started.elapsed();
+    if let Some(path) = &machine.profile {
+        write_profile(path, &cpu)?;
+    }
+    for line in self_modified(&cpu) {
+        eprintln!("warning: self-modifying code at {}", line);
+    }
+
+    let per_second = executed as f64 / elapsed.as_secs_f64();
+    let real_time = (cpu.speed * 60) as f64;
+    println!(
+        "{} instructions in {:.3}s: {:.2}M instructions/s, {:.0}x real time at {} per frame",
+        executed,
+        elapsed.as_secs_f64(),
+        per_second / 1e6,
+        per_second / real_time,
+        cpu.speed
+    );
+    Ok(status)
+}
+
+/// Takes up the opcode overrides of the config file at `path` after it was
+/// saved; the rest of it waits for the next run. Says what happened, if it
+/// was anything.
+fn reload_overrides(cpu: &mut Cpu, path: &Path, options: &RunOptions) -> Option<String> {
+    let mut config = match Config::load(path) {
+        Ok(config) => config,
+        Err(err) => return Some(err.to_string()),
+    };
+    if let Some(metadata) = &options.metadata {
+        metadata.apply(&mut config);
+    }
+    if config.opcodes == cpu.overrides {
+        return None;
+    }
+    cpu.overrides = config.opcodes;
+    let rules = cpu.overrides.rules().count();
+    Some(format!("{} opcode overrides now", rules))
+}
+
+/// The `--self-modify` summary, nothing without the flag.
+fn self_modified(cpu: &Cpu) -> Vec<String> {
+    cpu.self_modify
+        .as_ref()
+        .map(|self_modify| self_modify.summary())
+        .unwrap_or_default()
+}
+
+/// Writes the `--profile` report to `path`: JSON for a `.json` file, text
+/// otherwise, and `-` prints the text.
+fn write_profile(path: &str, cpu: &Cpu) -> Result<(), String> {
+    let Some(profile) = &cpu.profile else {
+        return Ok(());
+    };
+    if path == "-" {
+        eprint!("{}", profile.report(&cpu.memory));
+        return Ok(());
+    }
+    let report = if path.ends_with(".json") {
+        format!("{:#}\n", profile.to_json(&cpu.memory))
+    } else {
+        profile.report(&cpu.memory)
+    };
+    fs::write(path, report).map_err(|err| format!("{}: {}", path, err))?;
+    eprintln!("wrote the profile to {}", path);
+    Ok(())
+}
+
+const DEFAULT_FUZZ_RUNS: u64 = 1000;
+const DEFAULT_FUZZ_STEPS: usize = 1000;
+/// Enough for a header and a few hundred instructions.
+const MAX_FUZZ_INPUT: usize = 1024;
+
+fn fuzz_command(args: &[String]) -> Result<ExitStatus, String> {
+    let count = |flag: &str, default: u64| -> Result<u64, String> {
+        match flag_value(args, flag)? {
+            Some(count) => count
+                .replace('_', "")
+                .parse()
+                .ok()
+                .filter(|&count| count > 0)
+                .ok_or_else(|| format!("invalid {} count: {}", &flag[2..], count)),
+            None => Ok(default),
+        }
+    };
+    let runs = count("--runs", DEFAULT_FUZZ_RUNS)?;
+    let steps = count("--steps", DEFAULT_FUZZ_STEPS as u64)? as usize;
+    let seed = match flag_value(args, "--seed")? {
+        Some(seed) => seed
+            .parse()
+            .map_err(|_| format!("invalid seed: {}", seed))?,
+        None => Rng::time_seed(),
+    };
+
+    // every run gets its own seed, so a failure can be run again on its own
+    let mut total = 0;
+    for run in 0..runs {
+        let run_seed = seed.wrapping_add(run);
+        let mut rng = Rng::new(run_seed);
+        let len = u16::from_le_bytes([rng.next_u8(), rng.next_u8()]) as usize % MAX_FUZZ_INPUT;
+        let input: Vec<u8> = (0..len).map(|_| rng.next_u8()).collect();
+        match fuzz::check(&input, steps) {
+            Ok(steps) => total += steps,
+            Err(mismatch) => {
+                println!(
+                    "run {} (--seed {} --runs 1): {}",
+                    run + 1,
+                    run_seed,
+                    mismatch
+                );
+                if let Some(path) = flag_value(args, "--save")? {
+                    fs::write(path, &input).map_err(|err| format!("{}: {}", path, err))?;
+                    println!("saved the input to {}", path);
+                }
+                return Ok(ExitStatus::CheckFailed);
+            }
+        }
+    }
+    println!(
+        "{} runs, {} steps, no disagreements (--seed {})",
+        runs, total, seed
+    );
+    Ok(ExitStatus::Ok)
+}
+
+fn new_project(args: &[String]) -> Result<ExitStatus, String> {
+    let dir = args
+        .first()
+        .filter(|arg| !arg.starts_with("--"))
+        .map(PathBuf::from)
+        .ok_or_else(|| USAGE.to_string())?;
+    let name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .filter(|name| {
+            name.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+        .ok_or_else(|| {
+            format!(
+                "{}: use letters, digits, - and _ for the project name",
+                dir.display()
+            )
+        })?;
+    if dir.exists() {
+        return Err(format!("{} already exists", dir.display()));
+    }
+
+    for file in template::project(&name) {
+        let path = dir.join(&file.path);
+        let write = || -> std::io::Result<()> {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, &file.contents)?;
+            #[cfg(unix)]
+            if file.executable {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+            }
+            Ok(())
+        };
+        write().map_err(|err| format!("{}: {}", path.display(), err))?;
+        println!("created {}", path.display());
+    }
+    println!(
+        "run {} to build and test it",
+        dir.join("build.sh").display()
+    );
+    Ok(ExitStatus::Ok)
+}
+
+/// Downloads a ROM from an archive index into the ROM directory, with its
+/// index entry next to it as `<id>.json`.
+fn fetch(args: &[String]) -> Result<ExitStatus, String> {
+    let query = args
+        .first()
+        .filter(|arg| !arg.starts_with("--"))
+        .ok_or_else(|| USAGE.to_string())?;
+    let location = flag_value(args, "--index")?.unwrap_or(archive::DEFAULT_INDEX);
+    let (config, _) = load_config(args, None)?;
+    let dir = rom_dir(args, &config)?;
+
+    let text = archive::fetch(location)
+        .map_err(|err| format!("{}: {}", location, err))
+        .and_then(|bytes| {
+            String::from_utf8(bytes).map_err(|_| format!("{}: not a text file", location))
+        })?;
+    let index = Index::parse(&text, location).map_err(|err| format!("{}: {}", location, err))?;
+    let entry = match index.search(query)[..] {
+        [] => return Err(format!("no ROM in {} matches {:?}", location, query)),
+        [entry] => entry,
+        ref entries => {
+            let mut message = format!("{} ROMs match {:?}:", entries.len(), query);
+            for entry in entries {
+                message.push_str(&format!("\n    {:<24} {}", entry.id, entry.title));
+            }
+            return Err(message);
+        }
+    };
+    if !entry.redistributable() {
+        return Err(format!(
+            "{} is under {}, which does not allow downloading it",
+            entry.title, entry.license
+        ));
+    }
+
+    let rom = archive::fetch(&entry.rom).map_err(|err| format!("{}: {}", entry.rom, err))?;
+    let hash = sha1::hex(&rom);
+    match &entry.sha1 {
+        Some(expected) if *expected != hash => {
+            return Err(format!(
+                "{}: expected SHA-1 {}, got {}",
+                entry.rom, expected, hash
+            ))
+        }
+        Some(_) => {}
+        None => eprintln!(
+            "warning: the index has no SHA-1 for {}, not verified",
+            entry.id
+        ),
+    }
+
+    let path = dir.join(format!("{}.ch8", entry.id));
+    let metadata = dir.join(format!("{}.json", entry.id));
+    fs::create_dir_all(&dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    fs::write(&path, &rom).map_err(|err| format!("{}: {}", path.display(), err))?;
+    fs::write(&metadata, format!("{:#}\n", entry.metadata_with(&hash)))
+        .map_err(|err| format!("{}: {}", metadata.display(), err))?;
+
+    print!("fetched {}", entry.title);
+    if !entry.authors.is_empty() {
+        print!(" by {}", entry.authors.join(", "));
+    }
+    println!(" ({}) to {}", entry.license, path.display());
+    Ok(ExitStatus::Ok)
+}
+
+/// The ROMs named before the first flag, with directories expanded.
+fn rom_paths(args: &[String]) -> Result<Vec<PathBuf>, String> {
+    let paths: Vec<PathBuf> = args
+        .iter()
+        .take_while(|arg| !arg.starts_with("--"))
+        .map(PathBuf::from)
+        .collect();
+    if paths.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    batch::collect_roms(&paths).map_err(|err| err.to_string())
+}
+
+fn disasm_command(args: &[String]) -> Result<ExitStatus, String> {
+    let roms = rom_paths(args)?;
+    let out = flag_value(args, "--out")?.map(Path::new);
+    if let Some(out) = out {
+        fs::create_dir_all(out).map_err(|err| format!("{}: {}", out.display(), err))?;
+    }
+
+    let listings = batch::run(&roms, |path| -> Result<String, String> {
+        let rom = fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let listing = disasm::disassemble(&rom);
+        match out {
+            Some(out) => {
+                let name = path.file_stem().unwrap_or(path.as_os_str());
+                let file = out.join(name).with_extension("asm");
+                fs::write(&file, listing).map_err(|err| format!("{}: {}", file.display(), err))?;
+                Ok(String::new())
+            }
+            None => Ok(listing),
+        }
+    });
+
+    let mut status = ExitStatus::Ok;
+    for (path, listing) in roms.iter().zip(listings) {
+        match listing {
+            Ok(listing) if out.is_none() => {
+                if roms.len() > 1 {
+                    println!("== {} ==", path.display());
+                }
+                print!("{}", listing);
+            }
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("{}", err);
+                status = ExitStatus::Usage;
+            }
+        }
+    }
+    if let Some(out) = out {
+        println!("wrote {} listings to {}", roms.len(), out.display());
+    }
+    Ok(status)
+}
+
+/// Prints every finding; fails if any ROM has an error.
+fn lint_command(args: &[String]) -> Result<ExitStatus, String> {
+    let roms = rom_paths(args)?;
+    let results = batch::run(&roms, |path| fs::read(path).map(|rom| lint::lint(&rom)));
+
+    let mut status = ExitStatus::Ok;
+    let mut errors = 0;
+    for (path, findings) in roms.iter().zip(results) {
+        let findings = match findings {
+            Ok(findings) => findings,
+            Err(err) => {
+                eprintln!("{}: {}", path.display(), err);
+                status = ExitStatus::Usage;
+                continue;
+            }
+        };
+        for finding in findings {
+            let severity = if finding.issue.is_error() {
+                errors += 1;
+                "error"
+            } else {
+                "warning"
+            };
+            println!(
+                "{}:{:#05x}: {}: {}",
+                path.display(),
+                finding.addr,
+                severity,
+                finding.issue
+            );
+        }
+    }
+
+    if errors > 0 {
+        println!("{} errors in {} ROMs", errors, roms.len());
+        if status == ExitStatus::Ok {
+            status = ExitStatus::CheckFailed;
+        }
+    }
+    Ok(status)
+}
+
+fn bisect_command(args: &[String]) -> Result<ExitStatus, String> {
+    let [rom_path, replay_path] = match args {
+        [rom, replay, ..] if !rom.starts_with("--") && !replay.starts_with("--") => {
+            [Path::new(rom), Path::new(replay)]
+        }
+        _ => return Err(USAGE.to_string()),
+    };
+    let (config, _) = load_config(args, None)?;
+    let machine = MachineOptions::parse(args, &config)?;
+    let rom = fs::read(rom_path).map_err(|err| format!("{}: {}", rom_path.display(), err))?;
+    let recording = load_replay(replay_path, &rom)?;
+    // checked once here, so the probes can't fail
+    headless::machine()
+        .load_rom(&rom)
+        .map_err(|err| format!("{}: {}", rom_path.display(), err))?;
+    let prepare = || {
+        let mut cpu = headless::machine();
+        machine.apply(&mut cpu);
+        recording.apply(&mut cpu);
+        cpu.load_rom(&rom).expect("the ROM loaded before");
+        cpu
+    };
+
+    let desync = match bisect::bisect(&recording, prepare) {
+        bisect::Outcome::NoCheckpoints => {
+            return Err(format!(
+                "{}: no checkpoints to compare with, record it again",
+                replay_path.display()
+            ))
+        }
+        bisect::Outcome::Matches(checkpoints) => {
+            println!("all {} checkpoints match", checkpoints);
+            return Ok(ExitStatus::Ok);
+        }
+        bisect::Outcome::Desync(desync) => desync,
+    };
+    println!(
+        "the checkpoint at frame {} doesn't match: recorded {:016x}, replayed {:016x}",
+        desync.bad_frame, desync.expected, desync.actual
+    );
+    if desync.good_frame == 0 {
+        println!(
+            "it went wrong in frames 1-{}, before the first checkpoint",
+            desync.bad_frame
+        );
+    } else {
+        println!(
+            "it went wrong in frames {}-{}, the checkpoint at frame {} still matched",
+            desync.good_frame + 1,
+            desync.bad_frame,
+            desync.good_frame
+        );
+    }
+    println!("({} runs of the replay)", desync.runs);
+    println!(
+        "what changed from frame {} to {}:",
+        desync.good_frame, desync.bad_frame
+    );
+    for line in bisect::differences(&desync.good, &desync.bad) {
+        println!("  {}", line);
+    }
+
+    for (state, name, frame) in [
+        (&desync.good, "good", desync.good_frame),
+        (&desync.bad, "bad", desync.bad_frame),
+    ] {
+        let path = replay_path.with_extension(format!("{}.state", name));
+        state
+            .save(&path)
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        println!("wrote {} (frame {})", path.display(), frame);
+    }
+    Ok(ExitStatus::CheckFailed)
+}
+
+fn repro_command(args: &[String]) -> Result<ExitStatus, String> {
+    let [bundle_path, rom_path] = match args {
+        [bundle, rom, ..] if !bundle.starts_with("--") && !rom.starts_with("--") => {
+            [Path::new(bundle), Path::new(rom)]
+        }
+        _ => return Err(USAGE.to_string()),
+    };
+    let bundle =
+        Bundle::load(bundle_path).map_err(|err| format!("{}: {}", bundle_path.display(), err))?;
+    let rom = fs::read(rom_path).map_err(|err| format!("{}: {}", rom_path.display(), err))?;
+    if sha1::hex(&rom) != bundle.sha1 {
+        return Err(format!(
+            "{}: the bundle is for a different ROM, {} with SHA-1 {}",
+            rom_path.display(),
+            bundle.rom,
+            bundle.sha1
+        ));
+    }
+
+    // the machine flags it crashed with, not whatever the config says here
+    let machine = MachineOptions::parse(&bundle.args, &Config::default())?;
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&rom)
+        .map_err(|err| format!("{}: {}", rom_path.display(), err))?;
+    machine.apply(&mut cpu);
+    cpu.overrides = Overrides::default();
+    for rule in &bundle.opcodes {
+        cpu.overrides
+            .push(*rule)
+            .map_err(|rule| format!("too many opcode overrides at {}", rule))?;
+    }
+    let from_start = args.iter().any(|arg| arg == "--from-start");
+    let (first, state) = match &bundle.snapshot {
+        Some((frame, state)) if !from_start => (*frame, state),
+        _ => (0, &bundle.start),
+    };
+    state.restore(&mut cpu);
+    println!(
+        "{}: {} frames from frame {} of {}",
+        bundle_path.display(),
+        bundle.replay.frames - first,
+        first,
+        bundle.replay.frames
+    );
+
+    let mut player = Player::new(&bundle.replay);
+    for _ in 0..first {
+        player.frame();
+    }
+    let mut frame = first;
+    let mut desync = None;
+    let run = headless::resume(cpu, bundle.replay.frames - first, |cpu| {
+        let recorded = bundle.replay.hashes.iter().find(|&&(at, _)| at == frame);
+        if let (None, Some(&(at, hash))) = (desync, recorded) {
+            if replay::state_hash(cpu) != hash {
+                desync = Some(at);
+            }
+        }
+        cpu.keys = player.frame().unwrap_or([false; 16]);
+        frame += 1;
+    });
+
+    let got = match run.outcome {
+        Outcome::Error(err) => format!("{:#05x}: {}", run.cpu.program_counter, err),
+        Outcome::Halted => "halted".to_string(),
+        Outcome::FramesElapsed => "no error".to_string(),
+    };
+    if got == bundle.error && first + run.frames == bundle.replay.frames {
+        println!("reproduced after {} frames: {}", first + run.frames, got);
+        return Ok(ExitStatus::Ok);
+    }
+    println!(
+        "not reproduced: {} after {} frames, the player got {} at frame {}",
+        got,
+        first + run.frames,
+        bundle.error,
+        bundle.replay.frames
+    );
+    if let Some(frame) = desync {
+        println!(
+            "the machine stopped matching the recording by frame {}",
+            frame
+        );
+    }
+    Ok(ExitStatus::CheckFailed)
+}
+
+fn info_command(args: &[String]) -> Result<ExitStatus, String> {
+    let roms = rom_paths(args)?;
+    let results = batch::run(&roms, |path| fs::read(path).map(|rom| lint::info(&rom)));
+
+    let mut status = ExitStatus::Ok;
+    for (path, info) in roms.iter().zip(results) {
+        match info {
+            Ok(info) => println!("{}: {}", path.display(), info),
+            Err(err) => {
+                eprintln!("{}: {}", path.display(), err);
+                status = ExitStatus::Usage;
+            }
+        }
+    }
+    Ok(status)
+}
+
+fn list_gamepads() -> Result<ExitStatus, String> {
+    let pads = gamepad::list();
+    if pads.is_empty() {
+        println!("no gamepads found");
+    }
+    for pad in pads {
+        println!("{}  {}", pad.path.display(), pad.name);
+    }
+    Ok(ExitStatus::Ok)
+}
+
+fn demo() -> Result<ExitStatus, String> {
+    let mut cpu = Cpu::new();
     cpu.registers[0] = 5;
     cpu.registers[1] = 10;
 
-    let mem = &mut cpu.memory;
-    mem[0x000] = 0x21;
-    mem[0x001] = 0x00;
-    mem[0x002] = 0x21;
-    mem[0x003] = 0x00;
-    mem[0x004] = 0x00;
-    mem[0x005] = 0x00;
-
-    mem[0x100] = 0x80;
-    mem[0x101] = 0x14;
-    mem[0x102] = 0x80;
-    mem[0x103] = 0x14;
-    mem[0x104] = 0x00;
-    mem[0x105] = 0xEE;
+    cpu.memory
+        .load(0x000, &[0x21, 0x00, 0x21, 0x00, 0x00, 0x00])
+        .expect("demo program fits in memory");
+    cpu.memory
+        .load(0x100, &[0x80, 0x14, 0x80, 0x14, 0x00, 0xEE])
+        .expect("demo program fits in memory");
 
-    cpu.run();
+    cpu.run().map_err(|err| err.to_string())?;
 
     assert_eq!(cpu.registers[0], 45);
     println!("5 + (10 * 2) + (10 * 2) = {}", cpu.registers[0]);
+    Ok(ExitStatus::Ok)
 }
@@ -0,0 +1,295 @@
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+#[cfg(feature = "alloc")]
+use core::cell::RefCell;
+
+#[cfg(feature = "devices")]
+use crate::device::Shared;
+use crate::error::Error;
+
+pub const MEMORY_SIZE: usize = 0x1000;
+
+/// Programs are loaded here; everything below is reserved for the interpreter and fonts.
+pub const PROGRAM_START: usize = 0x200;
+
+/// Default address of the debug print mailbox, the last byte of the interpreter area.
+pub const MAILBOX_ADDR: usize = 0x1FF;
+
+/// The 4K memory bus. Every access is bounds checked.
+#[derive(Clone)]
+pub struct Memory {
+    bytes: [u8; MEMORY_SIZE],
+    /// Trap writes below PROGRAM_START.
+    pub write_protect: bool,
+    /// When set, bytes written to this address are collected as debug output
+    /// instead of being stored, so homebrew ROMs can print to the host.
+    #[cfg(feature = "alloc")]
+    pub mailbox: Option<usize>,
+    #[cfg(feature = "alloc")]
+    mailbox_line: Vec<u8>,
+    #[cfg(feature = "alloc")]
+    mailbox_lines: Vec<String>,
+    /// When set, the program's writes are kept for `take_writes`, e.g. for the
+    /// write handlers of scripts.
+    #[cfg(feature = "alloc")]
+    pub log_writes: bool,
+    #[cfg(feature = "alloc")]
+    writes: Vec<(usize, u8)>,
+    /// The same for reads, for the remote debugger's watchpoints. Reads only
+    /// borrow the memory, hence the cell.
+    #[cfg(feature = "alloc")]
+    pub log_reads: bool,
+    #[cfg(feature = "alloc")]
+    reads: RefCell<Vec<usize>>,
+    /// Pseudo-devices and the addresses they start at, see `device`.
+    #[cfg(feature = "devices")]
+    devices: Vec<(usize, Shared)>,
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Memory {
+    pub fn new() -> Memory {
+        Memory {
+            bytes: [0; MEMORY_SIZE],
+            write_protect: false,
+            #[cfg(feature = "alloc")]
+            mailbox: None,
+            #[cfg(feature = "alloc")]
+            mailbox_line: Vec::new(),
+            #[cfg(feature = "alloc")]
+            mailbox_lines: Vec::new(),
+            #[cfg(feature = "alloc")]
+            log_writes: false,
+            #[cfg(feature = "alloc")]
+            writes: Vec::new(),
+            #[cfg(feature = "alloc")]
+            log_reads: false,
+            #[cfg(feature = "alloc")]
+            reads: RefCell::new(Vec::new()),
+            #[cfg(feature = "devices")]
+            devices: Vec::new(),
+        }
+    }
+
+    pub fn read_byte(&self, addr: usize) -> Result<u8, Error> {
+        #[cfg(feature = "devices")]
+        let byte = match self.device_at(addr) {
+            Some((start, device)) => lock(device).read(addr - start),
+            None => self.peek_byte(addr)?,
+        };
+        #[cfg(not(feature = "devices"))]
+        let byte = self.peek_byte(addr)?;
+        #[cfg(feature = "alloc")]
+        if self.log_reads {
+            self.reads.borrow_mut().push(addr);
+        }
+        Ok(byte)
+    }
+
+    /// Reads without logging, for fetching opcodes.
+    pub fn peek_byte(&self, addr: usize) -> Result<u8, Error> {
+        self.bytes
+            .get(addr)
+            .copied()
+            .ok_or(Error::AddressOutOfBounds { addr })
+    }
+
+    /// Reads a big endian word, like opcodes are stored. Not logged either.
+    pub fn read_word(&self, addr: usize) -> Result<u16, Error> {
+        let hi = self.peek_byte(addr)? as u16;
+        let lo = self.peek_byte(addr + 1)? as u16;
+        Ok((hi << 8) | lo)
+    }
+
+    pub fn write_byte(&mut self, addr: usize, value: u8) -> Result<(), Error> {
+        #[cfg(feature = "alloc")]
+        if self.mailbox == Some(addr) {
+            self.post(value);
+            return Ok(());
+        }
+        #[cfg(feature = "devices")]
+        if let Some((start, device)) = self.device_at(addr) {
+            lock(device).write(addr - start, value);
+            return Ok(());
+        }
+        if self.write_protect && addr < PROGRAM_START {
+            return Err(Error::ProtectedWrite { addr });
+        }
+
+        let byte = self
+            .bytes
+            .get_mut(addr)
+            .ok_or(Error::AddressOutOfBounds { addr })?;
+        *byte = value;
+        #[cfg(feature = "alloc")]
+        if self.log_writes {
+            self.writes.push((addr, value));
+        }
+        Ok(())
+    }
+
+    /// Copies `data` into memory starting at `addr`. This is a host-side operation,
+    /// so it ignores write protection (fonts live in the protected area).
+    pub fn load(&mut self, addr: usize, data: &[u8]) -> Result<(), Error> {
+        let end = addr + data.len();
+        if end > MEMORY_SIZE {
+            return Err(Error::AddressOutOfBounds { addr: end - 1 });
+        }
+
+        self.bytes[addr..end].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// A newline or NUL ends the current line.
+    #[cfg(feature = "alloc")]
+    fn post(&mut self, byte: u8) {
+        match byte {
+            b'\n' | 0 => {
+                let line = String::from_utf8_lossy(&self.mailbox_line).into_owned();
+                self.mailbox_lines.push(line);
+                self.mailbox_line.clear();
+            }
+            byte => self.mailbox_line.push(byte),
+        }
+    }
+
+    /// Takes the lines printed through the mailbox so far, and through any
+    /// console devices. With `flush`, an unterminated last line is returned
+    /// as well.
+    #[cfg(feature = "alloc")]
+    pub fn take_mailbox_lines(&mut self, flush: bool) -> Vec<String> {
+        if flush && !self.mailbox_line.is_empty() {
+            self.post(b'\n');
+        }
+        #[cfg(feature = "devices")]
+        for (_, device) in &self.devices {
+            let lines = lock(device).take_output(flush);
+            self.mailbox_lines.extend(lines);
+        }
+        core::mem::take(&mut self.mailbox_lines)
+    }
+
+    /// Puts `device` at `addr`, in front of the memory there. Where two
+    /// overlap, the one mapped first wins.
+    #[cfg(feature = "devices")]
+    pub fn map(&mut self, addr: usize, device: Shared) -> Result<(), Error> {
+        let end = addr + lock(&device).size();
+        if end > MEMORY_SIZE {
+            return Err(Error::AddressOutOfBounds { addr: end - 1 });
+        }
+        self.devices.push((addr, device));
+        Ok(())
+    }
+
+    #[cfg(feature = "devices")]
+    pub fn unmap_all(&mut self) {
+        self.devices.clear();
+    }
+
+    #[cfg(feature = "devices")]
+    fn device_at(&self, addr: usize) -> Option<(usize, &Shared)> {
+        self.devices
+            .iter()
+            .find(|(start, device)| (*start..start + lock(device).size()).contains(&addr))
+            .map(|(start, device)| (*start, device))
+    }
+
+    /// Takes the addresses and values written since the last call.
+    #[cfg(feature = "alloc")]
+    pub fn take_writes(&mut self) -> Vec<(usize, u8)> {
+        core::mem::take(&mut self.writes)
+    }
+
+    /// Takes the addresses read since the last call.
+    #[cfg(feature = "alloc")]
+    pub fn take_reads(&mut self) -> Vec<usize> {
+        core::mem::take(self.reads.get_mut())
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn clear(&mut self) {
+        self.bytes = [0; MEMORY_SIZE];
+    }
+}
+
+/// A device that panicked while locked is still usable, it only holds bytes.
+#[cfg(feature = "devices")]
+fn lock(device: &Shared) -> std::sync::MutexGuard<'_, dyn crate::device::Device + 'static> {
+    device.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// A set of addresses, a bit each, so it needs no allocation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressSet {
+    bits: [u64; MEMORY_SIZE / 64],
+}
+
+impl Default for AddressSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AddressSet {
+    pub fn new() -> AddressSet {
+        AddressSet {
+            bits: [0; MEMORY_SIZE / 64],
+        }
+    }
+
+    /// Addresses past the end of memory are left out.
+    pub fn insert(&mut self, addr: usize) {
+        if addr < MEMORY_SIZE {
+            self.bits[addr / 64] |= 1 << (addr % 64);
+        }
+    }
+
+    pub fn contains(&self, addr: usize) -> bool {
+        addr < MEMORY_SIZE && self.bits[addr / 64] & (1 << (addr % 64)) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|&word| word == 0)
+    }
+
+    pub fn clear(&mut self) {
+        self.bits = [0; MEMORY_SIZE / 64];
+    }
+
+    /// In ascending order.
+    pub fn iter(&self) -> Addresses<'_> {
+        Addresses { set: self, next: 0 }
+    }
+}
+
+impl<'a> IntoIterator for &'a AddressSet {
+    type Item = usize;
+    type IntoIter = Addresses<'a>;
+
+    fn into_iter(self) -> Addresses<'a> {
+        self.iter()
+    }
+}
+
+pub struct Addresses<'a> {
+    set: &'a AddressSet,
+    next: usize,
+}
+
+impl Iterator for Addresses<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let found = (self.next..MEMORY_SIZE).find(|&addr| self.set.contains(addr))?;
+        self.next = found + 1;
+        Some(found)
+    }
+}
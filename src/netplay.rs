@@ -0,0 +1,309 @@
+//! Two players on one keypad over the network, for the two-player ROMs
+//! (most Pong variants): the host runs the machine as usual and the guest
+//! only shows it, sending its key presses back.
+//!
+//! Everything is plain TCP and non-blocking, polled once a frame like the
+//! remote debugger. The guest starts with `C8NP`, a version byte and the
+//! `replay::rom_hash` of its ROM, and the host answers with one byte, 1 to
+//! play or 0 for a different ROM. After that every host frame is
+//!
+//! ```text
+//! frame number (u32 BE) | sound on (u8) | the screen, 1 bit a pixel (256 bytes)
+//! ```
+//!
+//! and the guest answers each one it showed with that frame number and its
+//! keys (u16 LE, bit n for key n).
+//!
+//! A key press only takes effect `delay` frames after the frame it was
+//! pressed on, for both players: the host's presses wait in a queue, and the
+//! guest's have spent part of the delay on the way already. That way neither
+//! player is ahead as long as the round trip fits in the delay.
+
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::display::{Display, HEIGHT, WIDTH};
+use crate::replay;
+
+/// 50ms at 60 frames a second, enough for a LAN or a nearby server.
+pub const DEFAULT_INPUT_DELAY: u32 = 3;
+
+const MAGIC: &[u8; 4] = b"C8NP";
+const VERSION: u8 = 1;
+const HELLO_LEN: usize = 4 + 1 + 8;
+const SCREEN_LEN: usize = WIDTH * HEIGHT / 8;
+const FRAME_LEN: usize = 4 + 1 + SCREEN_LEN;
+const KEYS_LEN: usize = 4 + 2;
+/// How long joining waits for the host to answer.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Something the players should hear about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Notice {
+    Joined(SocketAddr),
+    /// The guest has a different ROM and was sent away.
+    Refused(SocketAddr),
+    Left,
+}
+
+struct Peer {
+    stream: TcpStream,
+    addr: SocketAddr,
+    input: Vec<u8>,
+    /// Past the handshake.
+    playing: bool,
+}
+
+pub struct Host {
+    listener: TcpListener,
+    guest: Option<Peer>,
+    rom_hash: u64,
+    delay: u32,
+    /// Frames run so far.
+    frame: u32,
+    /// The host's own keys of the last `delay` frames, oldest first.
+    local: VecDeque<[bool; 16]>,
+    /// The guest's keys and the frame they take effect on.
+    pending: VecDeque<(u32, [bool; 16])>,
+    guest_keys: [bool; 16],
+}
+
+impl Host {
+    /// Listens on `addr`, e.g. `0.0.0.0:7000`, for a guest with the same ROM.
+    pub fn bind(addr: &str, rom: &[u8], delay: u32) -> io::Result<Host> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Host {
+            listener,
+            guest: None,
+            rom_hash: replay::rom_hash(rom),
+            delay,
+            frame: 0,
+            local: VecDeque::new(),
+            pending: VecDeque::new(),
+            guest_keys: [false; 16],
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn connected(&self) -> bool {
+        self.guest.as_ref().is_some_and(|guest| guest.playing)
+    }
+
+    /// Lets a guest in and reads what it sent. Call once per frame.
+    pub fn poll(&mut self) -> Option<Notice> {
+        if self.guest.is_none() {
+            if let Ok((stream, addr)) = self.listener.accept() {
+                if stream.set_nonblocking(true).is_ok() && stream.set_nodelay(true).is_ok() {
+                    self.guest = Some(Peer {
+                        stream,
+                        addr,
+                        input: Vec::new(),
+                        playing: false,
+                    });
+                }
+            }
+        }
+
+        let guest = self.guest.as_mut()?;
+        if !receive(guest) {
+            return self.drop_guest(Notice::Left);
+        }
+        if !guest.playing {
+            if guest.input.len() < HELLO_LEN {
+                return None;
+            }
+            let hello: Vec<u8> = guest.input.drain(..HELLO_LEN).collect();
+            let hash = u64::from_be_bytes(hello[5..].try_into().expect("8 bytes"));
+            let welcome = &hello[..4] == MAGIC && hello[4] == VERSION && hash == self.rom_hash;
+            let addr = guest.addr;
+            if !welcome {
+                let _ = guest.stream.write_all(&[0]);
+                return self.drop_guest(Notice::Refused(addr));
+            }
+            if guest.stream.write_all(&[1]).is_err() {
+                return self.drop_guest(Notice::Left);
+            }
+            guest.playing = true;
+            return Some(Notice::Joined(addr));
+        }
+
+        while guest.input.len() >= KEYS_LEN {
+            let message: Vec<u8> = guest.input.drain(..KEYS_LEN).collect();
+            let seen = u32::from_be_bytes(message[..4].try_into().expect("4 bytes"));
+            let keys = unpack_keys(u16::from_le_bytes([message[4], message[5]]));
+            self.pending
+                .push_back((seen.wrapping_add(self.delay), keys));
+        }
+        None
+    }
+
+    fn drop_guest(&mut self, notice: Notice) -> Option<Notice> {
+        let was_playing = self.connected();
+        self.guest = None;
+        self.pending.clear();
+        self.guest_keys = [false; 16];
+        match notice {
+            Notice::Left if !was_playing => None,
+            notice => Some(notice),
+        }
+    }
+
+    /// The keys to run the next frame with: the host's `local` ones and the
+    /// guest's, each from `delay` frames ago. Playing alone there is no delay.
+    pub fn keys(&mut self, local: [bool; 16]) -> [bool; 16] {
+        self.frame = self.frame.wrapping_add(1);
+        if !self.connected() {
+            self.local.clear();
+            return local;
+        }
+        self.local.push_back(local);
+        let own = if self.local.len() > self.delay as usize {
+            self.local.pop_front().unwrap_or_default()
+        } else {
+            [false; 16]
+        };
+        while let Some(&(due, keys)) = self.pending.front() {
+            // anything late is used right away
+            if due > self.frame {
+                break;
+            }
+            self.guest_keys = keys;
+            self.pending.pop_front();
+        }
+        std::array::from_fn(|key| own[key] || self.guest_keys[key])
+    }
+
+    /// Sends the screen to the guest, once per frame after running it.
+    pub fn send_frame(&mut self, display: &Display, sound: bool) -> Option<Notice> {
+        let guest = self.guest.as_mut().filter(|guest| guest.playing)?;
+        let mut message = Vec::with_capacity(FRAME_LEN);
+        message.extend_from_slice(&self.frame.to_be_bytes());
+        message.push(sound as u8);
+        message.extend(
+            display
+                .pixels()
+                .chunks(8)
+                .map(|bits| bits.iter().fold(0, |byte, &bit| byte << 1 | bit as u8)),
+        );
+        // a guest too slow to keep up is dropped rather than waited for
+        if guest.stream.write_all(&message).is_err() {
+            return self.drop_guest(Notice::Left);
+        }
+        None
+    }
+}
+
+pub struct Guest {
+    stream: TcpStream,
+    input: Vec<u8>,
+    /// The last frame shown, which the keys sent are pressed on.
+    frame: u32,
+    sound: bool,
+}
+
+impl Guest {
+    /// Connects and waits for the host to accept `rom`.
+    pub fn join(addr: &str, rom: &[u8]) -> io::Result<Guest> {
+        let addr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "no address"))?;
+        let mut stream = TcpStream::connect_timeout(&addr, HANDSHAKE_TIMEOUT)?;
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+
+        let mut hello = MAGIC.to_vec();
+        hello.push(VERSION);
+        hello.extend_from_slice(&replay::rom_hash(rom).to_be_bytes());
+        stream.write_all(&hello)?;
+        let mut answer = [0];
+        stream.read_exact(&mut answer)?;
+        if answer[0] != 1 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "the host is playing a different ROM",
+            ));
+        }
+        stream.set_nonblocking(true)?;
+        Ok(Guest {
+            stream,
+            input: Vec::new(),
+            frame: 0,
+            sound: false,
+        })
+    }
+
+    /// Puts the newest frame from the host on `display`, skipping any older
+    /// ones that piled up. Fails once the host is gone.
+    pub fn poll(&mut self, display: &mut Display) -> io::Result<()> {
+        if !read_into(&mut self.stream, &mut self.input) {
+            return Err(io::Error::new(
+                ErrorKind::ConnectionAborted,
+                "the host left",
+            ));
+        }
+
+        let whole = self.input.len() / FRAME_LEN * FRAME_LEN;
+        if whole == 0 {
+            return Ok(());
+        }
+        let newest = &self.input[whole - FRAME_LEN..whole];
+        self.frame = u32::from_be_bytes(newest[..4].try_into().expect("4 bytes"));
+        self.sound = newest[4] != 0;
+        let pixels: Vec<bool> = newest[5..]
+            .iter()
+            .flat_map(|&byte| (0..8).map(move |bit| byte & 0x80 >> bit != 0))
+            .collect();
+        if pixels != display.pixels() {
+            display.set_pixels(&pixels);
+        }
+        self.input.drain(..whole);
+        Ok(())
+    }
+
+    /// Whether the host's sound timer was running on the last frame.
+    pub fn sound(&self) -> bool {
+        self.sound
+    }
+
+    /// Sends the keys held on the frame last shown.
+    pub fn send_keys(&mut self, keys: [bool; 16]) -> io::Result<()> {
+        let mut message = self.frame.to_be_bytes().to_vec();
+        message.extend_from_slice(&pack_keys(keys).to_le_bytes());
+        self.stream.write_all(&message)
+    }
+}
+
+/// Reads whatever arrived; false once the peer is gone.
+fn receive(peer: &mut Peer) -> bool {
+    read_into(&mut peer.stream, &mut peer.input)
+}
+
+fn read_into(stream: &mut TcpStream, input: &mut Vec<u8>) -> bool {
+    let mut buffer = [0; 1024];
+    loop {
+        match stream.read(&mut buffer) {
+            Ok(0) => return false,
+            Ok(len) => input.extend_from_slice(&buffer[..len]),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return true,
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(_) => return false,
+        }
+    }
+}
+
+fn pack_keys(keys: [bool; 16]) -> u16 {
+    keys.iter()
+        .enumerate()
+        .fold(0, |bits, (key, &held)| bits | (held as u16) << key)
+}
+
+fn unpack_keys(bits: u16) -> [bool; 16] {
+    std::array::from_fn(|key| bits & 1 << key != 0)
+}
@@ -0,0 +1,135 @@
+//! Per-opcode overrides, for old ROMs that won't run otherwise: an opcode can
+//! be skipped, like the `0nnn` machine code calls the VIP ran natively, or run
+//! as another one. Patterns are four hex digits with `x` for any digit:
+//!
+//! ```text
+//! 0230  skip the call to 0x230 only
+//! 0xxx  skip every machine code call
+//! 00FB  -> 00E0, x in the replacement keeps the digit of the original
+//! ```
+//!
+//! A machine holds at most `MAX_RULES` of them, in a fixed array so the core
+//! needs no allocation; the first rule that matches wins.
+
+use core::fmt;
+
+pub const MAX_RULES: usize = 16;
+
+/// What a rule does to the opcodes it matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Nop,
+    /// The fixed digits of `value`, and the original's where `keep` has ones.
+    Remap {
+        value: u16,
+        keep: u16,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    /// Ones for the digits the pattern fixes.
+    pub mask: u16,
+    pub value: u16,
+    pub action: Action,
+}
+
+impl Rule {
+    /// A rule from a pattern and `nop` or a replacement, as the config has
+    /// them: `0xxx` and `nop`, say, or `00FB` and `00E0`.
+    pub fn parse(pattern: &str, action: &str) -> Result<Rule, &'static str> {
+        let (mask, value) = parse_pattern(pattern).ok_or("expected 4 hex digits or x")?;
+        let action = if action.eq_ignore_ascii_case("nop") {
+            Action::Nop
+        } else {
+            let (fixed, value) =
+                parse_pattern(action).ok_or("expected \"nop\" or 4 hex digits or x")?;
+            Action::Remap {
+                value,
+                keep: !fixed,
+            }
+        };
+        Ok(Rule {
+            mask,
+            value,
+            action,
+        })
+    }
+
+    pub fn matches(&self, opcode: u16) -> bool {
+        opcode & self.mask == self.value
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_pattern(f, self.mask, self.value)?;
+        match self.action {
+            Action::Nop => write!(f, " nop"),
+            Action::Remap { value, keep } => {
+                write!(f, " -> ")?;
+                write_pattern(f, !keep, value)
+            }
+        }
+    }
+}
+
+/// (mask, value) for a pattern, with the `x` digits zero in both.
+fn parse_pattern(text: &str) -> Option<(u16, u16)> {
+    if text.len() != 4 {
+        return None;
+    }
+    text.chars()
+        .try_fold((0u16, 0u16), |(mask, value), digit| match digit {
+            'x' | 'X' => Some((mask << 4, value << 4)),
+            digit => Some((mask << 4 | 0xF, value << 4 | digit.to_digit(16)? as u16)),
+        })
+}
+
+fn write_pattern(f: &mut fmt::Formatter<'_>, mask: u16, value: u16) -> fmt::Result {
+    for shift in [12, 8, 4, 0] {
+        if mask >> shift & 0xF == 0 {
+            write!(f, "x")?;
+        } else {
+            write!(f, "{:X}", value >> shift & 0xF)?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Overrides {
+    rules: [Option<Rule>; MAX_RULES],
+}
+
+impl Overrides {
+    /// Adds a rule after the ones there, or fails when there are `MAX_RULES`.
+    pub fn push(&mut self, rule: Rule) -> Result<(), Rule> {
+        match self.rules.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(rule);
+                Ok(())
+            }
+            None => Err(rule),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules[0].is_none()
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &Rule> {
+        self.rules.iter().flatten()
+    }
+
+    /// The opcode to run instead of `opcode`, None to skip it.
+    pub fn apply(&self, opcode: u16) -> Option<u16> {
+        let Some(rule) = self.rules().find(|rule| rule.matches(opcode)) else {
+            return Some(opcode);
+        };
+        match rule.action {
+            Action::Nop => None,
+            Action::Remap { value, keep } => Some(value & !keep | opcode & keep),
+        }
+    }
+}
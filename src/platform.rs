@@ -0,0 +1,317 @@
+//! The CHIP-8 family. Each platform extends the instruction set of the ones
+//! before it. Only the CHIP-8 instructions and SUPER-CHIP's big font are
+//! emulated; the other SUPER-CHIP and XO-CHIP ones are recognised so ROMs
+//! using them can be told apart from broken ones.
+//!
+//! `--platform` picks a preset, which bundles an instruction set with the
+//! quirks, speed, font and colours of a particular machine. The presets and
+//! telling which platform a ROM needs are left out of no_std builds.
+
+use core::fmt;
+
+#[cfg(feature = "std")]
+use crate::config::Config;
+#[cfg(feature = "std")]
+use crate::disasm;
+#[cfg(feature = "std")]
+use crate::font::{self, Font};
+#[cfg(feature = "std")]
+use crate::frontend::Rgb;
+#[cfg(feature = "std")]
+use crate::instruction::Instruction;
+#[cfg(feature = "std")]
+use crate::memory::PROGRAM_START;
+#[cfg(feature = "std")]
+use crate::quirks::Quirks;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Platform {
+    /// The COSMAC VIP interpreter.
+    #[default]
+    Chip8,
+    /// The HP-48 port, same instructions with different quirks.
+    Chip48,
+    /// SUPER-CHIP 1.1: hires mode, scrolling, big sprites and font.
+    Schip,
+    /// Octo's XO-CHIP: bit planes, 16 bit addresses and audio patterns.
+    XoChip,
+}
+
+impl Platform {
+    /// The preset to suggest for ROMs that need this platform.
+    pub fn name(self) -> &'static str {
+        match self {
+            Platform::Chip8 => "vip",
+            Platform::Chip48 => "chip48",
+            Platform::Schip => "schip",
+            Platform::XoChip => "xochip",
+        }
+    }
+
+    /// The first platform to have `opcode`, if it is not a CHIP-8 instruction
+    /// but a later one.
+    pub fn introducing(opcode: u16) -> Option<Platform> {
+        match opcode {
+            // scroll down, scroll right/left, exit, lores/hires
+            0x00C0..=0x00CF | 0x00FB..=0x00FF => Some(Platform::Schip),
+            // scroll up
+            0x00D0..=0x00DF => Some(Platform::XoChip),
+            // 16x16 sprites
+            0xD000..=0xDFFF if opcode & 0xF == 0 => Some(Platform::Schip),
+            // save/load vx..vy
+            0x5000..=0x5FFF if matches!(opcode & 0xF, 2 | 3) => Some(Platform::XoChip),
+            // long I, audio pattern
+            0xF000 | 0xF002 => Some(Platform::XoChip),
+            0xF000..=0xFFFF => match opcode & 0xFF {
+                // big font, flag registers
+                0x30 | 0x75 | 0x85 => Some(Platform::Schip),
+                // plane selection, pitch
+                0x01 | 0x3A => Some(Platform::XoChip),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::Chip48 => "CHIP-48",
+            Platform::Schip => "SUPER-CHIP",
+            Platform::XoChip => "XO-CHIP",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A machine to emulate, everything `--platform` sets.
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq, Eq)]
+pub struct Preset {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    /// Instructions per frame.
+    pub speed: usize,
+    pub font: &'static Font,
+    pub foreground: Rgb,
+    pub background: Rgb,
+}
+
+#[cfg(feature = "std")]
+impl Preset {
+    /// Sets everything the preset covers, for settings applied later to override.
+    pub fn apply(&'static self, config: &mut Config) {
+        config.preset = self;
+        config.quirks = self.quirks;
+        config.speed = self.speed;
+        config.font = self.font;
+        config.style.foreground = self.foreground;
+        config.style.background = self.background;
+    }
+}
+
+/// The HP-48's greyish green LCD, for CHIP-48 and SUPER-CHIP.
+#[cfg(feature = "std")]
+const LCD: (Rgb, Rgb) = (Rgb(0x20, 0x28, 0x20), Rgb(0x9c, 0xa8, 0x8c));
+
+/// The first one is the default.
+#[cfg(feature = "std")]
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "vip",
+        description: "the original COSMAC VIP interpreter",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        speed: 10,
+        font: &font::VIP,
+        foreground: Rgb::WHITE,
+        background: Rgb::BLACK,
+    },
+    Preset {
+        name: "chip48",
+        description: "CHIP-48 on the HP-48 calculators",
+        platform: Platform::Chip48,
+        quirks: Quirks::CHIP48,
+        speed: 20,
+        font: &font::CHIP48,
+        foreground: LCD.0,
+        background: LCD.1,
+    },
+    Preset {
+        name: "schip",
+        description: "SUPER-CHIP 1.1 on the HP-48",
+        platform: Platform::Schip,
+        quirks: Quirks::SCHIP,
+        speed: 30,
+        font: &font::CHIP48,
+        foreground: LCD.0,
+        background: LCD.1,
+    },
+    Preset {
+        name: "xochip",
+        description: "XO-CHIP as Octo runs it, in Octo's colours",
+        platform: Platform::XoChip,
+        quirks: Quirks::XOCHIP,
+        speed: 200,
+        font: &font::OCTO,
+        foreground: Rgb(0xff, 0xcc, 0x00),
+        background: Rgb(0x99, 0x66, 0x00),
+    },
+    // its programs start at 0x600, which is not emulated, so this only suits
+    // ports that were moved to 0x200
+    Preset {
+        name: "eti660",
+        description: "the ETI-660 learner's computer, slower than the VIP",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        speed: 8,
+        font: &font::ETI660,
+        foreground: Rgb::WHITE,
+        background: Rgb::BLACK,
+    },
+    Preset {
+        name: "modern",
+        description: "what most ROMs written since the 2010s expect",
+        platform: Platform::Chip8,
+        quirks: Quirks::MODERN,
+        speed: 20,
+        font: &font::CHIP48,
+        foreground: Rgb::WHITE,
+        background: Rgb::BLACK,
+    },
+];
+
+/// `chip8` is taken as `vip`.
+#[cfg(feature = "std")]
+pub fn preset(name: &str) -> Option<&'static Preset> {
+    let name = if name == "chip8" { "vip" } else { name };
+    PRESETS.iter().find(|preset| preset.name == name)
+}
+
+/// The names `preset` accepts, for error messages.
+#[cfg(feature = "std")]
+pub fn preset_names() -> String {
+    let names: Vec<&str> = PRESETS.iter().map(|preset| preset.name).collect();
+    names.join(", ")
+}
+
+/// A reachable instruction from a later platform.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Requirement {
+    pub platform: Platform,
+    pub addr: usize,
+    pub opcode: u16,
+}
+
+/// The latest platform whose instructions `rom` uses, with the first one
+/// found that is not emulated, or else the first one. None for plain CHIP-8
+/// ROMs.
+#[cfg(feature = "std")]
+pub fn required(rom: &[u8]) -> Option<Requirement> {
+    let mut required: Option<Requirement> = None;
+    for addr in disasm::trace(rom).code {
+        let offset = addr - PROGRAM_START;
+        let opcode = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+        let Some(platform) = Platform::introducing(opcode) else {
+            continue;
+        };
+        let found = Requirement {
+            platform,
+            addr,
+            opcode,
+        };
+        if required.is_none_or(|required| {
+            platform > required.platform
+                || (platform == required.platform && required.emulated() && !found.emulated())
+        }) {
+            required = Some(found);
+        }
+    }
+    required
+}
+
+#[cfg(feature = "std")]
+impl Requirement {
+    /// Whether `platform` has the instruction at all.
+    pub fn met_by(&self, platform: Platform) -> bool {
+        self.platform <= platform
+    }
+
+    /// Whether the instruction runs, like SUPER-CHIP's big font does. The
+    /// 00Cx and 00Fx ones decode as 0nnn, which stops on them, and Dxy0 draws
+    /// nothing instead of a 16x16 sprite.
+    pub fn emulated(&self) -> bool {
+        match Instruction::decode(self.opcode) {
+            Ok(Instruction::Sys { .. } | Instruction::Draw { n: 0, .. }) => false,
+            decoded => decoded.is_ok(),
+        }
+    }
+
+    /// What goes wrong running the ROM as `platform`, if anything.
+    pub fn problem(&self, platform: Platform) -> Option<String> {
+        let found = format!(
+            "uses {} instructions ({:04x} at {:#05x})",
+            self.platform, self.opcode, self.addr
+        );
+        if !self.met_by(platform) {
+            Some(format!(
+                "{} but the platform is {}, try --platform {}",
+                found,
+                platform,
+                self.platform.name()
+            ))
+        } else if !self.emulated() {
+            Some(format!("{}, which are not emulated yet", found))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::testing;
+
+    #[test]
+    fn schip_allows_hires_but_does_not_run_it() {
+        let rom = [0x00, 0xFF];
+        let required = required(&rom).unwrap();
+        assert_eq!(required.platform, Platform::Schip);
+        assert!(required
+            .problem(Platform::Chip8)
+            .unwrap()
+            .contains("try --platform schip"));
+        assert!(required
+            .problem(Platform::Schip)
+            .unwrap()
+            .contains("not emulated"));
+
+        let mut cpu = testing::machine(&rom);
+        // all that --platform schip changes on the machine
+        cpu.quirks = preset("schip").unwrap().quirks;
+        assert_eq!(
+            cpu.step(),
+            Err(Error::UnsupportedInstruction {
+                opcode: 0x00FF,
+                platform: Platform::Schip,
+            })
+        );
+    }
+
+    #[test]
+    fn big_font_is_emulated_but_big_sprites_are_not() {
+        let rom = crate::asm::assemble("LD HF, V0").unwrap();
+        assert!(required(&rom).unwrap().emulated());
+        assert_eq!(required(&rom).unwrap().problem(Platform::Schip), None);
+
+        let rom = crate::asm::assemble("DRW V0, V1, 0").unwrap();
+        assert!(!required(&rom).unwrap().emulated());
+    }
+}
@@ -0,0 +1,181 @@
+//! Many headless machines at once, for when one run isn't the point: fuzzing
+//! a ROM with random key presses, RL rollouts, a compatibility sweep over a
+//! collection. Every `Job` is a machine with its own ROM and input script,
+//! the pool runs them on all cores and gives back what each one did:
+//!
+//! ```no_run
+//! use chip_8_emulate::headless;
+//! use chip_8_emulate::pool::{Job, MachinePool};
+//! use chip_8_emulate::replay::KeyEvent;
+//!
+//! let rom = std::fs::read("pong.ch8").unwrap();
+//! let jobs = (0..16u8).map(|key| {
+//!     let mut cpu = headless::machine();
+//!     cpu.load_rom(&rom).unwrap();
+//!     let press = KeyEvent { frame: 10, key, pressed: true };
+//!     Job::new(cpu, 600).keys(vec![press])
+//! });
+//! for report in MachinePool::new().run(jobs.collect()) {
+//!     println!("{:?} {:016x?}", report.outcome, report.hashes.last());
+//! }
+//! ```
+//!
+//! The machines share nothing, so the results are the same whatever the
+//! number of threads and come back in the order the jobs went in.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::assertion::Assertion;
+use crate::cpu::Cpu;
+use crate::headless::{self, Outcome};
+use crate::replay::{KeyEvent, Player, Replay};
+
+/// One machine to run.
+pub struct Job {
+    /// Set up and with its program in memory.
+    pub cpu: Cpu,
+    pub frames: usize,
+    /// Key changes in frame order, applied at the start of their frame.
+    pub keys: Vec<KeyEvent>,
+}
+
+impl Job {
+    /// `frames` of `cpu` with nothing pressed.
+    pub fn new(cpu: Cpu, frames: usize) -> Job {
+        Job {
+            cpu,
+            frames,
+            keys: Vec::new(),
+        }
+    }
+
+    /// Plays a recording, for as long as it is. The machine should have had
+    /// `Replay::apply` done to it.
+    pub fn replay(cpu: Cpu, replay: &Replay) -> Job {
+        Job {
+            cpu,
+            frames: replay.frames,
+            keys: replay.events.clone(),
+        }
+    }
+
+    pub fn keys(mut self, keys: Vec<KeyEvent>) -> Job {
+        self.keys = keys;
+        self
+    }
+}
+
+/// What a job did.
+pub struct Report {
+    /// The machine as it ended.
+    pub cpu: Cpu,
+    /// Frames actually run, fewer than asked for if it halted or failed.
+    pub frames: usize,
+    pub outcome: Outcome,
+    /// What a test ROM reported, with assertions on.
+    pub assertion: Option<Assertion>,
+    /// `Display::hash` after each frame the pool was asked to hash.
+    pub hashes: Vec<u64>,
+}
+
+pub struct MachinePool {
+    workers: usize,
+    hash_every: usize,
+}
+
+impl Default for MachinePool {
+    fn default() -> Self {
+        MachinePool::new()
+    }
+}
+
+impl MachinePool {
+    /// A thread per core, hashing the screen after every frame.
+    pub fn new() -> MachinePool {
+        MachinePool {
+            workers: thread::available_parallelism().map_or(1, |count| count.get()),
+            hash_every: 1,
+        }
+    }
+
+    /// At most `workers` threads, 1 runs everything on the calling one.
+    pub fn workers(mut self, workers: usize) -> MachinePool {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Hashes the screen every `frames` frames instead, 0 for never.
+    pub fn hash_every(mut self, frames: usize) -> MachinePool {
+        self.hash_every = frames;
+        self
+    }
+
+    /// Runs every job and returns their reports in the same order.
+    pub fn run(&self, jobs: Vec<Job>) -> Vec<Report> {
+        let workers = self.workers.min(jobs.len());
+        if workers <= 1 {
+            return jobs.into_iter().map(|job| self.run_one(job)).collect();
+        }
+
+        // a thread takes the next job whenever it is done with one, like
+        // `batch::run`, since some machines halt early and others don't
+        let next = AtomicUsize::new(0);
+        let slots: Vec<Mutex<Option<Job>>> =
+            jobs.into_iter().map(|job| Mutex::new(Some(job))).collect();
+        let reports: Vec<Mutex<Option<Report>>> = slots.iter().map(|_| Mutex::new(None)).collect();
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                let (slots, reports, next) = (&slots, &reports, &next);
+                scope.spawn(move || loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(slot) = slots.get(i) else {
+                        break;
+                    };
+                    let job = slot.lock().unwrap().take().expect("each job is taken once");
+                    *reports[i].lock().unwrap() = Some(self.run_one(job));
+                });
+            }
+        });
+        reports
+            .into_iter()
+            .map(|report| report.into_inner().unwrap().expect("every job is run"))
+            .collect()
+    }
+
+    fn run_one(&self, job: Job) -> Report {
+        let replay = Replay {
+            rom: 0,
+            seed: 0,
+            speed: job.cpu.speed,
+            quirks: job.cpu.quirks,
+            events: job.keys,
+            hashes: Vec::new(),
+            frames: job.frames,
+        };
+        let mut player = Player::new(&replay);
+        let mut hashes = Vec::new();
+        let mut frame = 0usize;
+        let run = headless::resume_with(
+            job.cpu,
+            job.frames,
+            |cpu| cpu.keys = player.frame().unwrap_or(cpu.keys),
+            |cpu| {
+                let result = cpu.run_frame();
+                frame += 1;
+                if self.hash_every > 0 && frame.is_multiple_of(self.hash_every) {
+                    hashes.push(cpu.display.hash());
+                }
+                result
+            },
+        );
+        Report {
+            assertion: run.cpu.assertion.clone(),
+            cpu: run.cpu,
+            frames: run.frames,
+            outcome: run.outcome,
+            hashes,
+        }
+    }
+}
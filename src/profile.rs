@@ -0,0 +1,210 @@
+//! Where a ROM spends its time: how often each address ran, the instruction
+//! mix by kind, and the loops, counted at every backward jump. Set
+//! `Cpu::profile` to start counting and read the report when done:
+//!
+//! ```text
+//! 1843210 instructions
+//! hot addresses:
+//!   0x21e  18.2%  335481  DRW V0, V1, 5
+//! hot loops:
+//!   0x21a-0x226  67096 times  91.0%
+//! instruction mix:
+//!   Dxyn  18.2%  335481
+//! ```
+//!
+//! A loop's share is of everything run inside it, nested loops included.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+#[cfg(feature = "std")]
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::fmt::Write;
+
+use crate::instruction::Instruction;
+#[cfg(feature = "std")]
+use crate::json::Value;
+#[cfg(feature = "std")]
+use crate::memory::Memory;
+use crate::memory::MEMORY_SIZE;
+
+/// Entries in each list of the report.
+#[cfg(feature = "std")]
+const SHOWN: usize = 10;
+
+pub struct Profile {
+    pub instructions: u64,
+    /// Executions per address.
+    counts: Box<[u64; MEMORY_SIZE]>,
+    /// Executions per `Instruction::pattern`.
+    kinds: BTreeMap<&'static str, u64>,
+    /// How often the jump at the end went back to the start, by (start, end).
+    loops: BTreeMap<(usize, usize), u64>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile {
+            instructions: 0,
+            counts: Box::new([0; MEMORY_SIZE]),
+            kinds: BTreeMap::new(),
+            loops: BTreeMap::new(),
+        }
+    }
+}
+
+impl Profile {
+    /// Counts the opcode at `pc`, about to run.
+    pub fn record(&mut self, pc: usize, opcode: u16) {
+        self.instructions += 1;
+        if let Some(count) = self.counts.get_mut(pc) {
+            *count += 1;
+        }
+        let Ok(instruction) = Instruction::decode(opcode) else {
+            return;
+        };
+        *self.kinds.entry(instruction.pattern()).or_default() += 1;
+        if let Instruction::Jump { addr } = instruction {
+            if addr as usize <= pc {
+                *self.loops.entry((addr as usize, pc)).or_default() += 1;
+            }
+        }
+    }
+
+    pub fn count(&self, addr: usize) -> u64 {
+        self.counts.get(addr).copied().unwrap_or(0)
+    }
+
+    /// The addresses that ran, the most often first.
+    pub fn hot_addresses(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        let mut hot: Vec<(usize, u64)> = (0..MEMORY_SIZE)
+            .map(|addr| (addr, self.counts[addr]))
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        hot.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hot.into_iter()
+    }
+
+    /// Instruction kinds and how often they ran, the most common first.
+    pub fn mix(&self) -> impl Iterator<Item = (&'static str, u64)> + '_ {
+        let mut mix: Vec<(&'static str, u64)> = self
+            .kinds
+            .iter()
+            .map(|(&kind, &count)| (kind, count))
+            .collect();
+        mix.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        mix.into_iter()
+    }
+
+    /// Loops as (start, end, times round, instructions run inside), the
+    /// busiest first.
+    pub fn loops(&self) -> impl Iterator<Item = (usize, usize, u64, u64)> + '_ {
+        let mut loops: Vec<(usize, usize, u64, u64)> = self
+            .loops
+            .iter()
+            .map(|(&(start, end), &times)| {
+                let inside = self.counts[start..=end].iter().sum();
+                (start, end, times, inside)
+            })
+            .collect();
+        loops.sort_by(|a, b| b.3.cmp(&a.3).then(a.0.cmp(&b.0)));
+        loops.into_iter()
+    }
+
+    #[cfg(feature = "std")]
+    fn share(&self, count: u64) -> f64 {
+        count as f64 * 100.0 / self.instructions.max(1) as f64
+    }
+
+    /// The report as text, with the instructions at the hot addresses as
+    /// `memory` has them now.
+    #[cfg(feature = "std")]
+    pub fn report(&self, memory: &Memory) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "{} instructions", self.instructions);
+        let _ = writeln!(out, "hot addresses:");
+        for (addr, count) in self.hot_addresses().take(SHOWN) {
+            let _ = writeln!(
+                out,
+                "  {:#05x}  {:5.1}%  {}  {}",
+                addr,
+                self.share(count),
+                count,
+                disassemble(memory, addr)
+            );
+        }
+        let _ = writeln!(out, "hot loops:");
+        for (start, end, times, inside) in self.loops().take(SHOWN) {
+            let _ = writeln!(
+                out,
+                "  {:#05x}-{:#05x}  {} times  {:.1}%",
+                start,
+                end,
+                times,
+                self.share(inside)
+            );
+        }
+        let _ = writeln!(out, "instruction mix:");
+        for (kind, count) in self.mix() {
+            let _ = writeln!(out, "  {}  {:5.1}%  {}", kind, self.share(count), count);
+        }
+        out
+    }
+
+    /// The same as JSON, with every address and loop rather than the top ones.
+    #[cfg(feature = "std")]
+    pub fn to_json(&self, memory: &Memory) -> Value {
+        let object = |entries: Vec<(&str, Value)>| {
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key.to_string(), value))
+                    .collect(),
+            )
+        };
+        let number = |n: u64| Value::Number(n as f64);
+        let addresses = self
+            .hot_addresses()
+            .map(|(addr, count)| {
+                object(vec![
+                    ("addr", number(addr as u64)),
+                    ("count", number(count)),
+                    ("instruction", Value::String(disassemble(memory, addr))),
+                ])
+            })
+            .collect();
+        let loops = self
+            .loops()
+            .map(|(start, end, times, inside)| {
+                object(vec![
+                    ("start", number(start as u64)),
+                    ("end", number(end as u64)),
+                    ("times", number(times)),
+                    ("instructions", number(inside)),
+                ])
+            })
+            .collect();
+        let mix = self
+            .mix()
+            .map(|(kind, count)| (kind.to_string(), number(count)))
+            .collect();
+        object(vec![
+            ("instructions", number(self.instructions)),
+            ("addresses", Value::Array(addresses)),
+            ("loops", Value::Array(loops)),
+            ("mix", Value::Object(mix)),
+        ])
+    }
+}
+
+#[cfg(feature = "std")]
+fn disassemble(memory: &Memory, addr: usize) -> String {
+    match memory.read_word(addr) {
+        Ok(opcode) => match Instruction::decode(opcode) {
+            Ok(instruction) => instruction.to_string(),
+            Err(_) => format!("{:04x}", opcode),
+        },
+        Err(_) => String::new(),
+    }
+}
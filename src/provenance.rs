@@ -0,0 +1,76 @@
+//! Which Dxyn drew each pixel. With `Cpu::provenance` set, every pixel a
+//! sprite turns on or off remembers the instruction and the frame, so a
+//! glitch on screen can be traced back to the code that drew it: the debug
+//! panel's pixel picking says so, and a `chip8::display` trace log line names
+//! whose pixels each sprite drew over:
+//!
+//! ```text
+//! TRACE chip8::display: 0x21c drew over pixels from 0x20a
+//! ```
+//!
+//! Clearing the screen leaves the pixels' origins alone, they are off but the
+//! Dxyn that last drew them is still the one to blame.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::display::{Display, HEIGHT, WIDTH};
+
+/// Where a pixel was last drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Origin {
+    /// The address of the Dxyn.
+    pub pc: usize,
+    /// The frame it ran in, counting timer ticks from 0.
+    pub frame: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Provenance {
+    pixels: Vec<Option<Origin>>,
+}
+
+impl Default for Provenance {
+    fn default() -> Self {
+        Provenance {
+            pixels: vec![None; WIDTH * HEIGHT],
+        }
+    }
+}
+
+impl Provenance {
+    /// A sprite drawn by `origin` made the screen `after` out of `before`.
+    /// Gives the other instructions whose pixels it changed, each once.
+    pub fn record(&mut self, before: &Display, after: &Display, origin: Origin) -> Vec<usize> {
+        let mut over = Vec::new();
+        let changed = before
+            .pixels()
+            .iter()
+            .zip(after.pixels())
+            .map(|(a, b)| a != b);
+        for (slot, _) in self
+            .pixels
+            .iter_mut()
+            .zip(changed)
+            .filter(|(_, changed)| *changed)
+        {
+            if let Some(previous) = slot.filter(|previous| previous.pc != origin.pc) {
+                if !over.contains(&previous.pc) {
+                    over.push(previous.pc);
+                }
+            }
+            *slot = Some(origin);
+        }
+        over
+    }
+
+    /// What last drew the pixel, None if no sprite has.
+    pub fn get(&self, x: usize, y: usize) -> Option<Origin> {
+        self.pixels[y * WIDTH + x]
+    }
+
+    /// Forgets every origin, for a machine that starts over.
+    pub fn clear(&mut self) {
+        self.pixels.fill(None);
+    }
+}
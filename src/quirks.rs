@@ -0,0 +1,85 @@
+/// Behaviours that differ between CHIP-8 interpreters. ROMs written for one
+/// interpreter can misbehave on another if these don't match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8xy6/8xyE shift vy into vx instead of shifting vx in place.
+    pub shift_uses_vy: bool,
+    /// Fx55/Fx65 leave I pointing past the last register stored/loaded.
+    pub load_store_increments_i: bool,
+    /// Bnnn is read as Bxnn and jumps to xnn + vx instead of nnn + v0.
+    pub jump_uses_vx: bool,
+    /// 8xy1/8xy2/8xy3 reset vf to 0.
+    pub logic_resets_vf: bool,
+    /// Sprites crossing the screen edge wrap around to the other side instead of being clipped.
+    pub wrap_sprites: bool,
+}
+
+impl Quirks {
+    /// The original COSMAC VIP interpreter.
+    pub const CHIP8: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store_increments_i: true,
+        jump_uses_vx: false,
+        logic_resets_vf: true,
+        wrap_sprites: false,
+    };
+
+    /// CHIP-48 on the HP-48, which most later interpreters copied.
+    pub const CHIP48: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: false,
+        jump_uses_vx: true,
+        logic_resets_vf: false,
+        wrap_sprites: false,
+    };
+
+    /// SUPER-CHIP 1.1.
+    pub const SCHIP: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: false,
+        jump_uses_vx: true,
+        logic_resets_vf: false,
+        wrap_sprites: false,
+    };
+
+    /// XO-CHIP, as Octo runs it.
+    pub const XOCHIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store_increments_i: true,
+        jump_uses_vx: false,
+        logic_resets_vf: false,
+        wrap_sprites: true,
+    };
+
+    /// What ROMs written for today's emulators assume: shifts in place, I left
+    /// alone, Bnnn as nnn + v0, vf untouched by logic ops and clipped sprites.
+    pub const MODERN: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: false,
+        jump_uses_vx: false,
+        logic_resets_vf: false,
+        wrap_sprites: false,
+    };
+}
+
+/// Profile names accepted by `Quirks::profile`.
+pub const PROFILES: &[&str] = &["chip8", "chip48", "schip", "xochip", "modern"];
+
+impl Quirks {
+    pub fn profile(name: &str) -> Option<Quirks> {
+        match name {
+            "chip8" => Some(Quirks::CHIP8),
+            "chip48" => Some(Quirks::CHIP48),
+            "schip" => Some(Quirks::SCHIP),
+            "xochip" => Some(Quirks::XOCHIP),
+            "modern" => Some(Quirks::MODERN),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::CHIP8
+    }
+}
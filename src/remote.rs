@@ -0,0 +1,810 @@
+//! A minimal GDB remote serial protocol server, so external debuggers can
+//! attach to a running emulator over TCP.
+//!
+//! Everything happens on the emulation thread: the socket is non-blocking and
+//! `poll` is called once per frame. A debugger that connects stops the
+//! machine, after that it runs only through `c` and `s`.
+//!
+//! Supported packets: `?`, `g`/`G`, `p`/`P`, `m`/`M`, `Z0`/`z0` (and `Z1`),
+//! `Z2`-`Z4`/`z2`-`z4` watchpoints, `s`, `c`, `D`, `k`, `qSupported` and
+//! `qRcmd`; Ctrl-C interrupts a running machine. Anything else gets the empty
+//! "unsupported" reply.
+//!
+//! Watchpoints stop the machine after the instruction that read or wrote the
+//! memory. The program's memory accesses are logged by `Memory` while any are
+//! set; register accesses are worked out from the instruction about to run.
+//! GDB can only watch memory, the monitor commands (`monitor help`) also watch
+//! V0-VF and I, and can wait for a particular value:
+//!
+//! ```text
+//! monitor watch v3 write = 0x10
+//! monitor watch 0x2f0 read
+//! monitor unwatch v3
+//! monitor watches
+//! ```
+//!
+//! Registers, in the order of `g` and numbered for `p`/`P`: V0-VF (0-15, one
+//! byte each), then I (16) and PC (17) as big endian words, then SP (18), DT
+//! (19) and ST (20), a byte each.
+//!
+//! Stop replies are `S05` for breakpoints and single steps, `T05watch:<addr>;`
+//! (or `rwatch`, `awatch`) for GDB's watchpoints, `S05` after a line of
+//! console output for the monitor's, `S02` for
+//! interrupts, `S04` for unknown opcodes and `S0b` for other emulation errors.
+//! A ROM executing 0000 reports `W00`.
+
+use std::collections::BTreeSet;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+
+use crate::cpu::Cpu;
+use crate::error::Error;
+use crate::instruction::Instruction;
+use crate::memory::MEMORY_SIZE;
+use crate::quirks::Quirks;
+
+const SIGINT: u8 = 0x02;
+const SIGILL: u8 = 0x04;
+const SIGTRAP: u8 = 0x05;
+const SIGSEGV: u8 = 0x0b;
+
+const REGISTER_COUNT: usize = 21;
+/// The `p` number of I, the last register that can be watched.
+const INDEX_REGISTER: usize = 16;
+
+/// What a watchpoint looks at: a range of memory, or a register by its `p`
+/// number, V0-VF or I.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Watched {
+    Memory { addr: usize, len: usize },
+    Register(usize),
+}
+
+/// In the order of `Z2`, `Z3` and `Z4`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Write,
+    Read,
+    Any,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Watchpoint {
+    watched: Watched,
+    access: Access,
+    /// Only stop when this value is read or written.
+    value: Option<u16>,
+    /// Set with a monitor command rather than a `Z` packet, the stop is
+    /// explained in console output.
+    monitor: bool,
+}
+
+/// A watchpoint going off: the address or register number, whether it was a
+/// write, and the value.
+struct Hit {
+    watchpoint: Watchpoint,
+    at: usize,
+    write: bool,
+    value: u16,
+}
+
+struct Connection {
+    stream: TcpStream,
+    input: Vec<u8>,
+}
+
+pub struct RemoteDebugger {
+    listener: TcpListener,
+    connection: Option<Connection>,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: Vec<Watchpoint>,
+    stopped: bool,
+    /// Set on continue, so the breakpoint the machine stopped on does not hit again right away.
+    resume_pc: Option<usize>,
+}
+
+impl RemoteDebugger {
+    /// Listens on localhost only, the protocol has no authentication.
+    pub fn bind(port: u16) -> io::Result<RemoteDebugger> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(RemoteDebugger {
+            listener,
+            connection: None,
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+            stopped: false,
+            resume_pc: None,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn connected(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    /// The debugger holds the machine; don't run frames.
+    pub fn stopped(&self) -> bool {
+        self.connected() && self.stopped
+    }
+
+    /// Accepts a debugger and handles whatever it sent. Call once per frame.
+    pub fn poll(&mut self, cpu: &mut Cpu) {
+        if self.connection.is_none() {
+            if let Ok((stream, _)) = self.listener.accept() {
+                if stream.set_nonblocking(true).is_ok() {
+                    self.connection = Some(Connection {
+                        stream,
+                        input: Vec::new(),
+                    });
+                    self.stopped = true;
+                }
+            }
+        }
+
+        let Some(connection) = &mut self.connection else {
+            return;
+        };
+        let mut buffer = [0; 1024];
+        loop {
+            match connection.stream.read(&mut buffer) {
+                Ok(0) => {
+                    self.disconnect();
+                    return;
+                }
+                Ok(len) => connection.input.extend_from_slice(&buffer[..len]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(_) => {
+                    self.disconnect();
+                    return;
+                }
+            }
+        }
+
+        while let Some(packet) = self.next_packet() {
+            let reply = self.handle(&packet, cpu);
+            if let Some(reply) = reply {
+                self.send(&reply);
+            }
+        }
+    }
+
+    /// Runs a frame like `Cpu::run_frame`, stopping at breakpoints and
+    /// watchpoints. Emulation
+    /// errors stop the machine for the debugger to look at; they are only
+    /// returned when no debugger is connected.
+    pub fn run_frame(&mut self, cpu: &mut Cpu) -> Result<(), Error> {
+        let _span = cpu.frame_span();
+        for _ in 0..cpu.speed {
+            // a collision break ends the frame early
+            if cpu.halted || cpu.paused || self.stopped() {
+                break;
+            }
+            let pc = cpu.program_counter;
+            if self.connected()
+                && self.breakpoints.contains(&pc)
+                && self.resume_pc.take() != Some(pc)
+            {
+                self.stop(SIGTRAP);
+                break;
+            }
+            self.resume_pc = None;
+
+            match self.step(cpu) {
+                Ok(None) => {}
+                Ok(Some(hit)) => {
+                    let reply = self.hit_reply(&hit);
+                    self.stopped = true;
+                    self.send(&reply);
+                    break;
+                }
+                Err(err) if !self.connected() => return Err(err),
+                Err(err) => {
+                    self.stop(signal(&err));
+                    break;
+                }
+            }
+            if cpu.halted && self.connected() {
+                self.stopped = true;
+                self.send("W00");
+            }
+        }
+        if !self.stopped() {
+            cpu.tick_timers();
+        }
+        Ok(())
+    }
+
+    /// Steps once, checking the watchpoints if there are any.
+    fn step(&mut self, cpu: &mut Cpu) -> Result<Option<Hit>, Error> {
+        if self.watchpoints.is_empty() {
+            return cpu.step().map(|()| None);
+        }
+
+        let pc = cpu.program_counter;
+        let instruction = cpu
+            .memory
+            .read_word(pc)
+            .ok()
+            .and_then(|opcode| Instruction::decode(opcode).ok());
+        let (read, mut written) =
+            instruction.map_or((0, 0), |instruction| register_use(instruction, &cpu.quirks));
+        let before: Vec<u16> = (0..=INDEX_REGISTER)
+            .map(|number| register_value(cpu, number))
+            .collect();
+
+        let logging = (cpu.memory.log_reads, cpu.memory.log_writes);
+        (cpu.memory.log_reads, cpu.memory.log_writes) = (true, true);
+        let result = cpu.step();
+        (cpu.memory.log_reads, cpu.memory.log_writes) = logging;
+        let reads = cpu.memory.take_reads();
+        let writes = cpu.memory.take_writes();
+        result?;
+
+        // Fx0A only stores the key once there is one
+        if matches!(instruction, Some(Instruction::WaitKey { .. })) && cpu.program_counter == pc {
+            written = 0;
+        }
+
+        for &watchpoint in &self.watchpoints {
+            let hit = |at: usize, write: bool, value: u16| {
+                let wanted = match watchpoint.access {
+                    Access::Write => write,
+                    Access::Read => !write,
+                    Access::Any => true,
+                };
+                (wanted && watchpoint.value.is_none_or(|wanted| wanted == value)).then_some(Hit {
+                    watchpoint,
+                    at,
+                    write,
+                    value,
+                })
+            };
+            let found = match watchpoint.watched {
+                Watched::Memory { addr, len } => {
+                    let range = addr..addr + len;
+                    writes
+                        .iter()
+                        .filter(|(at, _)| range.contains(at))
+                        .find_map(|&(at, value)| hit(at, true, value as u16))
+                        .or_else(|| {
+                            reads
+                                .iter()
+                                .filter(|at| range.contains(at))
+                                .find_map(|&at| hit(at, false, cpu.memory.as_slice()[at] as u16))
+                        })
+                }
+                Watched::Register(number) => {
+                    let bit = 1 << number;
+                    (written & bit != 0)
+                        .then(|| hit(number, true, register_value(cpu, number)))
+                        .flatten()
+                        .or_else(|| {
+                            (read & bit != 0)
+                                .then(|| hit(number, false, before[number]))
+                                .flatten()
+                        })
+                }
+            };
+            if found.is_some() {
+                return Ok(found);
+            }
+        }
+        Ok(None)
+    }
+
+    /// The stop reply for a watchpoint. The monitor's get a line of console
+    /// output first, GDB would not know what they are.
+    fn hit_reply(&mut self, hit: &Hit) -> String {
+        if !hit.watchpoint.monitor {
+            let kind = match hit.watchpoint.access {
+                Access::Write => "watch",
+                Access::Read => "rwatch",
+                Access::Any => "awatch",
+            };
+            return format!("T{:02x}{}:{:x};", SIGTRAP, kind, hit.at);
+        }
+
+        let what = match hit.watchpoint.watched {
+            Watched::Memory { .. } => format!("{:#05x}", hit.at),
+            Watched::Register(_) => register_name(hit.at),
+        };
+        let line = format!(
+            "watchpoint: {} {} {:#04x}\n",
+            what,
+            if hit.write { "written with" } else { "read as" },
+            hit.value
+        );
+        self.send(&format!("O{}", encode_hex(line.as_bytes())));
+        format!("S{:02x}", SIGTRAP)
+    }
+
+    fn stop(&mut self, signal: u8) {
+        self.stopped = true;
+        self.send(&format!("S{:02x}", signal));
+    }
+
+    fn disconnect(&mut self) {
+        self.connection = None;
+        self.stopped = false;
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+    }
+
+    /// Pops the next complete packet, acknowledging it. Interrupts come out
+    /// as a packet of their own, `\x03`.
+    fn next_packet(&mut self) -> Option<Vec<u8>> {
+        let connection = self.connection.as_mut()?;
+        loop {
+            let start = connection
+                .input
+                .iter()
+                .position(|&byte| byte == b'$' || byte == 0x03)?;
+            // acks and anything else between packets
+            connection.input.drain(..start);
+            if connection.input[0] == 0x03 {
+                connection.input.remove(0);
+                return Some(vec![0x03]);
+            }
+
+            let end = connection.input.iter().position(|&byte| byte == b'#')?;
+            if connection.input.len() < end + 3 {
+                return None;
+            }
+            let packet: Vec<u8> = connection.input[1..end].to_vec();
+            let checksum = std::str::from_utf8(&connection.input[end + 1..end + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            connection.input.drain(..end + 3);
+
+            let valid = checksum == Some(sum(&packet));
+            let _ = connection.stream.write_all(if valid { b"+" } else { b"-" });
+            if valid {
+                return Some(packet);
+            }
+        }
+    }
+
+    fn send(&mut self, reply: &str) {
+        let Some(connection) = &mut self.connection else {
+            return;
+        };
+        let packet = format!("${}#{:02x}", reply, sum(reply.as_bytes()));
+        // the socket is non-blocking, but replies are tiny
+        connection.stream.set_nonblocking(false).ok();
+        let sent = connection.stream.write_all(packet.as_bytes());
+        connection.stream.set_nonblocking(true).ok();
+        if sent.is_err() {
+            self.disconnect();
+        }
+    }
+
+    /// The reply to a packet, None for `c` (the reply comes when the machine stops).
+    fn handle(&mut self, packet: &[u8], cpu: &mut Cpu) -> Option<String> {
+        if packet == [0x03] {
+            if !self.stopped {
+                self.stop(SIGINT);
+            }
+            return None;
+        }
+
+        let packet = String::from_utf8_lossy(packet);
+        let (command, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        let reply = match command {
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => registers(cpu)
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+            "G" => match decode_hex(args) {
+                Some(bytes) if bytes.len() == registers(cpu).len() => {
+                    set_registers(cpu, &bytes);
+                    "OK".to_string()
+                }
+                _ => "E01".to_string(),
+            },
+            "p" => match usize::from_str_radix(args, 16) {
+                Ok(number) if number < REGISTER_COUNT => register(cpu, number),
+                _ => "E01".to_string(),
+            },
+            "P" => match args.split_once('=').and_then(|(number, value)| {
+                Some((usize::from_str_radix(number, 16).ok()?, decode_hex(value)?))
+            }) {
+                Some((number, value)) if set_register(cpu, number, &value) => "OK".to_string(),
+                _ => "E01".to_string(),
+            },
+            "m" => match parse_range(args) {
+                Some((addr, len)) => cpu.memory.as_slice()[addr..addr + len]
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect(),
+                None => "E01".to_string(),
+            },
+            "M" => match args.split_once(':').and_then(|(range, data)| {
+                let (addr, len) = parse_range(range)?;
+                let data = decode_hex(data).filter(|data| data.len() == len)?;
+                // the debugger may patch the font and interpreter area too
+                cpu.memory.load(addr, &data).ok()
+            }) {
+                Some(()) => "OK".to_string(),
+                None => "E01".to_string(),
+            },
+            "Z" | "z" if args.starts_with(['2', '3', '4']) => match parse_watchpoint(args) {
+                Some(watchpoint) => {
+                    if command == "Z" {
+                        self.watchpoints.push(watchpoint);
+                    } else if let Some(found) =
+                        self.watchpoints.iter().position(|&set| set == watchpoint)
+                    {
+                        self.watchpoints.remove(found);
+                    }
+                    "OK".to_string()
+                }
+                None => "E01".to_string(),
+            },
+            "Z" | "z" => match parse_breakpoint(args) {
+                Some(addr) => {
+                    if command == "Z" {
+                        self.breakpoints.insert(addr);
+                    } else {
+                        self.breakpoints.remove(&addr);
+                    }
+                    "OK".to_string()
+                }
+                None => String::new(),
+            },
+            "c" => {
+                self.stopped = false;
+                self.resume_pc = Some(cpu.program_counter);
+                return None;
+            }
+            "s" => {
+                if cpu.halted {
+                    "W00".to_string()
+                } else {
+                    match self.step(cpu) {
+                        Ok(Some(hit)) => self.hit_reply(&hit),
+                        Ok(None) if cpu.halted => "W00".to_string(),
+                        Ok(None) => format!("S{:02x}", SIGTRAP),
+                        Err(err) => format!("S{:02x}", signal(&err)),
+                    }
+                }
+            }
+            "D" | "k" => {
+                self.send("OK");
+                self.disconnect();
+                return None;
+            }
+            "H" => "OK".to_string(),
+            "q" if args.starts_with("Supported") => "PacketSize=4000".to_string(),
+            "q" if args == "Attached" => "1".to_string(),
+            "q" if args.starts_with("Rcmd,") => {
+                let command = decode_hex(&args[5..])
+                    .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                    .unwrap_or_default();
+                let output = self.monitor(&command);
+                // console output is sent as O packets before the final reply
+                for line in output.lines() {
+                    let line = format!("{}\n", line);
+                    self.send(&format!("O{}", encode_hex(line.as_bytes())));
+                }
+                "OK".to_string()
+            }
+            _ => String::new(),
+        };
+        Some(reply)
+    }
+}
+
+impl RemoteDebugger {
+    /// Runs a monitor command, returning what to print.
+    fn monitor(&mut self, command: &str) -> String {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        match words.as_slice() {
+            ["watch", rest @ ..] => match parse_watch(rest) {
+                Ok(watchpoint) => {
+                    self.watchpoints.push(watchpoint);
+                    format!("watching {}", describe(&watchpoint))
+                }
+                Err(message) => message,
+            },
+            ["unwatch"] => {
+                self.watchpoints.retain(|watchpoint| !watchpoint.monitor);
+                "removed all watchpoints".to_string()
+            }
+            ["unwatch", target] => match parse_watched(target) {
+                Some(watched) => {
+                    let before = self.watchpoints.len();
+                    self.watchpoints.retain(|watchpoint| {
+                        !(watchpoint.monitor && watchpoint.watched == watched)
+                    });
+                    format!("removed {}", before - self.watchpoints.len())
+                }
+                None => format!("no register or address {}", target),
+            },
+            ["watches"] if self.watchpoints.is_empty() => "no watchpoints".to_string(),
+            ["watches"] => self
+                .watchpoints
+                .iter()
+                .map(describe)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            _ => "commands:\n  watch <v0-vf, i or address> [read|write|access] [= value]\n  \
+                  unwatch [target]\n  watches"
+                .to_string(),
+        }
+    }
+}
+
+/// `watch`'s arguments: what, then optionally the kind of access and `= value`.
+fn parse_watch(words: &[&str]) -> Result<Watchpoint, String> {
+    let Some((target, mut rest)) = words.split_first() else {
+        return Err("watch what? a register (v0-vf, i) or an address".to_string());
+    };
+    let watched =
+        parse_watched(target).ok_or_else(|| format!("no register or address {}", target))?;
+    let mut access = Access::Write;
+    if let Some((&kind, after)) = rest.split_first() {
+        let parsed = match kind {
+            "write" => Some(Access::Write),
+            "read" => Some(Access::Read),
+            "access" => Some(Access::Any),
+            _ => None,
+        };
+        if let Some(parsed) = parsed {
+            access = parsed;
+            rest = after;
+        }
+    }
+    let value = match rest {
+        [] => None,
+        ["=", value] | ["==", value] => Some(
+            parse_number(value)
+                .filter(|&value| watched == Watched::Register(INDEX_REGISTER) || value <= 0xFF)
+                .ok_or_else(|| format!("bad value {}", value))?,
+        ),
+        _ => return Err(format!("don't understand {}", rest.join(" "))),
+    };
+    Ok(Watchpoint {
+        watched,
+        access,
+        value,
+        monitor: true,
+    })
+}
+
+/// `v0`-`vf`, `i`, or a memory address.
+fn parse_watched(text: &str) -> Option<Watched> {
+    let lower = text.to_ascii_lowercase();
+    if lower == "i" {
+        return Some(Watched::Register(INDEX_REGISTER));
+    }
+    if let Some(digit) = lower.strip_prefix('v').filter(|digit| digit.len() == 1) {
+        return usize::from_str_radix(digit, 16).ok().map(Watched::Register);
+    }
+    parse_number(&lower)
+        .map(|addr| addr as usize)
+        .filter(|&addr| addr < MEMORY_SIZE)
+        .map(|addr| Watched::Memory { addr, len: 1 })
+}
+
+/// Decimal, or hex with `0x`.
+fn parse_number(text: &str) -> Option<u16> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+fn describe(watchpoint: &Watchpoint) -> String {
+    let what = match watchpoint.watched {
+        Watched::Memory { addr, len: 1 } => format!("{:#05x}", addr),
+        Watched::Memory { addr, len } => format!("{:#05x}-{:#05x}", addr, addr + len - 1),
+        Watched::Register(number) => register_name(number),
+    };
+    let access = match watchpoint.access {
+        Access::Write => "writes",
+        Access::Read => "reads",
+        Access::Any => "accesses",
+    };
+    let mut text = format!("{} of {}", access, what);
+    if let Some(value) = watchpoint.value {
+        text += &format!(" = {:#04x}", value);
+    }
+    if !watchpoint.monitor {
+        text += " (gdb)";
+    }
+    text
+}
+
+fn register_name(number: usize) -> String {
+    match number {
+        INDEX_REGISTER => "I".to_string(),
+        _ => format!("V{:X}", number),
+    }
+}
+
+fn register_value(cpu: &Cpu, number: usize) -> u16 {
+    match number {
+        INDEX_REGISTER => cpu.index,
+        _ => cpu.registers[number] as u16,
+    }
+}
+
+/// The registers an instruction reads and writes, as bit masks of their `p`
+/// numbers. Quirks decide some of them.
+fn register_use(instruction: Instruction, quirks: &Quirks) -> (u32, u32) {
+    let v = |x: u8| 1u32 << x;
+    // v0 to vx
+    let through = |x: u8| (2u32 << x) - 1;
+    let i = 1u32 << INDEX_REGISTER;
+    let vf = v(0xF);
+    let increments = if quirks.load_store_increments_i { i } else { 0 };
+    match instruction {
+        Instruction::Sys { .. }
+        | Instruction::Cls
+        | Instruction::Ret
+        | Instruction::Exit
+        | Instruction::Jump { .. }
+        | Instruction::Call { .. } => (0, 0),
+        Instruction::SeXkk { x, .. }
+        | Instruction::SneXkk { x, .. }
+        | Instruction::SkipKey { x }
+        | Instruction::SkipNotKey { x }
+        | Instruction::SetDelay { x }
+        | Instruction::SetSound { x } => (v(x), 0),
+        Instruction::SeXy { x, y } | Instruction::SneXy { x, y } => (v(x) | v(y), 0),
+        Instruction::Set { x, .. }
+        | Instruction::Rand { x, .. }
+        | Instruction::GetDelay { x }
+        | Instruction::WaitKey { x } => (0, v(x)),
+        Instruction::Add { x, .. } => (v(x), v(x)),
+        Instruction::SetXy { x, y } => (v(y), v(x)),
+        Instruction::OrXy { x, y } | Instruction::AndXy { x, y } | Instruction::XorXy { x, y } => {
+            let reset = if quirks.logic_resets_vf { vf } else { 0 };
+            (v(x) | v(y), v(x) | reset)
+        }
+        Instruction::AddXy { x, y }
+        | Instruction::SubXy { x, y }
+        | Instruction::SubnXy { x, y } => (v(x) | v(y), v(x) | vf),
+        Instruction::ShrXy { x, y } | Instruction::ShlXy { x, y } => {
+            let source = if quirks.shift_uses_vy { y } else { x };
+            (v(source), v(x) | vf)
+        }
+        Instruction::SetI { .. } => (0, i),
+        Instruction::JumpV0 { addr } => {
+            let register = if quirks.jump_uses_vx {
+                (addr >> 8) as u8
+            } else {
+                0
+            };
+            (v(register), 0)
+        }
+        Instruction::Draw { x, y, .. } => (v(x) | v(y) | i, vf),
+        Instruction::AddI { x } => (v(x) | i, i),
+        Instruction::Font { x } | Instruction::BigFont { x } => (v(x), i),
+        Instruction::Audio => (i, 0),
+        Instruction::Pitch { x } => (v(x), 0),
+        Instruction::Bcd { x } => (v(x) | i, 0),
+        Instruction::Store { x } => (through(x) | i, increments),
+        Instruction::Load { x } => (i, through(x) | increments),
+    }
+}
+
+fn signal(err: &Error) -> u8 {
+    match err {
+        Error::UnknownOpcode { .. } => SIGILL,
+        _ => SIGSEGV,
+    }
+}
+
+fn sum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// `addr,len` in hex, checked against the size of memory.
+fn parse_range(text: &str) -> Option<(usize, usize)> {
+    let (addr, len) = text.split_once(',')?;
+    let addr = usize::from_str_radix(addr, 16).ok()?;
+    let len = usize::from_str_radix(len, 16).ok()?;
+    (addr.checked_add(len)? <= MEMORY_SIZE).then_some((addr, len))
+}
+
+/// `0,addr,kind` or `1,addr,kind`; software and hardware breakpoints are the same here.
+fn parse_breakpoint(text: &str) -> Option<usize> {
+    let mut fields = text.split(',');
+    let kind = fields.next()?;
+    if kind != "0" && kind != "1" {
+        return None;
+    }
+    usize::from_str_radix(fields.next()?, 16)
+        .ok()
+        .filter(|&addr| addr < MEMORY_SIZE)
+}
+
+/// `kind,addr,len` for `Z2` to `Z4`, a range of memory.
+fn parse_watchpoint(text: &str) -> Option<Watchpoint> {
+    let (kind, range) = text.split_once(',')?;
+    let access = match kind {
+        "2" => Access::Write,
+        "3" => Access::Read,
+        "4" => Access::Any,
+        _ => return None,
+    };
+    let (addr, len) = parse_range(range).filter(|&(_, len)| len > 0)?;
+    Some(Watchpoint {
+        watched: Watched::Memory { addr, len },
+        access,
+        value: None,
+        monitor: false,
+    })
+}
+
+fn registers(cpu: &Cpu) -> Vec<u8> {
+    let mut bytes = cpu.registers.to_vec();
+    bytes.extend_from_slice(&cpu.index.to_be_bytes());
+    bytes.extend_from_slice(&(cpu.program_counter as u16).to_be_bytes());
+    bytes.extend_from_slice(&[cpu.stack_pointer as u8, cpu.delay_timer, cpu.sound_timer]);
+    bytes
+}
+
+fn set_registers(cpu: &mut Cpu, bytes: &[u8]) {
+    cpu.registers.copy_from_slice(&bytes[..16]);
+    cpu.index = u16::from_be_bytes([bytes[16], bytes[17]]);
+    cpu.program_counter = u16::from_be_bytes([bytes[18], bytes[19]]) as usize;
+    cpu.stack_pointer = (bytes[20] as usize).min(cpu.stack.len());
+    cpu.delay_timer = bytes[21];
+    cpu.sound_timer = bytes[22];
+}
+
+fn register(cpu: &Cpu, number: usize) -> String {
+    let bytes = registers(cpu);
+    let range = match number {
+        0..=15 => number..number + 1,
+        16 => 16..18,
+        17 => 18..20,
+        _ => number + 2..number + 3,
+    };
+    bytes[range]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn set_register(cpu: &mut Cpu, number: usize, value: &[u8]) -> bool {
+    let word = || <[u8; 2]>::try_from(value).map(u16::from_be_bytes);
+    match (number, value) {
+        (0..=15, [byte]) => cpu.registers[number] = *byte,
+        (16, _) => match word() {
+            Ok(index) => cpu.index = index,
+            Err(_) => return false,
+        },
+        (17, _) => match word() {
+            Ok(pc) => cpu.program_counter = pc as usize,
+            Err(_) => return false,
+        },
+        (18, [sp]) if (*sp as usize) <= cpu.stack.len() => cpu.stack_pointer = *sp as usize,
+        (19, [dt]) => cpu.delay_timer = *dt,
+        (20, [st]) => cpu.sound_timer = *st,
+        _ => return false,
+    }
+    true
+}
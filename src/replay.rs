@@ -0,0 +1,344 @@
+//! Input recordings that replay a session exactly.
+//!
+//! A recording stores what the run depends on besides the ROM: the RNG seed,
+//! the speed, the quirks and every keypad change together with the frame it
+//! happened in. Emulation is deterministic per frame, so feeding the same
+//! keys into the same frames reproduces the session, crash included.
+//!
+//! The file is plain text:
+//!
+//! ```text
+//! chip8 replay 2
+//! rom 5d0f5ea7a6f4a1c3
+//! seed 1234
+//! speed 10
+//! quirks 11010
+//! hash 60 9a4e0c31d2b7f518
+//! 120 down 5
+//! 126 up 5
+//! end 4512
+//! ```
+//!
+//! Version 2 added the `hash` lines, a `state_hash` of the machine every
+//! `CHECKPOINT_FRAMES` frames, so a replay that stops doing what was
+//! recorded can be caught and `bisect` can tell when. Version 1 recordings
+//! have none and still play.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::cpu::Cpu;
+use crate::memory::MEMORY_SIZE;
+use crate::quirks::Quirks;
+use crate::rng::Rng;
+
+const HEADER: &str = "chip8 replay 2";
+/// What version 1 recordings start with.
+const HEADER_V1: &str = "chip8 replay 1";
+
+/// Frames between the checkpoints a recording keeps, one a second.
+pub const CHECKPOINT_FRAMES: usize = 60;
+
+/// A keypad key going down or up at the start of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub frame: usize,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay {
+    /// `rom_hash` of the ROM that was recorded.
+    pub rom: u64,
+    pub seed: u64,
+    pub speed: usize,
+    pub quirks: Quirks,
+    /// In frame order.
+    pub events: Vec<KeyEvent>,
+    /// `state_hash` of the machine before the frame ran, by frame, in order.
+    pub hashes: Vec<(usize, u64)>,
+    /// Frames recorded.
+    pub frames: usize,
+}
+
+/// FNV-1a hash of the ROM, to catch replays against the wrong file.
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// FNV-1a hash of everything a frame can change: the registers, the stack,
+/// the timers, the RNG, memory and the screen. It doesn't depend on the save
+/// state format, so recordings keep their hashes across versions.
+pub fn state_hash(cpu: &Cpu) -> u64 {
+    let mut bytes = Vec::with_capacity(MEMORY_SIZE + 256);
+    bytes.extend_from_slice(&cpu.registers);
+    bytes.extend_from_slice(&cpu.index.to_be_bytes());
+    bytes.extend_from_slice(&(cpu.program_counter as u16).to_be_bytes());
+    for addr in cpu.stack {
+        bytes.extend_from_slice(&addr.to_be_bytes());
+    }
+    bytes.extend_from_slice(&[
+        cpu.stack_pointer as u8,
+        cpu.delay_timer,
+        cpu.sound_timer,
+        cpu.halted as u8,
+    ]);
+    bytes.extend_from_slice(&cpu.rng.state().to_be_bytes());
+    bytes.extend_from_slice(cpu.memory.as_slice());
+    bytes.extend(cpu.display.pixels().iter().map(|&on| on as u8));
+    rom_hash(&bytes)
+}
+
+impl Replay {
+    /// Puts the machine in the state the recording started from.
+    /// `cpu.speed` and `cpu.quirks` are taken from the recording.
+    pub fn apply(&self, cpu: &mut Cpu) {
+        cpu.rng = Rng::new(self.seed);
+        cpu.speed = self.speed;
+        cpu.quirks = self.quirks;
+    }
+
+    pub fn load(path: &Path) -> io::Result<Replay> {
+        Replay::parse(&fs::read_to_string(path)?)
+            .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn parse(text: &str) -> Result<Replay, String> {
+        let mut lines = text.lines().enumerate();
+        if !matches!(
+            lines.next().map(|(_, line)| line.trim()),
+            Some(HEADER | HEADER_V1)
+        ) {
+            return Err(format!("not a replay, expected \"{}\" first", HEADER));
+        }
+
+        let mut replay = Replay {
+            rom: 0,
+            seed: 0,
+            speed: 0,
+            quirks: Quirks::default(),
+            events: Vec::new(),
+            hashes: Vec::new(),
+            frames: 0,
+        };
+        let mut end = None;
+
+        for (number, line) in lines {
+            let invalid = || format!("line {}: invalid entry {}", number + 1, line);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields[..] {
+                [] => {}
+                ["rom", hash] => {
+                    replay.rom = u64::from_str_radix(hash, 16).map_err(|_| invalid())?
+                }
+                ["seed", seed] => replay.seed = seed.parse().map_err(|_| invalid())?,
+                ["speed", speed] => replay.speed = speed.parse().map_err(|_| invalid())?,
+                ["quirks", bits] => replay.quirks = parse_quirks(bits).ok_or_else(invalid)?,
+                ["hash", frame, hash] => {
+                    let frame: usize = frame.parse().map_err(|_| invalid())?;
+                    let hash = u64::from_str_radix(hash, 16).map_err(|_| invalid())?;
+                    if replay.hashes.last().is_some_and(|&(last, _)| last >= frame) {
+                        return Err(format!("line {}: hashes out of order", number + 1));
+                    }
+                    replay.hashes.push((frame, hash));
+                }
+                ["end", frames] => end = Some(frames.parse().map_err(|_| invalid())?),
+                [frame, change @ ("down" | "up"), key] => {
+                    let frame: usize = frame.parse().map_err(|_| invalid())?;
+                    let key = u8::from_str_radix(key, 16)
+                        .ok()
+                        .filter(|&key| key < 16)
+                        .ok_or_else(invalid)?;
+                    if replay.events.last().is_some_and(|last| last.frame > frame) {
+                        return Err(format!("line {}: frames out of order", number + 1));
+                    }
+                    replay.events.push(KeyEvent {
+                        frame,
+                        key,
+                        pressed: change == "down",
+                    });
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        if replay.speed == 0 {
+            return Err("missing speed".to_string());
+        }
+        replay.frames = end.ok_or("missing end, the recording was cut short")?;
+        Ok(replay)
+    }
+}
+
+impl std::fmt::Display for Replay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "rom {:016x}", self.rom)?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "speed {}", self.speed)?;
+        writeln!(f, "quirks {}", format_quirks(&self.quirks))?;
+        // the hashes go in among the key events, in frame order
+        let mut hashes = self.hashes.iter().peekable();
+        for event in &self.events {
+            while let Some((frame, hash)) = hashes.next_if(|&&(frame, _)| frame <= event.frame) {
+                writeln!(f, "hash {} {:016x}", frame, hash)?;
+            }
+            let change = if event.pressed { "down" } else { "up" };
+            writeln!(f, "{} {} {:X}", event.frame, change, event.key)?;
+        }
+        for (frame, hash) in hashes {
+            writeln!(f, "hash {} {:016x}", frame, hash)?;
+        }
+        writeln!(f, "end {}", self.frames)
+    }
+}
+
+/// One 0/1 digit per quirk, in declaration order.
+fn format_quirks(quirks: &Quirks) -> String {
+    [
+        quirks.shift_uses_vy,
+        quirks.load_store_increments_i,
+        quirks.jump_uses_vx,
+        quirks.logic_resets_vf,
+        quirks.wrap_sprites,
+    ]
+    .iter()
+    .map(|&on| if on { '1' } else { '0' })
+    .collect()
+}
+
+fn parse_quirks(bits: &str) -> Option<Quirks> {
+    let bits: Vec<bool> = bits
+        .chars()
+        .map(|bit| match bit {
+            '0' => Some(false),
+            '1' => Some(true),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    let [shift_uses_vy, load_store_increments_i, jump_uses_vx, logic_resets_vf, wrap_sprites] =
+        bits[..]
+    else {
+        return None;
+    };
+    Some(Quirks {
+        shift_uses_vy,
+        load_store_increments_i,
+        jump_uses_vx,
+        logic_resets_vf,
+        wrap_sprites,
+    })
+}
+
+/// Builds a recording while a session runs.
+pub struct Recorder {
+    replay: Replay,
+    keys: [bool; 16],
+}
+
+impl Recorder {
+    /// Starts recording. The machine should already be set up with its ROM,
+    /// speed and quirks; the RNG is reseeded with `seed` so it can be stored.
+    pub fn start(cpu: &mut Cpu, rom: &[u8], seed: u64) -> Recorder {
+        cpu.rng = Rng::new(seed);
+
+        Recorder {
+            replay: Replay {
+                rom: rom_hash(rom),
+                seed,
+                speed: cpu.speed,
+                quirks: cpu.quirks,
+                events: Vec::new(),
+                hashes: Vec::new(),
+                frames: 0,
+            },
+            keys: [false; 16],
+        }
+    }
+
+    /// Starts recording from wherever the machine is, for recordings that go
+    /// with a save state of it: the RNG carries on as it was, so the seed
+    /// recorded means nothing and the state has to be restored after `apply`.
+    pub fn resume(cpu: &Cpu, rom: u64) -> Recorder {
+        Recorder {
+            replay: Replay {
+                rom,
+                seed: 0,
+                speed: cpu.speed,
+                quirks: cpu.quirks,
+                events: Vec::new(),
+                hashes: Vec::new(),
+                frames: 0,
+            },
+            keys: [false; 16],
+        }
+    }
+
+    /// Call right before emulating each frame, with the machine about to run
+    /// it and the keys it runs with.
+    pub fn frame(&mut self, cpu: &Cpu) {
+        let frame = self.replay.frames;
+        if frame > 0 && frame.is_multiple_of(CHECKPOINT_FRAMES) {
+            self.replay.hashes.push((frame, state_hash(cpu)));
+        }
+        for (key, (&now, was)) in cpu.keys.iter().zip(self.keys.iter_mut()).enumerate() {
+            if now != *was {
+                self.replay.events.push(KeyEvent {
+                    frame: self.replay.frames,
+                    key: key as u8,
+                    pressed: now,
+                });
+                *was = now;
+            }
+        }
+        self.replay.frames += 1;
+    }
+
+    pub fn finish(self) -> Replay {
+        self.replay
+    }
+}
+
+/// Feeds a recording back, frame by frame.
+pub struct Player<'a> {
+    replay: &'a Replay,
+    frame: usize,
+    next: usize,
+    keys: [bool; 16],
+}
+
+impl<'a> Player<'a> {
+    pub fn new(replay: &'a Replay) -> Player<'a> {
+        Player {
+            replay,
+            frame: 0,
+            next: 0,
+            keys: [false; 16],
+        }
+    }
+
+    /// Keys for the next frame, or None once the recording is over.
+    pub fn frame(&mut self) -> Option<[bool; 16]> {
+        if self.frame >= self.replay.frames {
+            return None;
+        }
+
+        while let Some(event) = self.replay.events.get(self.next) {
+            if event.frame > self.frame {
+                break;
+            }
+            self.keys[event.key as usize] = event.pressed;
+            self.next += 1;
+        }
+        self.frame += 1;
+        Some(self.keys)
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::VecDeque;
+
+use crate::cpu::Cpu;
+use crate::savestate::State;
+
+/// Frames between two keyframes.
+pub const KEYFRAME_INTERVAL: u32 = 10;
+
+/// Keyframes making up one second, what a single `step_back` undoes.
+const KEYFRAMES_PER_STEP: usize = 60 / KEYFRAME_INTERVAL as usize;
+
+/// The last few seconds of a session as save states, compressed with the
+/// compress feature, so the player can step back in time.
+pub struct Rewind {
+    keyframes: VecDeque<Vec<u8>>,
+    capacity: usize,
+    countdown: u32,
+}
+
+impl Rewind {
+    /// Keeps up to `seconds` of history.
+    pub fn new(seconds: u32) -> Rewind {
+        let capacity = seconds as usize * KEYFRAMES_PER_STEP;
+        Rewind {
+            keyframes: VecDeque::with_capacity(capacity),
+            capacity,
+            countdown: 0,
+        }
+    }
+
+    /// Call after every emulated frame.
+    pub fn frame(&mut self, cpu: &Cpu) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.countdown > 0 {
+            self.countdown -= 1;
+            return;
+        }
+
+        self.countdown = KEYFRAME_INTERVAL - 1;
+        if self.keyframes.len() == self.capacity {
+            self.keyframes.pop_front();
+        }
+        self.keyframes.push_back(State::capture(cpu).encode());
+    }
+
+    /// Goes back about a second. Returns false if there is no history left.
+    pub fn step_back(&mut self, cpu: &mut Cpu) -> bool {
+        let mut target = None;
+        for _ in 0..KEYFRAMES_PER_STEP {
+            match self.keyframes.pop_back() {
+                Some(keyframe) => target = Some(keyframe),
+                None => break,
+            }
+        }
+
+        let Some(keyframe) = target else {
+            return false;
+        };
+        State::decode(&keyframe)
+            .expect("keyframes are encoded by this emulator")
+            .restore(cpu);
+        self.countdown = KEYFRAME_INTERVAL - 1;
+        true
+    }
+
+    /// Bytes held by the keyframes.
+    pub fn size(&self) -> usize {
+        self.keyframes.iter().map(Vec::len).sum()
+    }
+}
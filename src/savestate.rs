@@ -0,0 +1,364 @@
+//! Save states: a snapshot of the whole machine that can be written to disk
+//! and restored later.
+//!
+//! The file starts with the magic `CH8S` and a little endian `u16` version.
+//! Old versions stay loadable: each one keeps its own decoder, which turns it
+//! into the current `State`, filling anything it lacks with what the emulator
+//! did at the time. `encode` only ever writes `VERSION`.
+//!
+//! * Version 1: the raw snapshot, see `decode_v1`.
+//! * Version 2: the version 1 snapshot compressed with `compress`, preceded
+//!   by its uncompressed length as a `u32`.
+//! * Version 3: like version 2, with the XO-CHIP audio after the snapshot: a
+//!   byte saying whether there is a pattern, the 16 pattern bytes and the
+//!   pitch. Older states load without a pattern, at the default pitch.
+//! * Version 4: the version 3 snapshot after a byte saying how it is stored:
+//!   0 as it is, 1 like version 3. Builds without the compress feature write
+//!   it as it is, and can't read compressed states.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "compress")]
+use crate::compress;
+use crate::cpu::{Cpu, Engine, DEFAULT_PITCH};
+use crate::display::{HEIGHT, WIDTH};
+use crate::memory::MEMORY_SIZE;
+use crate::quirks::Quirks;
+use crate::rng::Rng;
+use crate::stats;
+
+const MAGIC: &[u8; 4] = b"CH8S";
+
+/// The version `encode` writes.
+pub const VERSION: u16 = 4;
+
+/// Bytes in a version 1 snapshot, and what version 3 adds after it.
+const V1_LEN: usize = 16 + 2 + 2 + 16 * 2 + 4 + 8 + 4 + 1 + MEMORY_SIZE + WIDTH * HEIGHT / 8;
+const AUDIO_LEN: usize = 1 + 16 + 1;
+
+/// How a version 4 snapshot is stored.
+const STORED_RAW: u8 = 0;
+const STORED_COMPRESSED: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateError {
+    /// Not a save state at all.
+    BadMagic,
+    /// Written by a newer emulator.
+    UnsupportedVersion(u16),
+    Truncated,
+    Invalid(&'static str),
+    /// Compressed, by a build with the compress feature.
+    Compressed,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(version) => write!(
+                f,
+                "save state version {} is newer than this emulator (up to {})",
+                version, VERSION
+            ),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Invalid(what) => write!(f, "save state has invalid {}", what),
+            StateError::Compressed => write!(
+                f,
+                "save state is compressed, which needs a build with the compress feature"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+/// Everything needed to continue a session, in the current format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct State {
+    pub registers: [u8; 16],
+    pub index: u16,
+    pub program_counter: u16,
+    pub stack: [u16; 16],
+    pub stack_pointer: u8,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub audio_pattern: Option<[u8; 16]>,
+    pub pitch: u8,
+    pub halted: bool,
+    pub rng: u64,
+    pub speed: u32,
+    pub quirks: Quirks,
+    pub memory: Vec<u8>,
+    pub pixels: Vec<bool>,
+}
+
+impl State {
+    pub fn capture(cpu: &Cpu) -> State {
+        State {
+            registers: cpu.registers,
+            index: cpu.index,
+            program_counter: cpu.program_counter as u16,
+            stack: cpu.stack,
+            stack_pointer: cpu.stack_pointer as u8,
+            delay_timer: cpu.delay_timer,
+            sound_timer: cpu.sound_timer,
+            audio_pattern: cpu.audio_pattern,
+            pitch: cpu.pitch,
+            halted: cpu.halted,
+            rng: cpu.rng.state(),
+            speed: cpu.speed as u32,
+            quirks: cpu.quirks,
+            memory: cpu.memory.as_slice().to_vec(),
+            pixels: cpu.display.pixels().to_vec(),
+        }
+    }
+
+    /// Puts the machine back into the saved state. Host settings such as the
+    /// mailbox or the pc policies are left alone.
+    pub fn restore(&self, cpu: &mut Cpu) {
+        cpu.registers = self.registers;
+        cpu.index = self.index;
+        cpu.program_counter = self.program_counter as usize;
+        cpu.stack = self.stack;
+        cpu.stack_pointer = self.stack_pointer as usize;
+        cpu.delay_timer = self.delay_timer;
+        cpu.sound_timer = self.sound_timer;
+        cpu.audio_pattern = self.audio_pattern;
+        cpu.pitch = self.pitch;
+        cpu.halted = self.halted;
+        cpu.rng = Rng::from_state(self.rng);
+        cpu.speed = self.speed as usize;
+        cpu.quirks = self.quirks;
+        cpu.memory
+            .load(0, &self.memory)
+            .expect("decoding checks the memory size");
+        cpu.display.set_pixels(&self.pixels);
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut snapshot = self.encode_v1();
+        snapshot.push(self.audio_pattern.is_some() as u8);
+        snapshot.extend_from_slice(&self.audio_pattern.unwrap_or_default());
+        snapshot.push(self.pitch);
+        debug_assert_eq!(snapshot.len(), V1_LEN + AUDIO_LEN);
+
+        let mut out = Vec::with_capacity(snapshot.len() + 11);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        #[cfg(feature = "compress")]
+        {
+            out.push(STORED_COMPRESSED);
+            out.extend_from_slice(&(snapshot.len() as u32).to_le_bytes());
+            out.extend_from_slice(&compress::compress(&snapshot));
+        }
+        #[cfg(not(feature = "compress"))]
+        {
+            out.push(STORED_RAW);
+            out.extend_from_slice(&snapshot);
+        }
+        out
+    }
+
+    /// The version 1 snapshot, without the header.
+    fn encode_v1(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(MEMORY_SIZE + 512);
+        out.extend_from_slice(&self.registers);
+        out.extend_from_slice(&self.index.to_le_bytes());
+        out.extend_from_slice(&self.program_counter.to_le_bytes());
+        for entry in self.stack {
+            out.extend_from_slice(&entry.to_le_bytes());
+        }
+        out.extend_from_slice(&[
+            self.stack_pointer,
+            self.delay_timer,
+            self.sound_timer,
+            self.halted as u8,
+        ]);
+        out.extend_from_slice(&self.rng.to_le_bytes());
+        out.extend_from_slice(&self.speed.to_le_bytes());
+        out.push(quirk_bits(&self.quirks));
+        out.extend_from_slice(&self.memory);
+        for row in self.pixels.chunks(8) {
+            out.push(row.iter().fold(0, |byte, &on| (byte << 1) | on as u8));
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<State, StateError> {
+        let mut reader = Reader { bytes };
+        if reader.take(4).map_err(|_| StateError::BadMagic)? != MAGIC {
+            return Err(StateError::BadMagic);
+        }
+
+        let version = reader.u16()?;
+        let stored = match version {
+            1 => STORED_RAW,
+            2 | 3 => STORED_COMPRESSED,
+            4 => reader.take(1)?[0],
+            version => return Err(StateError::UnsupportedVersion(version)),
+        };
+        #[cfg(feature = "compress")]
+        let snapshot;
+        let mut reader = match stored {
+            STORED_RAW => reader,
+            #[cfg(feature = "compress")]
+            STORED_COMPRESSED => {
+                let len = reader.u32()? as usize;
+                // checked before decompressing, it comes from the file
+                if len != V1_LEN + if version >= 3 { AUDIO_LEN } else { 0 } {
+                    return Err(StateError::Invalid("length"));
+                }
+                snapshot = compress::decompress(reader.bytes, len)
+                    .map_err(|_| StateError::Invalid("compressed data"))?;
+                Reader { bytes: &snapshot }
+            }
+            #[cfg(not(feature = "compress"))]
+            STORED_COMPRESSED => return Err(StateError::Compressed),
+            _ => return Err(StateError::Invalid("storage")),
+        };
+        let mut state = decode_v1(&mut reader)?;
+        if version >= 3 {
+            decode_audio(&mut reader, &mut state)?;
+        }
+        if !reader.bytes.is_empty() {
+            return Err(StateError::Invalid("length"));
+        }
+        Ok(state)
+    }
+
+    pub fn load(path: &Path) -> io::Result<State> {
+        State::decode(&fs::read(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.encode())
+    }
+}
+
+/// Moves a running machine onto another engine: the machine is captured and
+/// encoded, the engine swapped and the decoded state put back, so nothing the
+/// old engine held on to comes along. If the snapshot doesn't decode the
+/// machine is left as it was.
+pub fn switch_engine(cpu: &mut Cpu, engine: Engine) -> Result<(), StateError> {
+    let state = State::decode(&State::capture(cpu).encode())?;
+    cpu.set_engine(engine);
+    state.restore(cpu);
+    Ok(())
+}
+
+/// Where the save state of a ROM goes, `<data dir>/states/<rom file name>.state`.
+pub fn path_for(rom: &Path) -> Option<PathBuf> {
+    let name = rom.file_name()?.to_string_lossy().into_owned();
+    Some(stats::data_dir()?.join("states").join(name + ".state"))
+}
+
+/// The version 1 snapshot, leaving anything after it in `reader`.
+fn decode_v1(reader: &mut Reader) -> Result<State, StateError> {
+    let registers = reader.take(16)?.try_into().unwrap();
+    let index = reader.u16()?;
+    let program_counter = reader.u16()?;
+    let mut stack = [0; 16];
+    for entry in &mut stack {
+        *entry = reader.u16()?;
+    }
+    let [stack_pointer, delay_timer, sound_timer, halted] = reader.take(4)?.try_into().unwrap();
+    if stack_pointer as usize > stack.len() {
+        return Err(StateError::Invalid("stack pointer"));
+    }
+    let rng = reader.u64()?;
+    let speed = reader.u32()?;
+    if speed == 0 {
+        return Err(StateError::Invalid("speed"));
+    }
+    let quirks = quirks_from_bits(reader.take(1)?[0]);
+    let memory = reader.take(MEMORY_SIZE)?.to_vec();
+    let pixels = reader
+        .take(WIDTH * HEIGHT / 8)?
+        .iter()
+        .flat_map(|&byte| (0..8).map(move |bit| byte & (0x80 >> bit) != 0))
+        .collect();
+
+    Ok(State {
+        registers,
+        index,
+        program_counter,
+        stack,
+        stack_pointer,
+        delay_timer,
+        sound_timer,
+        audio_pattern: None,
+        pitch: DEFAULT_PITCH,
+        halted: halted != 0,
+        rng,
+        speed,
+        quirks,
+        memory,
+        pixels,
+    })
+}
+
+/// What version 3 added after the snapshot.
+fn decode_audio(reader: &mut Reader, state: &mut State) -> Result<(), StateError> {
+    let present = reader.take(1)?[0];
+    let pattern: [u8; 16] = reader.take(16)?.try_into().unwrap();
+    state.audio_pattern = match present {
+        0 => None,
+        1 => Some(pattern),
+        _ => return Err(StateError::Invalid("audio pattern")),
+    };
+    state.pitch = reader.take(1)?[0];
+    Ok(())
+}
+
+fn quirk_bits(quirks: &Quirks) -> u8 {
+    quirks.shift_uses_vy as u8
+        | (quirks.load_store_increments_i as u8) << 1
+        | (quirks.jump_uses_vx as u8) << 2
+        | (quirks.logic_resets_vf as u8) << 3
+        | (quirks.wrap_sprites as u8) << 4
+}
+
+fn quirks_from_bits(bits: u8) -> Quirks {
+    Quirks {
+        shift_uses_vy: bits & 1 != 0,
+        load_store_increments_i: bits & 1 << 1 != 0,
+        jump_uses_vx: bits & 1 << 2 != 0,
+        logic_resets_vf: bits & 1 << 3 != 0,
+        wrap_sprites: bits & 1 << 4 != 0,
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.bytes.len() < len {
+            return Err(StateError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
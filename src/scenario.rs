@@ -0,0 +1,235 @@
+//! Scenario files describe a play session and what the screen should show along
+//! the way, one step per line:
+//!
+//! ```text
+//! # walks right for a second, then stops
+//! wait 30
+//! press 9
+//! wait 60
+//! release 9
+//! expect 5e1a0c34a7b2f9d1
+//! ```
+//!
+//! `press` and `release` take one or more hex keys, `wait` runs that many
+//! frames and `expect` checks a framebuffer hash, `halt` (the ROM has
+//! executed 0000) or `pass` (an assertion ROM has reported success). `reset`
+//! and `power-cycle` start the ROM over like F7 and F8 do. Anything after a
+//! `#` is a comment.
+
+use std::fmt;
+
+use crate::cpu::Cpu;
+use crate::headless::{self, ExitStatus, Outcome};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expect {
+    Hash(u64),
+    Halt,
+    Pass,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    Press(Vec<u8>),
+    Release(Vec<u8>),
+    Wait(usize),
+    Expect(Expect),
+    Reset,
+    PowerCycle,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scenario {
+    /// Each step with the line it is on.
+    pub steps: Vec<(usize, Step)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A step that did not go as expected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub line: usize,
+    pub message: String,
+    /// The exit status this failure is worth, `EmulationError` if the ROM
+    /// crashed rather than misbehaved.
+    pub status: ExitStatus,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// How far a scenario got.
+pub struct Report {
+    pub cpu: Cpu,
+    pub frames: usize,
+    /// The first failing step, the scenario stops there.
+    pub failure: Option<Failure>,
+}
+
+impl Scenario {
+    pub fn parse(source: &str) -> Result<Scenario, ParseError> {
+        let mut steps = Vec::new();
+        for (number, text) in source.lines().enumerate() {
+            let line = number + 1;
+            let error = |message: String| ParseError { line, message };
+            let text = text.split('#').next().unwrap_or("").trim();
+            let mut words = text.split_whitespace();
+            let Some(command) = words.next() else {
+                continue;
+            };
+            let args: Vec<&str> = words.collect();
+
+            let step = match (command, &args[..]) {
+                ("press" | "release", []) => {
+                    return Err(error(format!("{} needs at least one key", command)))
+                }
+                ("press" | "release", keys) => {
+                    let keys = keys
+                        .iter()
+                        .map(|key| match u8::from_str_radix(key, 16) {
+                            Ok(key) if key < 16 => Ok(key),
+                            _ => Err(error(format!("invalid key {:?}, expected 0-F", key))),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if command == "press" {
+                        Step::Press(keys)
+                    } else {
+                        Step::Release(keys)
+                    }
+                }
+                ("wait", [frames]) => Step::Wait(
+                    frames
+                        .replace('_', "")
+                        .parse()
+                        .map_err(|_| error(format!("invalid frame count {:?}", frames)))?,
+                ),
+                ("expect", ["halt"]) => Step::Expect(Expect::Halt),
+                ("expect", ["pass"]) => Step::Expect(Expect::Pass),
+                ("expect", [hash]) => Step::Expect(Expect::Hash(
+                    u64::from_str_radix(hash.trim_start_matches("0x"), 16)
+                        .map_err(|_| error(format!("invalid hash {:?}", hash)))?,
+                )),
+                ("reset", []) => Step::Reset,
+                ("power-cycle", []) => Step::PowerCycle,
+                ("reset" | "power-cycle", _) => {
+                    return Err(error(format!("{} takes no arguments", command)))
+                }
+                ("wait" | "expect", _) => {
+                    return Err(error(format!("{} takes one argument", command)))
+                }
+                _ => return Err(error(format!("unknown step {}", command))),
+            };
+            steps.push((line, step));
+        }
+        Ok(Scenario { steps })
+    }
+
+    /// Plays the scenario on `cpu`, which should have the ROM loaded, until
+    /// the end or the first failing step.
+    pub fn run(&self, mut cpu: Cpu) -> Report {
+        let mut frames = 0;
+        for (line, step) in &self.steps {
+            let fail = |message: String, status: ExitStatus| Failure {
+                line: *line,
+                message,
+                status,
+            };
+
+            let failure = match step {
+                Step::Press(keys) | Step::Release(keys) => {
+                    for &key in keys {
+                        cpu.keys[key as usize] = matches!(step, Step::Press(_));
+                    }
+                    None
+                }
+                Step::Wait(count) => {
+                    // a halted machine just stays on its last frame
+                    let run = headless::resume(cpu, *count, |_| {});
+                    cpu = run.cpu;
+                    match run.outcome {
+                        Outcome::Error(err) => {
+                            frames += run.frames;
+                            Some(fail(
+                                format!(
+                                    "{} at {:#05x} in frame {}",
+                                    err, cpu.program_counter, frames
+                                ),
+                                ExitStatus::EmulationError,
+                            ))
+                        }
+                        Outcome::Halted | Outcome::FramesElapsed => {
+                            frames += count;
+                            None
+                        }
+                    }
+                }
+                Step::Reset => {
+                    cpu.reset();
+                    None
+                }
+                Step::PowerCycle => {
+                    cpu.power_cycle();
+                    None
+                }
+                Step::Expect(Expect::Hash(expected)) => {
+                    let hash = cpu.display.hash();
+                    (hash != *expected).then(|| {
+                        fail(
+                            format!(
+                                "expected {:016x} in frame {}, got {:016x}",
+                                expected, frames, hash
+                            ),
+                            ExitStatus::CheckFailed,
+                        )
+                    })
+                }
+                Step::Expect(Expect::Halt) => (!cpu.halted).then(|| {
+                    fail(
+                        format!("still running in frame {}", frames),
+                        ExitStatus::Timeout,
+                    )
+                }),
+                Step::Expect(Expect::Pass) => match &cpu.assertion {
+                    Some(assertion) if assertion.passed => None,
+                    Some(assertion) => {
+                        Some(fail(assertion.to_string(), ExitStatus::AssertionFailed))
+                    }
+                    None => Some(fail(
+                        format!("no assertion reported by frame {}", frames),
+                        ExitStatus::CheckFailed,
+                    )),
+                },
+            };
+
+            if failure.is_some() {
+                return Report {
+                    cpu,
+                    frames,
+                    failure,
+                };
+            }
+        }
+
+        Report {
+            cpu,
+            frames,
+            failure: None,
+        }
+    }
+}
@@ -0,0 +1,817 @@
+//! Scripts that watch and steer a running machine, for cheats, automated
+//! checks and tool assisted play without changing the emulator. Built with
+//! the `scripting` feature.
+//!
+//! A script is a list of handlers, each run when something happens:
+//!
+//! ```text
+//! # infinite lives, the game keeps them at 0x3F0
+//! on frame
+//!     [0x3F0] = 3
+//! end
+//!
+//! on write 0x3F2
+//!     print "score", value
+//! end
+//!
+//! # hold 5 for the first second
+//! on frame
+//!     if frame < 60
+//!         press 5
+//!     else
+//!         release 5
+//!     end
+//! end
+//! ```
+//!
+//! `on frame` runs before every 60Hz frame, `on instruction` before every
+//! instruction (`on instruction <addr>` only before the one at that address)
+//! and `on write <addr> [<last addr>]` after the program wrote to that address
+//! or range, with `addr` and `value` set to where and what.
+//!
+//! Statements are assignments (`v3 = 1`, `[i + 2] = 0`, `lives = lives - 1`),
+//! `press` and `release` for keys, which stay pressed until released,
+//! `print` with strings and numbers, `assert`, which stops the script when
+//! its expression is 0, and `if ... else ... end`. Expressions have numbers,
+//! the registers `v0`-`vf`, `i`, `pc`, `dt` and `st`, memory as `[addr]`,
+//! `frame` (frames run so far), variables, and C's operators. Names are not
+//! case sensitive. Lines outside of handlers run once, before the first frame,
+//! and anything after a `#` is a comment.
+
+use std::fmt;
+
+use crate::cpu::Cpu;
+use crate::error::Error;
+use crate::memory::MEMORY_SIZE;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    Frame,
+    /// Before any instruction, or the one at this address.
+    Instruction(Option<usize>),
+    /// After a write to this range of addresses.
+    Write(usize, usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Name {
+    V(u8),
+    I,
+    Pc,
+    Dt,
+    St,
+    Frame,
+    Addr,
+    Value,
+    Variable(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Number(i64),
+    Read(Name),
+    Memory(Box<Expr>),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Place {
+    Name(Name),
+    Memory(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Arg {
+    Text(String),
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Statement {
+    Assign(Place, Expr),
+    Press(Expr),
+    Release(Expr),
+    Print(Vec<Arg>),
+    /// The expression and its source, for the message.
+    Assert(Expr, String),
+    If(Expr, Vec<Line>, Vec<Line>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Line {
+    number: usize,
+    statement: Statement,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Handler {
+    event: Event,
+    body: Vec<Line>,
+}
+
+/// What the statements can change besides the machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct State {
+    variables: Vec<i64>,
+    held: [bool; 16],
+    frame: u64,
+    output: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script {
+    setup: Vec<Line>,
+    handlers: Vec<Handler>,
+    state: State,
+    started: bool,
+    failure: Option<ScriptError>,
+}
+
+impl Script {
+    pub fn parse(source: &str) -> Result<Script, ScriptError> {
+        Parser::default().parse(source)
+    }
+
+    /// Runs a frame like `Cpu::run_frame`, calling the handlers along the
+    /// way. After a handler failed the machine runs on without the script.
+    pub fn run_frame(&mut self, cpu: &mut Cpu) -> Result<(), Error> {
+        if cpu.paused || self.failure.is_some() {
+            return cpu.run_frame();
+        }
+        let _span = cpu.frame_span();
+        if !self.started {
+            self.started = true;
+            cpu.memory.log_writes = self
+                .handlers
+                .iter()
+                .any(|handler| matches!(handler.event, Event::Write(..)));
+            if let Err(err) = run(&self.setup, &mut self.state, cpu, None) {
+                self.failure = Some(err);
+            }
+        }
+
+        self.trigger(cpu, |event| event == Event::Frame, None);
+        for (key, &held) in self.state.held.iter().enumerate() {
+            cpu.keys[key] |= held;
+        }
+        let every_instruction = self
+            .handlers
+            .iter()
+            .any(|handler| matches!(handler.event, Event::Instruction(_)));
+        for _ in 0..cpu.speed {
+            // a collision break ends the frame early
+            if cpu.halted || cpu.paused {
+                break;
+            }
+            if every_instruction {
+                let pc = cpu.program_counter;
+                self.trigger(
+                    cpu,
+                    |event| {
+                        matches!(event, Event::Instruction(None))
+                            || event == Event::Instruction(Some(pc))
+                    },
+                    None,
+                );
+            }
+            cpu.step()?;
+            for (addr, value) in cpu.memory.take_writes() {
+                self.trigger(
+                    cpu,
+                    |event| matches!(event, Event::Write(first, last) if (first..=last).contains(&addr)),
+                    Some((addr, value)),
+                );
+            }
+        }
+        cpu.tick_timers();
+        self.state.frame += 1;
+        Ok(())
+    }
+
+    /// Takes the lines printed so far.
+    pub fn take_output(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.state.output)
+    }
+
+    /// Why the script stopped, if it did.
+    pub fn failure(&self) -> Option<&ScriptError> {
+        self.failure.as_ref()
+    }
+
+    fn trigger(
+        &mut self,
+        cpu: &mut Cpu,
+        wanted: impl Fn(Event) -> bool,
+        write: Option<(usize, u8)>,
+    ) {
+        for handler in &self.handlers {
+            if self.failure.is_some() {
+                return;
+            }
+            if wanted(handler.event) {
+                if let Err(err) = run(&handler.body, &mut self.state, cpu, write) {
+                    self.failure = Some(err);
+                }
+            }
+        }
+    }
+}
+
+fn run(
+    lines: &[Line],
+    state: &mut State,
+    cpu: &mut Cpu,
+    write: Option<(usize, u8)>,
+) -> Result<(), ScriptError> {
+    for line in lines {
+        let error = |message: String| ScriptError {
+            line: line.number,
+            message,
+        };
+        let eval =
+            |expr: &Expr, state: &State, cpu: &Cpu| eval(expr, state, cpu, write).map_err(error);
+        match &line.statement {
+            Statement::Assign(place, expr) => {
+                let value = eval(expr, state, cpu)?;
+                match place {
+                    Place::Name(Name::V(x)) => cpu.registers[*x as usize] = value as u8,
+                    Place::Name(Name::I) => cpu.index = value as u16,
+                    Place::Name(Name::Pc) => cpu.program_counter = address(value).map_err(error)?,
+                    Place::Name(Name::Dt) => cpu.delay_timer = value as u8,
+                    Place::Name(Name::St) => cpu.sound_timer = value as u8,
+                    Place::Name(Name::Variable(n)) => state.variables[*n] = value,
+                    // the parser only lets the above through
+                    Place::Name(_) => unreachable!(),
+                    Place::Memory(addr) => {
+                        let addr = address(eval(addr, state, cpu)?).map_err(error)?;
+                        // like the debugger, past write protection and the
+                        // write handlers
+                        cpu.memory
+                            .load(addr, &[value as u8])
+                            .map_err(|err| error(err.to_string()))?;
+                    }
+                }
+            }
+            Statement::Press(key) | Statement::Release(key) => {
+                let key = eval(key, state, cpu)?;
+                let key = usize::try_from(key)
+                    .ok()
+                    .filter(|&key| key < 16)
+                    .ok_or_else(|| error(format!("invalid key {}", key)))?;
+                // held keys are pressed again every frame, in case the
+                // frontend sets the keys from the keyboard
+                let pressed = matches!(line.statement, Statement::Press(_));
+                state.held[key] = pressed;
+                cpu.keys[key] = pressed;
+            }
+            Statement::Print(args) => {
+                let mut words = Vec::new();
+                for arg in args {
+                    match arg {
+                        Arg::Text(text) => words.push(text.clone()),
+                        Arg::Expr(expr) => words.push(eval(expr, state, cpu)?.to_string()),
+                    }
+                }
+                state.output.push(words.join(" "));
+            }
+            Statement::Assert(expr, source) => {
+                if eval(expr, state, cpu)? == 0 {
+                    return Err(error(format!("assertion failed: {}", source)));
+                }
+            }
+            Statement::If(condition, then, otherwise) => {
+                let branch = if eval(condition, state, cpu)? != 0 {
+                    then
+                } else {
+                    otherwise
+                };
+                run(branch, state, cpu, write)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn eval(expr: &Expr, state: &State, cpu: &Cpu, write: Option<(usize, u8)>) -> Result<i64, String> {
+    let eval = |expr: &Expr| eval(expr, state, cpu, write);
+    Ok(match expr {
+        Expr::Number(value) => *value,
+        Expr::Read(name) => match *name {
+            Name::V(x) => cpu.registers[x as usize] as i64,
+            Name::I => cpu.index as i64,
+            Name::Pc => cpu.program_counter as i64,
+            Name::Dt => cpu.delay_timer as i64,
+            Name::St => cpu.sound_timer as i64,
+            Name::Frame => state.frame as i64,
+            Name::Addr => write.map_or(0, |(addr, _)| addr as i64),
+            Name::Value => write.map_or(0, |(_, value)| value as i64),
+            Name::Variable(n) => state.variables[n],
+        },
+        Expr::Memory(addr) => cpu
+            .memory
+            .read_byte(address(eval(addr)?)?)
+            .map_err(|err| err.to_string())? as i64,
+        Expr::Unary(op, operand) => {
+            let operand = eval(operand)?;
+            match *op {
+                "-" => operand.wrapping_neg(),
+                _ => (operand == 0) as i64,
+            }
+        }
+        Expr::Binary(op, left, right) => {
+            let left = eval(left)?;
+            // the right side of && and || only counts if it has to
+            match *op {
+                "&&" => return Ok((left != 0 && eval(right)? != 0) as i64),
+                "||" => return Ok((left != 0 || eval(right)? != 0) as i64),
+                _ => {}
+            }
+            let right = eval(right)?;
+            match *op {
+                "+" => left.wrapping_add(right),
+                "-" => left.wrapping_sub(right),
+                "*" => left.wrapping_mul(right),
+                "/" => left.checked_div(right).ok_or("division by zero")?,
+                "%" => left.checked_rem(right).ok_or("division by zero")?,
+                "&" => left & right,
+                "|" => left | right,
+                "^" => left ^ right,
+                "<<" => left.wrapping_shl(right as u32),
+                ">>" => left.wrapping_shr(right as u32),
+                "==" => (left == right) as i64,
+                "!=" => (left != right) as i64,
+                "<" => (left < right) as i64,
+                "<=" => (left <= right) as i64,
+                ">" => (left > right) as i64,
+                _ => (left >= right) as i64,
+            }
+        }
+    })
+}
+
+fn address(value: i64) -> Result<usize, String> {
+    usize::try_from(value)
+        .ok()
+        .filter(|&addr| addr < MEMORY_SIZE)
+        .ok_or_else(|| format!("address {:#x} is out of bounds", value))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Word(String),
+    Text(String),
+    Op(&'static str),
+}
+
+/// Longer operators first, so `<=` is not read as `<` and `=`.
+const OPERATORS: [&str; 25] = [
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "&", "|", "^", "<",
+    ">", "!", "=", "(", ")", "[", "]", ",",
+];
+
+/// Binary operators and how tightly they bind.
+const BINARY: [(&str, u8); 18] = [
+    ("||", 1),
+    ("&&", 2),
+    ("==", 3),
+    ("!=", 3),
+    ("<", 4),
+    ("<=", 4),
+    (">", 4),
+    (">=", 4),
+    ("|", 5),
+    ("^", 6),
+    ("&", 7),
+    ("<<", 8),
+    (">>", 8),
+    ("+", 9),
+    ("-", 9),
+    ("*", 10),
+    ("/", 10),
+    ("%", 10),
+];
+
+const KEYWORDS: [&str; 8] = [
+    "on", "end", "if", "else", "press", "release", "print", "assert",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    loop {
+        rest = rest.trim_start();
+        let Some(c) = rest.chars().next() else {
+            break;
+        };
+        if c == '#' {
+            break;
+        } else if c == '"' {
+            let end = rest[1..].find('"').ok_or("unterminated string")?;
+            tokens.push(Token::Text(rest[1..1 + end].to_string()));
+            rest = &rest[end + 2..];
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            let word = rest[..end].to_ascii_lowercase();
+            tokens.push(if c.is_ascii_digit() {
+                Token::Number(number(&word)?)
+            } else {
+                Token::Word(word)
+            });
+            rest = &rest[end..];
+        } else {
+            let op = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(**op))
+                .ok_or_else(|| format!("unexpected {:?}", c))?;
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        }
+    }
+    Ok(tokens)
+}
+
+fn number(word: &str) -> Result<i64, String> {
+    let digits = word.replace('_', "");
+    let parsed = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2)
+    } else {
+        digits.parse()
+    };
+    parsed.map_err(|_| format!("invalid number {}", word))
+}
+
+/// A block waiting for its `end`.
+struct Open {
+    line: usize,
+    kind: OpenKind,
+    body: Vec<Line>,
+}
+
+enum OpenKind {
+    On(Event),
+    If(Expr),
+    Else(Expr, Vec<Line>),
+}
+
+#[derive(Default)]
+struct Parser {
+    variables: Vec<String>,
+    /// Whether each variable is set anywhere, and the line it first appears on.
+    assigned: Vec<bool>,
+    first_seen: Vec<usize>,
+    line: usize,
+    /// Inside an `on write` handler, where `addr` and `value` mean something.
+    in_write: bool,
+}
+
+impl Parser {
+    fn parse(mut self, source: &str) -> Result<Script, ScriptError> {
+        let mut setup = Vec::new();
+        let mut handlers = Vec::new();
+        let mut open: Vec<Open> = Vec::new();
+
+        for (number, text) in source.lines().enumerate() {
+            let line = number + 1;
+            self.line = line;
+            let error = |message: String| ScriptError { line, message };
+            let tokens = tokenize(text).map_err(error)?;
+            let Some(first) = tokens.first() else {
+                continue;
+            };
+            let word = match first {
+                Token::Word(word) => word.as_str(),
+                _ => "",
+            };
+            let mut rest = Tokens {
+                tokens: &tokens[1..],
+                pos: 0,
+            };
+
+            let statement = match word {
+                "on" => {
+                    if !open.is_empty() {
+                        return Err(error("handlers can't be nested".to_string()));
+                    }
+                    let event = self.event(&mut rest).map_err(error)?;
+                    self.in_write = matches!(event, Event::Write(..));
+                    open.push(Open {
+                        line,
+                        kind: OpenKind::On(event),
+                        body: Vec::new(),
+                    });
+                    continue;
+                }
+                "if" => {
+                    let condition = self.expr(&mut rest).map_err(error)?;
+                    rest.finish().map_err(error)?;
+                    open.push(Open {
+                        line,
+                        kind: OpenKind::If(condition),
+                        body: Vec::new(),
+                    });
+                    continue;
+                }
+                "else" => {
+                    rest.finish().map_err(error)?;
+                    match open.pop() {
+                        Some(Open {
+                            kind: OpenKind::If(condition),
+                            body,
+                            ..
+                        }) => open.push(Open {
+                            line,
+                            kind: OpenKind::Else(condition, body),
+                            body: Vec::new(),
+                        }),
+                        _ => return Err(error("else without if".to_string())),
+                    }
+                    continue;
+                }
+                "end" => {
+                    rest.finish().map_err(error)?;
+                    let block = open
+                        .pop()
+                        .ok_or_else(|| error("end without a block".to_string()))?;
+                    let statement = match block.kind {
+                        OpenKind::On(event) => {
+                            handlers.push(Handler {
+                                event,
+                                body: block.body,
+                            });
+                            self.in_write = false;
+                            continue;
+                        }
+                        OpenKind::If(condition) => Statement::If(condition, block.body, Vec::new()),
+                        OpenKind::Else(condition, then) => {
+                            Statement::If(condition, then, block.body)
+                        }
+                    };
+                    Line {
+                        number: block.line,
+                        statement,
+                    }
+                }
+                _ => Line {
+                    number: line,
+                    statement: self.statement(word, text, &tokens).map_err(error)?,
+                },
+            };
+            match open.last_mut() {
+                Some(block) => block.body.push(statement),
+                None => setup.push(statement),
+            }
+        }
+
+        if let Some(block) = open.last() {
+            return Err(ScriptError {
+                line: block.line,
+                message: "missing end".to_string(),
+            });
+        }
+        for (n, name) in self.variables.iter().enumerate() {
+            if !self.assigned[n] {
+                return Err(ScriptError {
+                    line: self.first_seen[n],
+                    message: format!("{} is never set", name),
+                });
+            }
+        }
+
+        Ok(Script {
+            setup,
+            handlers,
+            state: State {
+                variables: vec![0; self.variables.len()],
+                held: [false; 16],
+                frame: 0,
+                output: Vec::new(),
+            },
+            started: false,
+            failure: None,
+        })
+    }
+
+    fn event(&mut self, tokens: &mut Tokens) -> Result<Event, String> {
+        let event = match tokens.next() {
+            Some(Token::Word(word)) if word == "frame" => Event::Frame,
+            Some(Token::Word(word)) if word == "instruction" => match tokens.peek() {
+                None => Event::Instruction(None),
+                Some(_) => Event::Instruction(Some(self.address(tokens)?)),
+            },
+            Some(Token::Word(word)) if word == "write" => {
+                let first = self.address(tokens)?;
+                let last = match tokens.peek() {
+                    None => first,
+                    Some(_) => self.address(tokens)?,
+                };
+                if last < first {
+                    return Err(format!("{:#05x} comes before {:#05x}", last, first));
+                }
+                Event::Write(first, last)
+            }
+            _ => return Err("expected on frame, on instruction or on write".to_string()),
+        };
+        tokens.finish()?;
+        Ok(event)
+    }
+
+    fn address(&mut self, tokens: &mut Tokens) -> Result<usize, String> {
+        match tokens.next() {
+            Some(Token::Number(value)) => address(*value),
+            _ => Err("expected an address".to_string()),
+        }
+    }
+
+    fn statement(&mut self, word: &str, text: &str, tokens: &[Token]) -> Result<Statement, String> {
+        let mut rest = Tokens {
+            tokens: &tokens[1..],
+            pos: 0,
+        };
+        let statement = match word {
+            "press" => Statement::Press(self.expr(&mut rest)?),
+            "release" => Statement::Release(self.expr(&mut rest)?),
+            "assert" => {
+                let source = text.split('#').next().unwrap_or("").trim();
+                let source = source[source.find(' ').unwrap_or(source.len())..].trim();
+                Statement::Assert(self.expr(&mut rest)?, source.to_string())
+            }
+            "print" => {
+                let mut args = Vec::new();
+                while rest.peek().is_some() {
+                    if !args.is_empty() {
+                        rest.expect(",")?;
+                    }
+                    args.push(match rest.peek() {
+                        Some(Token::Text(text)) => {
+                            let text = text.clone();
+                            rest.next();
+                            Arg::Text(text)
+                        }
+                        _ => Arg::Expr(self.expr(&mut rest)?),
+                    });
+                }
+                Statement::Print(args)
+            }
+            _ => {
+                let mut all = Tokens { tokens, pos: 0 };
+                let place = match all.next() {
+                    Some(Token::Op("[")) => {
+                        let addr = self.expr(&mut all)?;
+                        all.expect("]")?;
+                        Place::Memory(addr)
+                    }
+                    Some(Token::Word(word)) => Place::Name(self.place(word)?),
+                    _ => return Err("expected a statement".to_string()),
+                };
+                all.expect("=")?;
+                let value = self.expr(&mut all)?;
+                all.finish()?;
+                return Ok(Statement::Assign(place, value));
+            }
+        };
+        rest.finish()?;
+        Ok(statement)
+    }
+
+    fn place(&mut self, word: &str) -> Result<Name, String> {
+        match self.name(word)? {
+            Name::Frame | Name::Addr | Name::Value => Err(format!("{} can't be changed", word)),
+            Name::Variable(n) => {
+                self.assigned[n] = true;
+                Ok(Name::Variable(n))
+            }
+            name => Ok(name),
+        }
+    }
+
+    fn name(&mut self, word: &str) -> Result<Name, String> {
+        let register = word
+            .strip_prefix('v')
+            .filter(|digit| digit.len() == 1)
+            .and_then(|digit| u8::from_str_radix(digit, 16).ok());
+        if let Some(x) = register {
+            return Ok(Name::V(x));
+        }
+        match word {
+            "i" => Ok(Name::I),
+            "pc" => Ok(Name::Pc),
+            "dt" => Ok(Name::Dt),
+            "st" => Ok(Name::St),
+            "frame" => Ok(Name::Frame),
+            "addr" | "value" if !self.in_write => {
+                Err(format!("{} is only set in on write handlers", word))
+            }
+            "addr" => Ok(Name::Addr),
+            "value" => Ok(Name::Value),
+            _ if KEYWORDS.contains(&word) => Err(format!("{} is a keyword", word)),
+            _ => {
+                let n = match self.variables.iter().position(|name| name == word) {
+                    Some(n) => n,
+                    None => {
+                        self.variables.push(word.to_string());
+                        self.assigned.push(false);
+                        self.first_seen.push(self.line);
+                        self.variables.len() - 1
+                    }
+                };
+                Ok(Name::Variable(n))
+            }
+        }
+    }
+
+    fn expr(&mut self, tokens: &mut Tokens) -> Result<Expr, String> {
+        self.binary(tokens, 0)
+    }
+
+    /// Precedence climbing: operands joined by operators binding at least
+    /// as tightly as `min`.
+    fn binary(&mut self, tokens: &mut Tokens, min: u8) -> Result<Expr, String> {
+        let mut left = self.operand(tokens)?;
+        while let Some(Token::Op(op)) = tokens.peek() {
+            let Some(&(op, precedence)) = BINARY.iter().find(|(name, _)| name == op) else {
+                break;
+            };
+            if precedence < min {
+                break;
+            }
+            tokens.next();
+            let right = self.binary(tokens, precedence + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn operand(&mut self, tokens: &mut Tokens) -> Result<Expr, String> {
+        match tokens.next() {
+            Some(Token::Number(value)) => Ok(Expr::Number(*value)),
+            Some(Token::Word(word)) => Ok(Expr::Read(self.name(word)?)),
+            Some(Token::Op("(")) => {
+                let inner = self.expr(tokens)?;
+                tokens.expect(")")?;
+                Ok(inner)
+            }
+            Some(Token::Op("[")) => {
+                let addr = self.expr(tokens)?;
+                tokens.expect("]")?;
+                Ok(Expr::Memory(Box::new(addr)))
+            }
+            Some(Token::Op(op @ ("-" | "!"))) => {
+                Ok(Expr::Unary(op, Box::new(self.operand(tokens)?)))
+            }
+            Some(Token::Text(_)) => Err("strings only work in print".to_string()),
+            Some(Token::Op(op)) => Err(format!("unexpected {}", op)),
+            None => Err("expected an expression".to_string()),
+        }
+    }
+}
+
+struct Tokens<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Op(found)) if *found == op => Ok(()),
+            _ => Err(format!("expected {}", op)),
+        }
+    }
+
+    fn finish(&self) -> Result<(), String> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err("unexpected text at the end of the line".to_string()),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Spotting self-modifying code. With `Cpu::self_modify` set, every byte the
+//! program writes (Fx33, Fx55) is remembered with the instruction that wrote
+//! it, and running an opcode that includes such a byte is a hit. Plenty of
+//! old ROMs do this on purpose, but in new code it is usually a stray I, so
+//! the summary says which code changed and what changed it:
+//!
+//! ```text
+//! 0x2a0-0x2a3 written by 0x21e, ran 12 times
+//! ```
+//!
+//! The cached engine would notice the new opcodes by itself; with this on,
+//! writes also drop the entries they touch right away.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[derive(Debug, Default)]
+pub struct SelfModify {
+    /// The instruction that last wrote each address.
+    writers: BTreeMap<usize, usize>,
+    /// How often each address ran, by (address, writer), after the writer
+    /// changed it.
+    hits: BTreeMap<(usize, usize), u64>,
+}
+
+impl SelfModify {
+    /// The instruction at `pc` wrote `addr`.
+    pub fn record_write(&mut self, addr: usize, pc: usize) {
+        self.writers.insert(addr, pc);
+    }
+
+    /// The opcode at `pc` is about to run. Gives its writer the first time it
+    /// runs after being written.
+    pub fn record_execute(&mut self, pc: usize) -> Option<usize> {
+        let writer = self
+            .writers
+            .get(&pc)
+            .or_else(|| self.writers.get(&(pc + 1)))?;
+        let times = self.hits.entry((pc, *writer)).or_default();
+        *times += 1;
+        (*times == 1).then_some(*writer)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hits.is_empty()
+    }
+
+    /// Every hit as (address, writer, times run), by address.
+    pub fn hits(&self) -> impl Iterator<Item = (usize, usize, u64)> + '_ {
+        self.hits
+            .iter()
+            .map(|(&(addr, writer), &times)| (addr, writer, times))
+    }
+
+    /// One line per stretch of code with the same writer, see above.
+    pub fn summary(&self) -> Vec<String> {
+        // (first opcode, last opcode, writer, times)
+        let mut ranges: Vec<(usize, usize, usize, u64)> = Vec::new();
+        let mut by_writer: Vec<(usize, usize, u64)> = self.hits().collect();
+        by_writer.sort_by_key(|&(addr, writer, _)| (writer, addr));
+        for (addr, writer, times) in by_writer {
+            match ranges.last_mut() {
+                Some((_, last, same, total)) if *same == writer && addr <= *last + 2 => {
+                    *last = addr;
+                    *total += times;
+                }
+                _ => ranges.push((addr, addr, writer, times)),
+            }
+        }
+        ranges.sort();
+        ranges
+            .into_iter()
+            .map(|(first, last, writer, times)| {
+                format!(
+                    "{:#05x}-{:#05x} written by {:#05x}, ran {} times",
+                    first,
+                    last + 1,
+                    writer,
+                    times
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,149 @@
+//! A log of play sessions for exhibitions and studies, to see how a kiosk is
+//! used or how participants played. `--session-log <file>` appends one JSON
+//! object a line:
+//!
+//! ```text
+//! {"time":1760443200.125,"session":1760443190042,"event":"rom_loaded","rom":"pong.ch8",...}
+//! ```
+//!
+//! `time` is seconds since the Unix epoch and `session` is the same for
+//! everything one `chip8` process played, ROMs picked from the browser
+//! included. The events:
+//!
+//! * `rom_loaded`: `rom`, `sha1`, `title` when the database knows it, `platform`
+//! * `frames`: `frames` played so far, once a minute of play
+//! * `state_saved`, `state_loaded`, `reset`, `power_cycle`, `rewind`,
+//!   `screenshot`: `result`, the message the player saw
+//! * `halted`, `exited`: the ROM stopped itself, with `frames`
+//! * `error`: `message`, the emulation error that ended the game, or why the
+//!   frontend couldn't start
+//! * `rom_ended`: `frames`, `seconds`, `how` (quit, menu, load or error)
+//!
+//! Writing is best effort: the game goes on if the file can't be written, and
+//! the first problem is reported once it ends.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::json::Value;
+use crate::sha1;
+
+/// Frames between `frames` events, a minute.
+pub const FRAMES_EVENT: u64 = 60 * 60;
+
+pub struct SessionLog {
+    file: File,
+    frames: u64,
+    failed: Option<io::Error>,
+}
+
+impl SessionLog {
+    /// Appends to `path`, creating it if need be.
+    pub fn open(path: &Path) -> io::Result<SessionLog> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(SessionLog {
+            file,
+            frames: 0,
+            failed: None,
+        })
+    }
+
+    /// Frames played since it was opened.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Call once per frame played.
+    pub fn frame(&mut self) {
+        self.frames += 1;
+        if self.frames.is_multiple_of(FRAMES_EVENT) {
+            self.event("frames", &[("frames", number(self.frames))]);
+        }
+    }
+
+    /// The `rom_loaded` event for the ROM `name`, made of `bytes`.
+    pub fn rom_loaded(&mut self, name: &str, bytes: &[u8], platform: &str, title: Option<&str>) {
+        let mut fields = vec![
+            ("rom", text(name)),
+            ("sha1", text(sha1::hex(bytes))),
+            ("platform", text(platform)),
+        ];
+        if let Some(title) = title {
+            fields.push(("title", text(title)));
+        }
+        self.event("rom_loaded", &fields);
+    }
+
+    pub fn event(&mut self, event: &str, fields: &[(&str, Value)]) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut line = format!(
+            "{{\"time\":{:.3},\"session\":{},\"event\":{}",
+            now.as_secs_f64(),
+            session(),
+            Value::String(event.to_string())
+        );
+        for (key, value) in fields {
+            line.push_str(&format!(",{}:{}", Value::String(key.to_string()), value));
+        }
+        line.push_str("}\n");
+        // a line at a time, so nothing is lost if the kiosk is switched off
+        if let Err(err) = self.file.write_all(line.as_bytes()) {
+            self.failed.get_or_insert(err);
+        }
+    }
+
+    /// The first write that failed, if any did.
+    pub fn failed(&self) -> Option<&io::Error> {
+        self.failed.as_ref()
+    }
+}
+
+/// When this process started logging, in milliseconds since the epoch.
+fn session() -> u128 {
+    static SESSION: OnceLock<u128> = OnceLock::new();
+    *SESSION.get_or_init(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis()
+    })
+}
+
+/// A text field, for the `fields` of an event.
+pub fn text(value: impl Into<String>) -> Value {
+    Value::String(value.into())
+}
+
+pub fn number(value: u64) -> Value {
+    Value::Number(value as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn rom_loaded_hashes_the_rom() {
+        let path = std::env::temp_dir().join(format!("chip8-session-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut log = SessionLog::open(&path).unwrap();
+        log.rom_loaded("abc.ch8", b"abc", "vip", Some("ABC"));
+        let line = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let event = json::parse(line.trim_end()).unwrap();
+        let field = |name| event.get(name).and_then(Value::as_str);
+        assert_eq!(field("event"), Some("rom_loaded"));
+        assert_eq!(
+            field("sha1"),
+            Some("a9993e364706816aba3e25717850c26c9cd0d89d")
+        );
+        assert_eq!(field("title"), Some("ABC"));
+    }
+}
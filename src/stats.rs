@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const STATS_FILE: &str = "stats.tsv";
+
+/// Directory where the emulator keeps its per-user data.
+///
+/// `CHIP8_DATA_DIR` wins, then `$XDG_DATA_HOME/chip8`, then `~/.local/share/chip8`.
+pub fn data_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("CHIP8_DATA_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    if let Some(dir) = env::var_os("XDG_DATA_HOME") {
+        return Some(PathBuf::from(dir).join("chip8"));
+    }
+    if let Some(dir) = env::var_os("APPDATA") {
+        return Some(PathBuf::from(dir).join("chip8"));
+    }
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share/chip8"))
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RomStats {
+    pub launches: u32,
+    pub playtime: Duration,
+    pub last_played: Option<SystemTime>,
+}
+
+/// Per-ROM launch count, playtime and last-played date, keyed by ROM name.
+#[derive(Debug, Default)]
+pub struct Stats {
+    path: PathBuf,
+    roms: BTreeMap<String, RomStats>,
+}
+
+impl Stats {
+    /// Loads the stats file in the data directory, or starts empty if there is none yet.
+    pub fn load_default() -> io::Result<Stats> {
+        let dir = data_dir()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+        Stats::load(dir.join(STATS_FILE))
+    }
+
+    pub fn load(path: impl Into<PathBuf>) -> io::Result<Stats> {
+        let path = path.into();
+        let mut roms = BTreeMap::new();
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+
+        // name \t launches \t playtime secs \t last played (unix secs, 0 = never)
+        for line in contents.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 4 {
+                continue;
+            }
+            let (Ok(launches), Ok(playtime), Ok(last_played)) = (
+                fields[1].parse::<u32>(),
+                fields[2].parse::<u64>(),
+                fields[3].parse::<u64>(),
+            ) else {
+                continue;
+            };
+
+            roms.insert(
+                fields[0].to_string(),
+                RomStats {
+                    launches,
+                    playtime: Duration::from_secs(playtime),
+                    last_played: (last_played != 0)
+                        .then(|| UNIX_EPOCH + Duration::from_secs(last_played)),
+                },
+            );
+        }
+
+        Ok(Stats { path, roms })
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut contents = String::new();
+        for (name, rom) in &self.roms {
+            let last_played = rom
+                .last_played
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since| since.as_secs());
+            contents.push_str(&format!(
+                "{}\t{}\t{}\t{}\n",
+                name,
+                rom.launches,
+                rom.playtime.as_secs(),
+                last_played
+            ));
+        }
+
+        fs::write(&self.path, contents)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self, rom: &str) -> Option<&RomStats> {
+        self.roms.get(rom)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &RomStats)> {
+        self.roms.iter().map(|(name, rom)| (name.as_str(), rom))
+    }
+
+    /// Counts a launch of `rom` and marks it as played now.
+    pub fn record_launch(&mut self, rom: &str) {
+        let entry = self.roms.entry(sanitize(rom)).or_default();
+        entry.launches += 1;
+        entry.last_played = Some(SystemTime::now());
+    }
+
+    pub fn add_playtime(&mut self, rom: &str, played: Duration) {
+        let entry = self.roms.entry(sanitize(rom)).or_default();
+        entry.playtime += played;
+        entry.last_played = Some(SystemTime::now());
+    }
+}
+
+/// Tabs and newlines would break the file format.
+fn sanitize(rom: &str) -> String {
+    rom.replace(['\t', '\n', '\r'], " ")
+}
+
+/// Formats a point in time as `YYYY-MM-DD` (UTC).
+pub fn format_date(time: SystemTime) -> String {
+    let days = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() / 86_400) as i64;
+
+    // days since 1970-01-01 to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Formats a playtime as `1h 02m` / `5m 07s`.
+pub fn format_playtime(playtime: Duration) -> String {
+    let secs = playtime.as_secs();
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, secs % 3600 / 60)
+    } else {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    }
+}
@@ -0,0 +1,129 @@
+//! The homebrew project written by `chip8 new`: a sample program in the
+//! assembler's syntax, a build script and a test scenario that already passes.
+
+use crate::asm;
+use crate::headless;
+
+/// Frames the generated scenario waits before and while holding a key.
+const TEST_FRAMES: [usize; 2] = [30, 20];
+
+pub struct File {
+    /// Relative to the project directory.
+    pub path: String,
+    pub contents: String,
+    pub executable: bool,
+}
+
+const MAIN: &str = r#"; {name}: move the smiley around with W A S D (keypad 5 7 8 9)
+
+start:
+    LD V0, 28           ; x
+    LD V1, 12           ; y
+    LD I, smiley
+    DRW V0, V1, 8
+
+loop:
+    LD V3, 2            ; move every other frame
+    LD DT, V3
+wait:
+    LD V3, DT
+    SE V3, 0
+    JP wait
+
+    LD V4, V0           ; the new position
+    LD V5, V1
+    LD V2, 5
+    SKNP V2
+    ADD V5, 255         ; adding 255 subtracts one
+    LD V2, 8
+    SKNP V2
+    ADD V5, 1
+    LD V2, 7
+    SKNP V2
+    ADD V4, 255
+    LD V2, 9
+    SKNP V2
+    ADD V4, 1
+
+    SE V4, V0
+    JP move
+    SE V5, V1
+    JP move
+    JP loop
+move:
+    DRW V0, V1, 8       ; drawing it again erases it
+    LD V0, V4
+    LD V1, V5
+    DRW V0, V1, 8
+    JP loop
+
+smiley:
+    DB 0b00111100
+    DB 0b01000010
+    DB 0b10100101
+    DB 0b10000001
+    DB 0b10100101
+    DB 0b10011001
+    DB 0b01000010
+    DB 0b00111100
+"#;
+
+const BUILD: &str = r#"#!/bin/sh
+# Builds {name}.ch8 and runs its tests. While working on it,
+#   chip8 asm src/main.asm --out {name}.ch8 --watch --run
+# reloads the game every time src/main.asm is saved.
+set -e
+cd "$(dirname "$0")"
+chip8 asm src/main.asm --out {name}.ch8
+chip8 test . --junit tests/results.xml
+"#;
+
+const SCENARIO: &str = r#"# `chip8 test .` plays every tests/*.scenario against src/main.asm, see the
+# chip8 README. A failing expect prints the hash it got instead.
+wait {wait}
+expect {start:016x}     # the smiley in the middle
+press 9
+wait {hold}
+release 9
+expect {moved:016x}     # and moved right
+"#;
+
+/// The files of a new project called `name`.
+pub fn project(name: &str) -> Vec<File> {
+    let main = MAIN.replace("{name}", name);
+    let rom = asm::assemble(&main).expect("the sample program assembles");
+    let start = headless::run_rom(&rom, TEST_FRAMES[0]).expect("the sample program loads");
+    let start_hash = start.cpu.display.hash();
+    let moved = headless::resume(start.cpu, TEST_FRAMES[1], |cpu| cpu.keys[9] = true);
+    let scenario = SCENARIO
+        .replace("{wait}", &TEST_FRAMES[0].to_string())
+        .replace("{hold}", &TEST_FRAMES[1].to_string())
+        .replace("{start:016x}", &format!("{:016x}", start_hash))
+        .replace(
+            "{moved:016x}",
+            &format!("{:016x}", moved.cpu.display.hash()),
+        );
+
+    vec![
+        File {
+            path: "src/main.asm".to_string(),
+            contents: main,
+            executable: false,
+        },
+        File {
+            path: "build.sh".to_string(),
+            contents: BUILD.replace("{name}", name),
+            executable: true,
+        },
+        File {
+            path: "tests/moves.scenario".to_string(),
+            contents: scenario,
+            executable: false,
+        },
+        File {
+            path: ".gitignore".to_string(),
+            contents: "*.ch8\ntests/results.xml\n".to_string(),
+            executable: false,
+        },
+    ]
+}
@@ -0,0 +1,221 @@
+//! Helpers for testing ROMs, or the emulator, from Rust: set a machine up
+//! from assembly, run it until something happens and check registers and
+//! pixels, with failures that show the whole machine rather than two numbers.
+//!
+//! ```
+//! use chip_8_emulate::{assert_pixel, assert_reg, testing};
+//!
+//! let mut cpu = testing::assembled("LD V0, 5\nLD F, V0\nDRW V1, V1, 5\nwait: JP wait");
+//! testing::run_until(&mut cpu, |cpu| cpu.program_counter == 0x206);
+//! assert_reg!(cpu, V0 == 5);
+//! assert_reg!(cpu, I == 0x69);
+//! assert_pixel!(cpu, 0, 0, on);
+//! ```
+//!
+//! A failing check panics at the line of the check:
+//!
+//! ```text
+//! assert_reg!(V0 == 0x06) failed: V0 is 0x05 (5)
+//! pc 0x206  JP 0x206
+//! I 0x069  sp 0  DT 0x00  ST 0x00
+//! V0-V7 05 00 00 00 00 00 00 00
+//! V8-VF 00 00 00 00 00 00 00 00
+//! ```
+
+use std::fmt::Write;
+
+use crate::asm;
+use crate::cpu::Cpu;
+use crate::display::{HEIGHT, WIDTH};
+use crate::headless;
+use crate::instruction::Instruction;
+
+/// Instructions `run_until` gives up after, a minute at the default speed.
+pub const RUN_LIMIT: usize = 60 * 60 * 10;
+
+/// A `headless::machine` with `rom` loaded.
+#[track_caller]
+pub fn machine(rom: &[u8]) -> Cpu {
+    let mut cpu = headless::machine();
+    if let Err(err) = cpu.load_rom(rom) {
+        panic!("loading the ROM failed: {}", err);
+    }
+    cpu
+}
+
+/// A `machine` running `source`, assembled.
+#[track_caller]
+pub fn assembled(source: &str) -> Cpu {
+    match asm::assemble(source) {
+        Ok(rom) => machine(&rom),
+        Err(err) => panic!("assembling failed: {}", err),
+    }
+}
+
+/// Steps `cpu` until `done` holds, ticking the timers every `speed`
+/// instructions as frames would, and gives how many it ran. Panics if the
+/// machine fails, halts or runs `RUN_LIMIT` instructions first.
+#[track_caller]
+pub fn run_until(cpu: &mut Cpu, mut done: impl FnMut(&Cpu) -> bool) -> usize {
+    for steps in 0..RUN_LIMIT {
+        if done(cpu) {
+            return steps;
+        }
+        if cpu.halted {
+            panic!(
+                "run_until: halted after {} instructions\n{}",
+                steps,
+                describe(cpu)
+            );
+        }
+        if let Err(err) = cpu.step() {
+            panic!(
+                "run_until: {} after {} instructions\n{}",
+                err,
+                steps,
+                describe(cpu)
+            );
+        }
+        if (steps + 1) % cpu.speed.max(1) == 0 {
+            cpu.tick_timers();
+        }
+    }
+    if done(cpu) {
+        return RUN_LIMIT;
+    }
+    panic!(
+        "run_until: gave up after {} instructions\n{}",
+        RUN_LIMIT,
+        describe(cpu)
+    );
+}
+
+/// A register by the name `assert_reg!` takes: `V0` to `VF`, `I`, `PC`, `SP`,
+/// `DT` or `ST`.
+pub fn register(cpu: &Cpu, name: &str) -> Option<u16> {
+    let value = match name {
+        "I" => cpu.index,
+        "PC" => cpu.program_counter as u16,
+        "SP" => cpu.stack_pointer as u16,
+        "DT" => cpu.delay_timer as u16,
+        "ST" => cpu.sound_timer as u16,
+        _ => {
+            let digit = name.strip_prefix('V').filter(|digit| digit.len() == 1)?;
+            let v = u8::from_str_radix(digit, 16).ok()?;
+            cpu.registers[v as usize] as u16
+        }
+    };
+    Some(value)
+}
+
+/// What `assert_reg!` runs.
+#[track_caller]
+pub fn check_register(cpu: &Cpu, name: &str, expected: u16) {
+    let Some(actual) = register(cpu, name) else {
+        panic!("assert_reg!: there is no register {}", name);
+    };
+    if actual != expected {
+        panic!(
+            "assert_reg!({} == {:#04x}) failed: {} is {:#04x} ({})\n{}",
+            name,
+            expected,
+            name,
+            actual,
+            actual,
+            describe(cpu)
+        );
+    }
+}
+
+/// What `assert_pixel!` runs.
+#[track_caller]
+pub fn check_pixel(cpu: &Cpu, x: usize, y: usize, on: bool) {
+    if x >= WIDTH || y >= HEIGHT {
+        panic!(
+            "assert_pixel!: {},{} is off the {}x{} screen",
+            x, y, WIDTH, HEIGHT
+        );
+    }
+    if cpu.display.pixel(x, y) != on {
+        let state = |on| if on { "on" } else { "off" };
+        panic!(
+            "assert_pixel!({}, {}, {}) failed: it is {}, at the middle of\n{}",
+            x,
+            y,
+            state(on),
+            state(!on),
+            around(cpu, x, y)
+        );
+    }
+}
+
+/// The registers, short enough to read in a failed test.
+pub fn describe(cpu: &Cpu) -> String {
+    let pc = cpu.program_counter;
+    let instruction = match cpu.memory.read_word(pc) {
+        Ok(opcode) => match Instruction::decode(opcode) {
+            Ok(instruction) => instruction.to_string(),
+            Err(_) => format!("{:04x}", opcode),
+        },
+        Err(_) => String::new(),
+    };
+    let mut out = format!("pc {:#05x}  {}\n", pc, instruction);
+    let _ = writeln!(
+        out,
+        "I {:#05x}  sp {}  DT {:#04x}  ST {:#04x}",
+        cpu.index, cpu.stack_pointer, cpu.delay_timer, cpu.sound_timer
+    );
+    for (row, registers) in cpu.registers.chunks(8).enumerate() {
+        let values: Vec<String> = registers.iter().map(|v| format!("{:02x}", v)).collect();
+        let first = row * 8;
+        let _ = writeln!(out, "V{:X}-V{:X} {}", first, first + 7, values.join(" "));
+    }
+    if cpu.halted {
+        out.push_str("halted\n");
+    }
+    out
+}
+
+/// The screen around `x`,`y`, 9 rows of 17 pixels with that one in the
+/// middle in brackets, its column spaced out to match.
+fn around(cpu: &Cpu, x: usize, y: usize) -> String {
+    let mut out = String::new();
+    for row in y.saturating_sub(4)..(y + 5).min(HEIGHT) {
+        for column in x.saturating_sub(8)..(x + 9).min(WIDTH) {
+            let pixel = if cpu.display.pixel(column, row) {
+                '#'
+            } else {
+                '.'
+            };
+            if (column, row) == (x, y) {
+                let _ = write!(out, "[{}]", pixel);
+            } else if column == x {
+                let _ = write!(out, " {} ", pixel);
+            } else {
+                out.push(pixel);
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Checks a register, `assert_reg!(cpu, V0 == 45)`; see `testing::register`
+/// for the names.
+#[macro_export]
+macro_rules! assert_reg {
+    ($cpu:expr, $register:ident == $expected:expr) => {
+        $crate::testing::check_register(&$cpu, stringify!($register), ($expected) as u16)
+    };
+}
+
+/// Checks a pixel, `assert_pixel!(cpu, 10, 5, on)` or `off`.
+#[macro_export]
+macro_rules! assert_pixel {
+    ($cpu:expr, $x:expr, $y:expr, on) => {
+        $crate::testing::check_pixel(&$cpu, $x, $y, true)
+    };
+    ($cpu:expr, $x:expr, $y:expr, off) => {
+        $crate::testing::check_pixel(&$cpu, $x, $y, false)
+    };
+}
@@ -0,0 +1,79 @@
+use std::time::{Duration, Instant};
+
+/// Session time limit for exhibition kiosks: once it runs out the frontend
+/// pauses emulation and shows an overlay until an operator resets it.
+///
+/// Only time spent running counts, so pausing the game also pauses the limit.
+#[derive(Debug, Clone)]
+pub struct TimeLimit {
+    limit: Duration,
+    played: Duration,
+    running_since: Option<Instant>,
+}
+
+impl TimeLimit {
+    /// Creates a running time limit.
+    pub fn new(limit: Duration) -> TimeLimit {
+        TimeLimit {
+            limit,
+            played: Duration::ZERO,
+            running_since: Some(Instant::now()),
+        }
+    }
+
+    pub fn limit(&self) -> Duration {
+        self.limit
+    }
+
+    pub fn pause(&mut self) {
+        if let Some(since) = self.running_since.take() {
+            self.played += since.elapsed();
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if self.running_since.is_none() {
+            self.running_since = Some(Instant::now());
+        }
+    }
+
+    pub fn played(&self) -> Duration {
+        self.played
+            + self
+                .running_since
+                .map_or(Duration::ZERO, |since| since.elapsed())
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.limit.saturating_sub(self.played())
+    }
+
+    pub fn expired(&self) -> bool {
+        self.played() >= self.limit
+    }
+
+    /// Starts a fresh session, e.g. for the next player.
+    pub fn reset(&mut self) {
+        self.played = Duration::ZERO;
+        self.running_since = Some(Instant::now());
+    }
+}
+
+/// Parses durations like `90`, `90s`, `15m` or `1h`. A bare number is seconds.
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let (number, unit) = match text.find(|c: char| !c.is_ascii_digit()) {
+        Some(split) => text.split_at(split),
+        None => (text, "s"),
+    };
+
+    let number: u64 = number.parse().ok()?;
+    let secs = match unit {
+        "s" => number,
+        "m" => number.checked_mul(60)?,
+        "h" => number.checked_mul(3600)?,
+        _ => return None,
+    };
+
+    Some(Duration::from_secs(secs))
+}
@@ -0,0 +1,36 @@
+//! Noticing when a file is saved, by polling its modification time. Polling
+//! a quarter of a second apart is plenty for a file someone is editing.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+pub struct Watcher {
+    path: PathBuf,
+    seen: Option<(SystemTime, u64)>,
+}
+
+impl Watcher {
+    /// Starts watching `path`, whether or not it exists yet.
+    pub fn new(path: &Path) -> Watcher {
+        let mut watcher = Watcher {
+            path: path.to_path_buf(),
+            seen: None,
+        };
+        watcher.changed();
+        watcher
+    }
+
+    /// True once after every write to the file.
+    pub fn changed(&mut self) -> bool {
+        let current = fs::metadata(&self.path)
+            .and_then(|meta| Ok((meta.modified()?, meta.len())))
+            .ok();
+        if current == self.seen {
+            return false;
+        }
+        self.seen = current;
+        // a deleted file is not something to reload
+        current.is_some()
+    }
+}
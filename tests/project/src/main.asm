@@ -0,0 +1,5 @@
+; waits for a key, draws it and halts
+    LD V0, K
+    LD F, V0
+    DRW V1, V1, 5
+    DW 0x0000